@@ -67,4 +67,30 @@ impl CacheService {
         info!("Invalidated auth session for user {}", email);
         Ok(())
     }
+
+    #[cfg(feature = "auth")]
+    /// Добавить JWT в список отозванных до истечения его срока действия.
+    /// `SET NX` атомарен: возвращает `true` только вызову, который отозвал
+    /// токен первым, и `false`, если он уже был отозван.
+    pub async fn revoke_jwt(&self, jti: &str, ttl_seconds: u64) -> Result<bool, redis::RedisError> {
+        let key = format!("jwt:revoked:{}", jti);
+        let mut conn = self.redis.conn.clone();
+        let result: Option<String> = redis::cmd("SET")
+            .arg(&key)
+            .arg(1)
+            .arg("NX")
+            .arg("PX")
+            .arg(ttl_seconds.max(1) * 1000)
+            .query_async(&mut conn)
+            .await?;
+        Ok(result.is_some())
+    }
+
+    #[cfg(feature = "auth")]
+    /// Проверить, отозван ли JWT
+    pub async fn is_jwt_revoked(&self, jti: &str) -> Result<bool, redis::RedisError> {
+        let key = format!("jwt:revoked:{}", jti);
+        let mut conn = self.redis.conn.clone();
+        conn.exists(key).await
+    }
 }
\ No newline at end of file
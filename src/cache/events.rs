@@ -1,6 +1,7 @@
 use crate::cache::CacheService;
-use crate::models::Event;
+use crate::models::{Event, EventStatus};
 use redis::AsyncCommands;
+use tracing::info;
 
 impl CacheService {
     // Получить события
@@ -18,15 +19,26 @@ impl CacheService {
         
         vec![]
     }
-    
+
+    // Инвалидировать список событий и все результаты поиска событий
+    // (поиск переходит в новое поколение, см. `cache::search`).
+    pub async fn invalidate_events(&self) -> Result<(), redis::RedisError> {
+        let mut conn = self.redis.conn.clone();
+        let _: () = conn.del("events").await?;
+        self.invalidate_search().await?;
+
+        info!("Invalidated events cache");
+        Ok(())
+    }
 
     async fn load_events_from_db(&self) -> Result<Vec<Event>, sqlx::Error> {
         sqlx::query_as::<_, Event>(
-            "SELECT id, title, description, type as event_type, datetime_start, provider 
-             FROM events_archive 
-             WHERE datetime_start > NOW()
+            "SELECT id, title, description, type as event_type, datetime_start, provider, venue_id, status
+             FROM events_upcoming
+             WHERE datetime_start > NOW() AND status = $1
              ORDER BY datetime_start"
         )
+        .bind(EventStatus::Published)
         .fetch_all(&self.db.pool)
         .await
     }
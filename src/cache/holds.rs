@@ -0,0 +1,189 @@
+use crate::cache::CacheService;
+use tracing::warn;
+
+/// Сколько живет удержание места в Redis.
+pub const SEAT_HOLD_TTL_SECONDS: u64 = 300;
+
+/// Sorted set с индексом удержаний: член - ID места, score - момент истечения в мс.
+/// По нему фоновая задача находит истекшие удержания, не перебирая ключи.
+pub const SEAT_HOLDS_INDEX_KEY: &str = "seat_holds";
+
+/// Ключ удержания места.
+pub fn seat_hold_key(seat_id: i64) -> String {
+    format!("seat:{}:reserved", seat_id)
+}
+
+/// Удержание и запись в индекс одной операцией.
+/// Время берется из Redis (`TIME`), как и в остальных скриптах.
+const HOLD_SCRIPT: &str = r#"
+if not redis.call('SET', KEYS[1], ARGV[1], 'NX', 'EX', ARGV[2]) then
+    return 0
+end
+local t = redis.call('TIME')
+local now = tonumber(t[1]) * 1000 + math.floor(tonumber(t[2]) / 1000)
+redis.call('ZADD', KEYS[2], now + tonumber(ARGV[2]) * 1000, ARGV[3])
+return 1
+"#;
+
+/// Удерживает все места или ни одного. KEYS[1] - индекс, KEYS[2..] - ключи
+/// удержаний; ARGV[1] - пользователь, ARGV[2] - TTL, ARGV[3..] - ID мест.
+/// Возвращает ID уже удерживаемых мест; пустой список - удержание получено.
+const HOLD_BATCH_SCRIPT: &str = r#"
+local conflicts = {}
+for i = 2, #KEYS do
+    if redis.call('EXISTS', KEYS[i]) == 1 then
+        table.insert(conflicts, ARGV[i + 1])
+    end
+end
+if #conflicts > 0 then
+    return conflicts
+end
+local t = redis.call('TIME')
+local now = tonumber(t[1]) * 1000 + math.floor(tonumber(t[2]) / 1000)
+local expires = now + tonumber(ARGV[2]) * 1000
+for i = 2, #KEYS do
+    redis.call('SET', KEYS[i], ARGV[1], 'EX', ARGV[2])
+    redis.call('ZADD', KEYS[1], expires, ARGV[i + 1])
+end
+return conflicts
+"#;
+
+/// Места, срок удержания которых по индексу наступил. KEYS[1] - индекс, ARGV[1] - лимит.
+const DUE_HOLDS_SCRIPT: &str = r#"
+local t = redis.call('TIME')
+local now = tonumber(t[1]) * 1000 + math.floor(tonumber(t[2]) / 1000)
+return redis.call('ZRANGEBYSCORE', KEYS[1], '-inf', now, 'LIMIT', 0, tonumber(ARGV[1]))
+"#;
+
+/// Забирает из индекса места, удержание которых истекло, и возвращает их ID.
+/// KEYS[1] - индекс, KEYS[2..] - ключи удержаний; ARGV[1] - срок по умолчанию
+/// в мс, ARGV[2..] - ID мест. Если ключ удержания еще жив (место заняли
+/// повторно), запись переносится на новый срок. Забранные записи удаляются,
+/// поэтому каждое место достается только одному вызывающему.
+const CLAIM_EXPIRED_SCRIPT: &str = r#"
+local t = redis.call('TIME')
+local now = tonumber(t[1]) * 1000 + math.floor(tonumber(t[2]) / 1000)
+local expired = {}
+for i = 2, #KEYS do
+    local id = ARGV[i]
+    local due = redis.call('ZSCORE', KEYS[1], id)
+    if due and tonumber(due) <= now then
+        local ttl = redis.call('PTTL', KEYS[i])
+        if ttl == -2 then
+            redis.call('ZREM', KEYS[1], id)
+            table.insert(expired, id)
+        else
+            if ttl < 0 then
+                ttl = tonumber(ARGV[1])
+            end
+            redis.call('ZADD', KEYS[1], now + ttl, id)
+        end
+    end
+end
+return expired
+"#;
+
+impl CacheService {
+    /// Атомарно удерживает место за пользователем на `SEAT_HOLD_TTL_SECONDS`.
+    pub async fn reserve_seat(&self, seat_id: i64, user_id: i32) -> bool {
+        let mut conn = self.redis.conn.clone();
+        let result: Result<i64, _> = redis::Script::new(HOLD_SCRIPT)
+            .key(seat_hold_key(seat_id))
+            .key(SEAT_HOLDS_INDEX_KEY)
+            .arg(user_id)
+            .arg(SEAT_HOLD_TTL_SECONDS)
+            .arg(seat_id)
+            .invoke_async(&mut conn)
+            .await;
+
+        matches!(result, Ok(1))
+    }
+
+    /// Атомарно удерживает все места за пользователем. Если хотя бы одно место
+    /// уже удерживается, не удерживает ни одного и возвращает занятые места.
+    pub async fn reserve_seats(&self, seat_ids: &[i64], user_id: i32) -> Result<Vec<i64>, redis::RedisError> {
+        let mut conn = self.redis.conn.clone();
+        let script = redis::Script::new(HOLD_BATCH_SCRIPT);
+        let mut invocation = script.key(SEAT_HOLDS_INDEX_KEY);
+        for seat_id in seat_ids {
+            invocation.key(seat_hold_key(*seat_id));
+        }
+        invocation.arg(user_id).arg(SEAT_HOLD_TTL_SECONDS);
+        for seat_id in seat_ids {
+            invocation.arg(*seat_id);
+        }
+        invocation.invoke_async(&mut conn).await
+    }
+
+    /// Снимает удержания мест и убирает их из индекса. Ошибки только логируются:
+    /// ключи все равно истекут, а индекс дочистит фоновая задача.
+    pub async fn release_seat_holds(&self, seat_ids: &[i64]) {
+        if seat_ids.is_empty() {
+            return;
+        }
+
+        let mut conn = self.redis.conn.clone();
+        let mut pipe = redis::pipe();
+        pipe.atomic();
+        for seat_id in seat_ids {
+            pipe.del(seat_hold_key(*seat_id)).ignore();
+        }
+        pipe.zrem(SEAT_HOLDS_INDEX_KEY, seat_ids).ignore();
+
+        if let Err(e) = pipe.query_async::<()>(&mut conn).await {
+            warn!("Failed to release {} seat holds: {:?}", seat_ids.len(), e);
+        }
+    }
+
+    /// Забирает до `limit` мест с истекшим удержанием (см. `CLAIM_EXPIRED_SCRIPT`).
+    /// Ключи удержаний строятся здесь, через `seat_hold_key`, и передаются
+    /// скрипту в `KEYS`.
+    pub async fn claim_expired_holds(&self, limit: usize) -> Result<Vec<i64>, redis::RedisError> {
+        let mut conn = self.redis.conn.clone();
+        let due: Vec<i64> = redis::Script::new(DUE_HOLDS_SCRIPT)
+            .key(SEAT_HOLDS_INDEX_KEY)
+            .arg(limit)
+            .invoke_async(&mut conn)
+            .await?;
+        if due.is_empty() {
+            return Ok(due);
+        }
+
+        let script = redis::Script::new(CLAIM_EXPIRED_SCRIPT);
+        let mut invocation = script.key(SEAT_HOLDS_INDEX_KEY);
+        for seat_id in &due {
+            invocation.key(seat_hold_key(*seat_id));
+        }
+        invocation.arg(SEAT_HOLD_TTL_SECONDS * 1000);
+        for seat_id in &due {
+            invocation.arg(*seat_id);
+        }
+        invocation.invoke_async(&mut conn).await
+    }
+
+    /// Сколько записей индекса уже просрочено и ждет обработки.
+    pub async fn count_expired_holds(&self) -> Result<i64, redis::RedisError> {
+        let mut conn = self.redis.conn.clone();
+        let now = chrono::Utc::now().timestamp_millis();
+        redis::cmd("ZCOUNT")
+            .arg(SEAT_HOLDS_INDEX_KEY)
+            .arg("-inf")
+            .arg(now)
+            .query_async(&mut conn)
+            .await
+    }
+
+    /// Для каждого места сообщает, есть ли у него живое удержание.
+    pub async fn seat_holds_exist(&self, seat_ids: &[i64]) -> Result<Vec<bool>, redis::RedisError> {
+        if seat_ids.is_empty() {
+            return Ok(vec![]);
+        }
+
+        let mut conn = self.redis.conn.clone();
+        let mut pipe = redis::pipe();
+        for seat_id in seat_ids {
+            pipe.exists(seat_hold_key(*seat_id));
+        }
+        pipe.query_async(&mut conn).await
+    }
+}
@@ -0,0 +1,92 @@
+use crate::cache::CacheService;
+use crate::models::{SeatPosition, Section, Venue};
+use redis::AsyncCommands;
+use serde::{Deserialize, Serialize};
+
+/// Схема зала события: площадка, секции и координаты мест.
+/// Меняется редко, поэтому кешируется отдельно от статусов мест.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SeatLayout {
+    pub venue: Option<Venue>,
+    pub sections: Vec<Section>,
+    pub positions: Vec<SeatPosition>,
+}
+
+impl CacheService {
+    // Получить схему зала события
+    pub async fn get_seat_layout(&self, event_id: i64) -> Result<SeatLayout, sqlx::Error> {
+        if let Ok(layout) = self.get_seat_layout_from_cache(event_id).await {
+            return Ok(layout);
+        }
+
+        let layout = self.load_seat_layout_from_db(event_id).await?;
+        let _ = self.save_seat_layout_to_cache(event_id, &layout).await;
+        Ok(layout)
+    }
+
+    // Инвалидировать кеш схемы зала
+    pub async fn invalidate_seat_layout(&self, event_id: i64) {
+        let key = format!("seat_layout:{}", event_id);
+        let mut conn = self.redis.conn.clone();
+        let _: Result<(), _> = conn.del(&key).await;
+    }
+
+    // === Работа с БД ===
+    async fn load_seat_layout_from_db(&self, event_id: i64) -> Result<SeatLayout, sqlx::Error> {
+        let venue = sqlx::query_as::<_, Venue>(
+            "SELECT v.id, v.name, v.address, v.width, v.height
+             FROM venues v
+             JOIN events_archive e ON e.venue_id = v.id
+             WHERE e.id = $1"
+        )
+        .bind(event_id)
+        .fetch_optional(&self.db.pool)
+        .await?;
+
+        let Some(venue) = venue else {
+            return Ok(SeatLayout::default());
+        };
+
+        let sections = sqlx::query_as::<_, Section>(
+            "SELECT id, venue_id, name, tier, sort_order
+             FROM sections
+             WHERE venue_id = $1
+             ORDER BY sort_order, id"
+        )
+        .bind(venue.id)
+        .fetch_all(&self.db.pool)
+        .await?;
+
+        let positions = sqlx::query_as::<_, SeatPosition>(
+            "SELECT s.id AS seat_id, l.x, l.y
+             FROM seats s
+             JOIN seat_layouts l
+               ON l.section_id = s.section_id AND l.row = s.row AND l.number = s.number
+             WHERE s.event_id = $1"
+        )
+        .bind(event_id)
+        .fetch_all(&self.db.pool)
+        .await?;
+
+        Ok(SeatLayout { venue: Some(venue), sections, positions })
+    }
+
+    // === Работа с кешем ===
+    async fn get_seat_layout_from_cache(&self, event_id: i64) -> Result<SeatLayout, redis::RedisError> {
+        let mut conn = self.redis.conn.clone();
+        let key = format!("seat_layout:{}", event_id);
+        let data: String = conn.get(key).await?;
+        serde_json::from_str(&data).map_err(|_| {
+            redis::RedisError::from((redis::ErrorKind::TypeError, "Parse error"))
+        })
+    }
+
+    async fn save_seat_layout_to_cache(&self, event_id: i64, layout: &SeatLayout) -> Result<(), redis::RedisError> {
+        let data = serde_json::to_string(layout).map_err(|_| {
+            redis::RedisError::from((redis::ErrorKind::TypeError, "Serialize error"))
+        })?;
+        let key = format!("seat_layout:{}", event_id);
+        let mut conn = self.redis.conn.clone();
+        conn.set_ex(key, data, 3600).await // 1 час
+    }
+}
@@ -0,0 +1,54 @@
+use crate::cache::CacheService;
+
+/// Захватить блокировку, если она свободна, или продлить, если уже наша.
+const ACQUIRE_OR_RENEW_SCRIPT: &str = r#"
+local current = redis.call('GET', KEYS[1])
+if current == ARGV[1] then
+    redis.call('PEXPIRE', KEYS[1], ARGV[2])
+    return 1
+end
+if not current then
+    redis.call('SET', KEYS[1], ARGV[1], 'PX', ARGV[2])
+    return 1
+end
+return 0
+"#;
+
+/// Снять блокировку, только если она принадлежит нам.
+const RELEASE_SCRIPT: &str = r#"
+if redis.call('GET', KEYS[1]) == ARGV[1] then
+    return redis.call('DEL', KEYS[1])
+end
+return 0
+"#;
+
+impl CacheService {
+    /// Захватывает или продлевает лидерскую блокировку для `owner`.
+    /// Возвращает `true`, если после вызова блокировка принадлежит `owner`.
+    pub async fn acquire_leader_lock(
+        &self,
+        key: &str,
+        owner: &str,
+        ttl_seconds: u64,
+    ) -> Result<bool, redis::RedisError> {
+        let mut conn = self.redis.conn.clone();
+        let acquired: i64 = redis::Script::new(ACQUIRE_OR_RENEW_SCRIPT)
+            .key(key)
+            .arg(owner)
+            .arg(ttl_seconds.max(1) * 1000)
+            .invoke_async(&mut conn)
+            .await?;
+        Ok(acquired == 1)
+    }
+
+    /// Освобождает лидерскую блокировку, если ее держит `owner`.
+    pub async fn release_leader_lock(&self, key: &str, owner: &str) -> Result<(), redis::RedisError> {
+        let mut conn = self.redis.conn.clone();
+        let _: i64 = redis::Script::new(RELEASE_SCRIPT)
+            .key(key)
+            .arg(owner)
+            .invoke_async(&mut conn)
+            .await?;
+        Ok(())
+    }
+}
@@ -3,6 +3,12 @@ use tracing::info;
 
 pub mod auth;
 pub mod events;
+pub mod holds;
+pub mod layout;
+pub mod leader;
+#[cfg(feature = "rate-limiting")]
+pub mod rate_limit;
+pub mod recent_writes;
 pub mod search;
 pub mod seats;
 
@@ -0,0 +1,68 @@
+use crate::cache::CacheService;
+use uuid::Uuid;
+
+/// Скользящее окно на sorted set: score - время запроса в мс, член - уникальный ID запроса.
+/// Время берется из Redis (`TIME`), чтобы реплики приложения не зависели от своих часов.
+/// Возвращает `{allowed, remaining, reset_after_ms}`.
+const SLIDING_WINDOW_SCRIPT: &str = r#"
+local key = KEYS[1]
+local window = tonumber(ARGV[1])
+local limit = tonumber(ARGV[2])
+local member = ARGV[3]
+local t = redis.call('TIME')
+local now = tonumber(t[1]) * 1000 + math.floor(tonumber(t[2]) / 1000)
+
+redis.call('ZREMRANGEBYSCORE', key, 0, now - window)
+local count = redis.call('ZCARD', key)
+local allowed = 0
+if count < limit then
+    redis.call('ZADD', key, now, member)
+    redis.call('PEXPIRE', key, window)
+    allowed = 1
+    count = count + 1
+end
+
+local reset = window
+local oldest = redis.call('ZRANGE', key, 0, 0, 'WITHSCORES')
+if oldest[2] then
+    reset = tonumber(oldest[2]) + window - now
+end
+
+return {allowed, limit - count, reset}
+"#;
+
+/// Результат проверки лимита.
+#[derive(Debug, Clone, Copy)]
+pub struct RateLimitDecision {
+    pub allowed: bool,
+    pub limit: u32,
+    pub remaining: u32,
+    /// Через сколько миллисекунд освободится место в окне.
+    pub reset_after_ms: u64,
+}
+
+impl CacheService {
+    /// Учитывает запрос в скользящем окне и решает, пропускать ли его.
+    pub async fn check_rate_limit(
+        &self,
+        key: &str,
+        limit: u32,
+        window_seconds: u64,
+    ) -> Result<RateLimitDecision, redis::RedisError> {
+        let mut conn = self.redis.conn.clone();
+        let (allowed, remaining, reset_after_ms): (i64, i64, i64) = redis::Script::new(SLIDING_WINDOW_SCRIPT)
+            .key(key)
+            .arg(window_seconds * 1000)
+            .arg(limit)
+            .arg(Uuid::new_v4().to_string())
+            .invoke_async(&mut conn)
+            .await?;
+
+        Ok(RateLimitDecision {
+            allowed: allowed == 1,
+            limit,
+            remaining: remaining.max(0) as u32,
+            reset_after_ms: reset_after_ms.max(0) as u64,
+        })
+    }
+}
@@ -0,0 +1,84 @@
+//! Отметки недавних записей для чтения с реплик.
+//!
+//! Реплика может еще не содержать только что записанные данные. Пока отметка
+//! жива (`Database::read_your_writes_window`), чтения в той же области идут
+//! в основную БД. Отметки хранятся в Redis, чтобы их видели все реплики сервиса.
+//!
+//! Отметка ставится до записи, чтобы не было окна между фиксацией и отметкой,
+//! и снимается, если запрос отклонен (4xx). После 5xx отметка остается:
+//! запись могла зафиксироваться до ошибки. Значение ключа - число отмеченных
+//! записей: отклоненный запрос снимает только свою отметку, а не отметку
+//! параллельного успешного.
+
+use crate::cache::CacheService;
+use redis::AsyncCommands;
+use std::time::Duration;
+use tracing::warn;
+
+/// Область, в которой недавняя запись должна быть видна следующему чтению.
+#[derive(Debug, Clone, Copy)]
+pub enum WriteScope {
+    /// Брони и места, измененные пользователем.
+    User(i32),
+    /// Каталог событий (админка): списки и поиск.
+    Events,
+}
+
+impl WriteScope {
+    fn key(&self) -> String {
+        match self {
+            WriteScope::User(user_id) => format!("recent_write:user:{}", user_id),
+            WriteScope::Events => "recent_write:events".to_string(),
+        }
+    }
+}
+
+/// Снимает одну отметку. Истекший ключ не создается заново: без TTL он жил бы вечно.
+const UNMARK_SCRIPT: &str = r#"
+if redis.call('EXISTS', KEYS[1]) == 1 then
+    return redis.call('DECR', KEYS[1])
+end
+return 0
+"#;
+
+impl CacheService {
+    /// Отмечает запись в области на `window`.
+    pub async fn mark_recent_write(&self, scope: WriteScope, window: Duration) {
+        let mut conn = self.redis.conn.clone();
+        let ttl = window.as_millis().max(1) as i64;
+        let key = scope.key();
+        let result: Result<(), _> = redis::pipe()
+            .atomic()
+            .incr(&key, 1)
+            .ignore()
+            .pexpire(&key, ttl)
+            .ignore()
+            .query_async(&mut conn)
+            .await;
+        if let Err(e) = result {
+            warn!("Failed to mark recent write for {:?}: {:?}", scope, e);
+        }
+    }
+
+    /// Снимает отметку отклоненного запроса, который ничего не записал.
+    pub async fn unmark_recent_write(&self, scope: WriteScope) {
+        let mut conn = self.redis.conn.clone();
+        let result: Result<i64, _> = redis::Script::new(UNMARK_SCRIPT)
+            .key(scope.key())
+            .invoke_async(&mut conn)
+            .await;
+        if let Err(e) = result {
+            warn!("Failed to unmark recent write for {:?}: {:?}", scope, e);
+        }
+    }
+
+    /// Была ли недавно запись в области. Если Redis недоступен, считаем, что была:
+    /// лишнее чтение из основной БД лучше, чем устаревшие данные.
+    pub async fn has_recent_write(&self, scope: WriteScope) -> bool {
+        let mut conn = self.redis.conn.clone();
+        match conn.get::<_, Option<i64>>(scope.key()).await {
+            Ok(marks) => marks.is_some_and(|marks| marks > 0),
+            Err(_) => true,
+        }
+    }
+}
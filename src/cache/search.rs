@@ -1,21 +1,148 @@
+//! Кеш результатов поиска событий.
+//!
+//! Ключ записи - `search:events:v{поколение}:{вид}:{sha256 параметров}`.
+//! Параметры нормализуются (`SearchCacheKey`), поэтому "Rock" и "rock "
+//! попадают в одну запись.
+//!
+//! Сброс без `KEYS`/`SCAN`:
+//! - изменение каталога (создание, правка, перенос, смена статуса события)
+//!   увеличивает поколение: старые записи больше не читаются и истекают по TTL;
+//! - изменение одного события, которое не меняет состав выдачи (распродажа),
+//!   удаляет только записи, где событие встречается: при сохранении ключ
+//!   записи добавляется в тег `search:events:tag:{event_id}`.
+
 use crate::cache::CacheService;
 use redis::AsyncCommands;
+use sha2::{Digest, Sha256};
+use tracing::info;
+
+/// Префикс ключей с закешированными результатами поиска событий.
+pub const SEARCH_EVENTS_PREFIX: &str = "search:events:";
+
+/// Текущее поколение записей поиска.
+const SEARCH_GENERATION_KEY: &str = "search:events:generation";
+
+/// Наибольший TTL записи поиска. Теги живут столько же, чтобы короткая
+/// запись (подсказки) не сокращала жизнь тега с длинными записями.
+pub const SEARCH_RESULT_TTL_SECONDS: u64 = 3600;
+
+fn search_tag_key(event_id: i64) -> String {
+    format!("{}tag:{}", SEARCH_EVENTS_PREFIX, event_id)
+}
+
+/// Нормализует текст запроса для поиска и ключа кеша: нижний регистр,
+/// без пробелов по краям и с одиночными пробелами между словами.
+pub fn normalize_query(query: &str) -> String {
+    query.split_whitespace().collect::<Vec<_>>().join(" ").to_lowercase()
+}
+
+/// Параметры закешированного ответа поиска. Ключ не зависит от поколения:
+/// его подставляет `CacheService::resolve_search_key`.
+#[derive(Debug, Clone)]
+pub struct SearchCacheKey {
+    kind: &'static str,
+    params: String,
+}
+
+impl SearchCacheKey {
+    pub fn new(kind: &'static str) -> Self {
+        Self { kind, params: String::new() }
+    }
+
+    /// Добавляет параметр. Значения должны быть уже нормализованы вызывающим:
+    /// параметры с одинаковым смыслом должны давать одинаковую строку.
+    pub fn param(mut self, name: &str, value: impl std::fmt::Display) -> Self {
+        if !self.params.is_empty() {
+            self.params.push('&');
+        }
+        self.params.push_str(&format!("{}={}", name, value));
+        self
+    }
+
+    fn digest(&self) -> String {
+        let mut hasher = Sha256::new();
+        hasher.update(self.params.as_bytes());
+        format!("{:x}", hasher.finalize())
+    }
+}
 
 impl CacheService {
+    /// Ключ записи в текущем поколении. Сохранять результат нужно под этим же
+    /// ключом: если поколение сменится во время запроса, запись уйдет в старое
+    /// поколение и не будет прочитана.
+    pub async fn resolve_search_key(&self, key: &SearchCacheKey) -> Result<String, redis::RedisError> {
+        let mut conn = self.redis.conn.clone();
+        let generation: Option<u64> = conn.get(SEARCH_GENERATION_KEY).await?;
+        Ok(format!(
+            "{}v{}:{}:{}",
+            SEARCH_EVENTS_PREFIX,
+            generation.unwrap_or(0),
+            key.kind,
+            key.digest()
+        ))
+    }
+
     /// Получает закешированный результат поиска по ключу.
     pub async fn get_cached_search(&self, key: &str) -> Result<Option<String>, redis::RedisError> {
         let mut conn = self.redis.conn.clone();
         conn.get(key).await
     }
 
-    /// Сохраняет результат поиска в кеш с указанным TTL (в секундах).
+    /// Сохраняет результат поиска в кеш с указанным TTL (в секундах)
+    /// и отмечает его тегами событий из выдачи.
     pub async fn cache_search_result(
         &self,
         key: &str,
         value: &str,
         ttl_seconds: u64,
+        event_ids: &[i64],
     ) -> Result<(), redis::RedisError> {
         let mut conn = self.redis.conn.clone();
-        conn.set_ex(key, value, ttl_seconds).await
+        let mut pipe = redis::pipe();
+        pipe.atomic();
+        pipe.set_ex(key, value, ttl_seconds).ignore();
+        for event_id in event_ids {
+            let tag = search_tag_key(*event_id);
+            pipe.sadd(&tag, key).ignore();
+            pipe.expire(&tag, SEARCH_RESULT_TTL_SECONDS.max(ttl_seconds) as i64).ignore();
+        }
+        pipe.query_async(&mut conn).await
+    }
+
+    /// Сбрасывает все результаты поиска: следующее чтение идет в новое поколение.
+    pub async fn invalidate_search(&self) -> Result<(), redis::RedisError> {
+        let mut conn = self.redis.conn.clone();
+        let generation: u64 = conn.incr(SEARCH_GENERATION_KEY, 1).await?;
+        info!("Search cache moved to generation {}", generation);
+        Ok(())
+    }
+
+    /// Удаляет записи поиска, в выдаче которых есть событие.
+    pub async fn invalidate_event_search(&self, event_id: i64) -> Result<(), redis::RedisError> {
+        let mut conn = self.redis.conn.clone();
+        let tag = search_tag_key(event_id);
+
+        // Тег читается и удаляется в одной транзакции: запись, сохраненная
+        // после нее, попадет в новый тег и не потеряет отметку.
+        let (keys,): (Vec<String>,) = redis::pipe()
+            .atomic()
+            .smembers(&tag)
+            .del(&tag)
+            .ignore()
+            .query_async(&mut conn)
+            .await?;
+        if keys.is_empty() {
+            return Ok(());
+        }
+
+        // Каждая запись удаляется своей командой: ключи записей и тега могут
+        // лежать в разных слотах кластера.
+        let mut pipe = redis::pipe();
+        for key in &keys {
+            pipe.unlink(key).ignore();
+        }
+        pipe.query_async::<()>(&mut conn).await?;
+        info!("Invalidated {} search entries for event {}", keys.len(), event_id);
+        Ok(())
     }
-}
\ No newline at end of file
+}
@@ -1,5 +1,5 @@
-use crate::cache::CacheService;
-use crate::models::Seat;
+use crate::cache::{holds::seat_hold_key, CacheService};
+use crate::models::{Seat, SeatStatus};
 use redis::AsyncCommands;
 use tracing::info;
 
@@ -23,24 +23,6 @@ impl CacheService {
         vec![]
     }
 
-    // Атомарно резервировать место на 5 минут
-    pub async fn reserve_seat(&self, seat_id: i64, user_id: i32) -> bool {
-        let key = format!("seat:{}:reserved", seat_id);
-        let mut conn = self.redis.conn.clone();
-        
-        // SET NX EX - атомарная операция без гонок
-        let result: Result<String, _> = redis::cmd("SET")
-            .arg(&key)
-            .arg(user_id)
-            .arg("NX")  // только если ключа нет
-            .arg("EX")  // TTL в секундах
-            .arg(300)   // 5 минут
-            .query_async(&mut conn)
-            .await;
-            
-        result.is_ok()
-    }
-
     // Инвалидировать кеш мест
     pub async fn invalidate_seats(&self, event_id: i64) {
         let key = format!("seats:{}", event_id);
@@ -51,7 +33,7 @@ impl CacheService {
 
     // Проверить зарезервировано ли место пользователем
     pub async fn is_seat_reserved_by_user(&self, seat_id: i64, user_id: i32) -> bool {
-        let key = format!("seat:{}:reserved", seat_id);
+        let key = seat_hold_key(seat_id);
         let mut conn = self.redis.conn.clone();
         let reserved_user: Option<i32> = conn.get(&key).await.unwrap_or(None);
         reserved_user == Some(user_id)
@@ -60,10 +42,10 @@ impl CacheService {
     // === Работа с БД ===
     async fn load_seats_from_db(&self, event_id: i64) -> Result<Vec<Seat>, sqlx::Error> {
         sqlx::query_as::<_, Seat>(
-            "SELECT id, event_id, row, number, status, booking_id, category, price::FLOAT as price
+            "SELECT id, event_id, row, number, section_id, status, booking_id, category, price::FLOAT as price
              FROM seats 
              WHERE event_id = $1
-             ORDER BY row, number"
+             ORDER BY section_id, row, number"
         )
         .bind(event_id)
         .fetch_all(&self.db.pool)
@@ -96,9 +78,8 @@ impl CacheService {
         let mut pipe = redis::pipe();
 
         for seat in seats.iter() {
-            if seat.status == "FREE" {
-                let key = format!("seat:{}:reserved", seat.id);
-                pipe.exists(key);
+            if seat.status == SeatStatus::Free {
+                pipe.exists(seat_hold_key(seat.id));
             }
         }
 
@@ -109,10 +90,10 @@ impl CacheService {
 
         let mut reserved_iter = results.iter();
         for seat in seats.iter_mut() {
-            if seat.status == "FREE" {
+            if seat.status == SeatStatus::Free {
                 if let Some(is_reserved) = reserved_iter.next() {
                     if *is_reserved {
-                        seat.status = "SELECTED".to_string();
+                        seat.status = SeatStatus::Selected;
                     }
                 }
             }
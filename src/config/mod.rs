@@ -7,9 +7,17 @@ pub struct Config {
     pub app: AppConfig,
     pub database: DatabaseConfig,
     pub redis: RedisConfig,
+    #[cfg(feature = "auth")]
+    pub jwt: JwtConfig,
     pub payment: PaymentConfig,
     pub circuit_breaker: CircuitBreakerConfig,
     pub cache: CacheConfig,
+    pub cleanup: CleanupConfig,
+    pub admin: AdminConfig,
+    #[cfg(feature = "rate-limiting")]
+    pub rate_limit: RateLimitConfig,
+    #[cfg(feature = "search")]
+    pub search: SearchConfig,
 }
 
 // Настройки приложения
@@ -28,7 +36,24 @@ pub struct DatabaseConfig {
     pub user: String,
     pub password: String,
     pub db: String,
+    /// Размер пула основной БД.
     pub pool_size: u32,
+    /// Реплики только для чтения; пусто - все запросы идут в основную БД.
+    pub replica_urls: Vec<String>,
+    /// Размер пула каждой реплики.
+    pub replica_pool_size: u32,
+    /// Сколько ждать свободного соединения из пула.
+    pub acquire_timeout_ms: u64,
+    /// Через сколько закрывать простаивающее соединение.
+    pub idle_timeout_seconds: u64,
+    /// `statement_timeout` соединений основной БД; 0 - без ограничения.
+    pub statement_timeout_ms: u64,
+    /// `statement_timeout` соединений реплик; 0 - без ограничения.
+    pub replica_statement_timeout_ms: u64,
+    /// Реплика с большим отставанием не получает запросы.
+    pub replica_max_lag_ms: u64,
+    /// Как часто проверять доступность и отставание реплик.
+    pub replica_check_interval_seconds: u64,
 }
 
 // Настройки Redis
@@ -61,14 +86,140 @@ pub struct PaymentConfig {
 pub struct CircuitBreakerConfig {
     pub failure_threshold: u32,
     pub timeout_seconds: u64,
+    /// Сколько пробных запросов пропускается в HalfOpen.
+    pub half_open_max_requests: u32,
+    /// Хранить состояние в Redis, чтобы все реплики разделяли одно решение.
+    pub shared: bool,
 }
 
 // Настройки кэша
 #[derive(Debug, Clone, Deserialize)]
 pub struct CacheConfig {
     pub auth_ttl_seconds: Option<u64>,
+    /// Ключ HMAC отпечатка учетных данных в кеше авторизации. Без него каждый
+    /// процесс берет случайный ключ, и реплики не разделяют кеш.
+    pub auth_secret: Option<String>,
 }
 
+// Настройки фоновой очистки (CleanupService)
+#[derive(Debug, Clone, Deserialize)]
+pub struct CleanupConfig {
+    pub enabled: bool,
+    /// Как часто отменять просроченные платежи.
+    pub payments_interval_seconds: u64,
+    /// Как часто удалять пустые и зависшие бронирования.
+    pub bookings_interval_seconds: u64,
+    /// Как часто чистить осиротевшие резервы в Redis.
+    pub redis_reserves_interval_seconds: u64,
+    /// Как часто освобождать места с истекшим удержанием по индексу в Redis.
+    pub holds_interval_seconds: u64,
+    /// Как часто искать в БД зарезервированные места без удержания.
+    pub holds_sweep_interval_seconds: u64,
+    /// Как часто перестраивать `events_upcoming` (предстоящие события для списков и поиска).
+    pub upcoming_refresh_interval_seconds: u64,
+    /// Как часто проверять, что секции `events_archive` созданы заранее.
+    pub partitions_interval_seconds: u64,
+    /// На сколько лет вперед создавать секции `events_archive`.
+    pub partitions_ahead_years: u32,
+    /// Как часто обрабатывать очередную пачку фоновых переносов данных (`background_migrations`).
+    pub backfill_interval_seconds: u64,
+    /// Сколько строк обрабатывает одна пачка фонового переноса.
+    pub backfill_batch_size: i64,
+    /// TTL лидерской блокировки в Redis: очистку выполняет только одна реплика.
+    pub leader_lock_ttl_seconds: u64,
+}
+
+// Настройки администрирования
+#[derive(Debug, Clone, Deserialize)]
+pub struct AdminConfig {
+    /// Email пользователей, которым доступны `/api/admin/*`.
+    pub emails: Vec<String>,
+}
+
+// Настройки поиска событий
+#[cfg(feature = "search")]
+#[derive(Debug, Clone, Deserialize)]
+pub struct SearchConfig {
+    /// Конфигурации полнотекстового поиска Postgres, по которым ищется запрос.
+    /// Ранги по всем конфигурациям объединяются; `simple` - без стемминга
+    /// (казахский, транслит, имена).
+    pub languages: Vec<String>,
+    /// Искать также транслитерацию запроса (латиница <-> кириллица).
+    pub transliterate: bool,
+    /// Порог `word_similarity` для нечеткого поиска, когда полнотекстовый ничего не нашел.
+    pub fuzzy_threshold: f32,
+    /// Сколько совпадений считается точно; выше порога `total` - нижняя оценка.
+    pub count_threshold: i64,
+    pub highlight: HighlightConfig,
+}
+
+// Подсветка совпадений в `snippet` (параметры `ts_headline`)
+#[cfg(feature = "search")]
+#[derive(Debug, Clone, Deserialize)]
+pub struct HighlightConfig {
+    /// Маркеры начала и конца совпадения.
+    pub start_sel: String,
+    pub stop_sel: String,
+    /// Длина фрагмента в словах.
+    pub min_words: u32,
+    pub max_words: u32,
+}
+
+#[cfg(feature = "search")]
+impl HighlightConfig {
+    /// Строка опций `ts_headline`. Маркеры берутся в кавычки, поэтому могут
+    /// содержать запятые и пробелы, но не двойные кавычки.
+    pub fn options(&self) -> String {
+        format!(
+            "StartSel=\"{}\", StopSel=\"{}\", MinWords={}, MaxWords={}",
+            self.start_sel.replace('"', ""),
+            self.stop_sel.replace('"', ""),
+            self.min_words,
+            self.max_words
+        )
+    }
+}
+
+// Настройки rate limiting
+#[cfg(feature = "rate-limiting")]
+#[derive(Debug, Clone, Deserialize)]
+pub struct RateLimitConfig {
+    /// Публичные события (`GET /api/events`, `/api/events/search`, `/api/events/suggest`, `/api/events/{id}`).
+    pub events: RateLimitRule,
+    /// Выбор мест (`PATCH /api/seats/select`, `PATCH /api/seats/select-batch`).
+    pub seat_select: RateLimitRule,
+    /// Инициация оплаты (`PATCH /api/bookings/initiatePayment`).
+    pub payment_init: RateLimitRule,
+    /// Брать IP клиента из `X-Forwarded-For` / `X-Real-IP` (за балансировщиком).
+    pub trust_forwarded_for: bool,
+}
+
+// Лимит для группы маршрутов: не больше `limit` запросов за скользящее окно
+#[cfg(feature = "rate-limiting")]
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub struct RateLimitRule {
+    /// 0 - лимит для группы отключен.
+    pub limit: u32,
+    pub window_seconds: u64,
+}
+
+#[cfg(feature = "rate-limiting")]
+impl RateLimitRule {
+    fn from_env(prefix: &str, default_limit: u32, default_window_seconds: u64) -> Self {
+        let limit_var = format!("{}_LIMIT", prefix);
+        let window_var = format!("{}_WINDOW_SECONDS", prefix);
+        RateLimitRule {
+            limit: env::var(&limit_var)
+                .unwrap_or_else(|_| default_limit.to_string())
+                .parse()
+                .unwrap_or_else(|_| panic!("{} must be a valid number", limit_var)),
+            window_seconds: env::var(&window_var)
+                .unwrap_or_else(|_| default_window_seconds.to_string())
+                .parse()
+                .unwrap_or_else(|_| panic!("{} must be a valid number", window_var)),
+        }
+    }
+}
 
 impl Config {
     pub fn from_env() -> Self {
@@ -92,6 +243,41 @@ impl Config {
                     .unwrap_or_else(|_| "20".to_string())
                     .parse()
                     .expect("DB_POOL_SIZE must be a valid number"),
+                replica_urls: env::var("DATABASE_REPLICA_URLS")
+                    .unwrap_or_default()
+                    .split(',')
+                    .map(|s| s.trim().to_string())
+                    .filter(|s| !s.is_empty())
+                    .collect(),
+                replica_pool_size: env::var("DB_REPLICA_POOL_SIZE")
+                    .or_else(|_| env::var("DB_POOL_SIZE"))
+                    .unwrap_or_else(|_| "20".to_string())
+                    .parse()
+                    .expect("DB_REPLICA_POOL_SIZE must be a valid number"),
+                acquire_timeout_ms: env::var("DB_ACQUIRE_TIMEOUT_MS")
+                    .unwrap_or_else(|_| "1000".to_string())
+                    .parse()
+                    .expect("DB_ACQUIRE_TIMEOUT_MS must be a valid number"),
+                idle_timeout_seconds: env::var("DB_IDLE_TIMEOUT_SECONDS")
+                    .unwrap_or_else(|_| "300".to_string())
+                    .parse()
+                    .expect("DB_IDLE_TIMEOUT_SECONDS must be a valid number"),
+                statement_timeout_ms: env::var("DB_STATEMENT_TIMEOUT_MS")
+                    .unwrap_or_else(|_| "0".to_string())
+                    .parse()
+                    .expect("DB_STATEMENT_TIMEOUT_MS must be a valid number"),
+                replica_statement_timeout_ms: env::var("DB_REPLICA_STATEMENT_TIMEOUT_MS")
+                    .unwrap_or_else(|_| "0".to_string())
+                    .parse()
+                    .expect("DB_REPLICA_STATEMENT_TIMEOUT_MS must be a valid number"),
+                replica_max_lag_ms: env::var("DB_REPLICA_MAX_LAG_MS")
+                    .unwrap_or_else(|_| "5000".to_string())
+                    .parse()
+                    .expect("DB_REPLICA_MAX_LAG_MS must be a valid number"),
+                replica_check_interval_seconds: env::var("DB_REPLICA_CHECK_INTERVAL_SECONDS")
+                    .unwrap_or_else(|_| "5".to_string())
+                    .parse()
+                    .expect("DB_REPLICA_CHECK_INTERVAL_SECONDS must be a valid number"),
             },
             redis: RedisConfig {
                 url: env::var("REDIS_URL").expect("REDIS_URL must be set"),
@@ -100,6 +286,14 @@ impl Config {
                     .parse()
                     .expect("REDIS_POOL_SIZE must be a valid number"),
             },
+            #[cfg(feature = "auth")]
+            jwt: JwtConfig {
+                secret: env::var("JWT_SECRET").expect("JWT_SECRET must be set"),
+                expires_in_hours: env::var("JWT_EXPIRES_IN_HOURS")
+                    .unwrap_or_else(|_| "24".to_string())
+                    .parse()
+                    .expect("JWT_EXPIRES_IN_HOURS must be a valid number"),
+            },
             payment: PaymentConfig {
                 merchant_id: env::var("MERCHANT_ID").expect("MERCHANT_ID must be set"),
                 merchant_password: env::var("MERCHANT_PASSWORD").expect("MERCHANT_PASSWORD must be set"),
@@ -121,12 +315,120 @@ impl Config {
                     .unwrap_or_else(|_| "60".to_string())
                     .parse()
                     .expect("CIRCUIT_BREAKER_TIMEOUT_SECONDS must be a valid number"),
+                half_open_max_requests: env::var("CIRCUIT_BREAKER_HALF_OPEN_MAX_REQUESTS")
+                    .unwrap_or_else(|_| "1".to_string())
+                    .parse()
+                    .expect("CIRCUIT_BREAKER_HALF_OPEN_MAX_REQUESTS must be a valid number"),
+                shared: env::var("CIRCUIT_BREAKER_SHARED")
+                    .map(|v| v == "true" || v == "1")
+                    .unwrap_or(false),
             },
             cache: CacheConfig {
                 auth_ttl_seconds: env::var("CACHE_AUTH_TTL")
                     .ok()
                     .and_then(|s| s.parse().ok()),
-            }
+                auth_secret: env::var("CACHE_AUTH_SECRET")
+                    .ok()
+                    .filter(|s| !s.is_empty()),
+            },
+            cleanup: CleanupConfig {
+                enabled: env::var("CLEANUP_ENABLED")
+                    .map(|v| v == "true" || v == "1")
+                    .unwrap_or(true),
+                payments_interval_seconds: env::var("CLEANUP_PAYMENTS_INTERVAL_SECONDS")
+                    .unwrap_or_else(|_| "60".to_string())
+                    .parse()
+                    .expect("CLEANUP_PAYMENTS_INTERVAL_SECONDS must be a valid number"),
+                bookings_interval_seconds: env::var("CLEANUP_BOOKINGS_INTERVAL_SECONDS")
+                    .unwrap_or_else(|_| "300".to_string())
+                    .parse()
+                    .expect("CLEANUP_BOOKINGS_INTERVAL_SECONDS must be a valid number"),
+                redis_reserves_interval_seconds: env::var("CLEANUP_REDIS_RESERVES_INTERVAL_SECONDS")
+                    .unwrap_or_else(|_| "600".to_string())
+                    .parse()
+                    .expect("CLEANUP_REDIS_RESERVES_INTERVAL_SECONDS must be a valid number"),
+                holds_interval_seconds: env::var("CLEANUP_HOLDS_INTERVAL_SECONDS")
+                    .unwrap_or_else(|_| "5".to_string())
+                    .parse()
+                    .expect("CLEANUP_HOLDS_INTERVAL_SECONDS must be a valid number"),
+                holds_sweep_interval_seconds: env::var("CLEANUP_HOLDS_SWEEP_INTERVAL_SECONDS")
+                    .unwrap_or_else(|_| "60".to_string())
+                    .parse()
+                    .expect("CLEANUP_HOLDS_SWEEP_INTERVAL_SECONDS must be a valid number"),
+                upcoming_refresh_interval_seconds: env::var("CLEANUP_UPCOMING_REFRESH_INTERVAL_SECONDS")
+                    .unwrap_or_else(|_| "60".to_string())
+                    .parse()
+                    .expect("CLEANUP_UPCOMING_REFRESH_INTERVAL_SECONDS must be a valid number"),
+                partitions_interval_seconds: env::var("CLEANUP_PARTITIONS_INTERVAL_SECONDS")
+                    .unwrap_or_else(|_| "86400".to_string())
+                    .parse()
+                    .expect("CLEANUP_PARTITIONS_INTERVAL_SECONDS must be a valid number"),
+                partitions_ahead_years: env::var("CLEANUP_PARTITIONS_AHEAD_YEARS")
+                    .unwrap_or_else(|_| "2".to_string())
+                    .parse()
+                    .expect("CLEANUP_PARTITIONS_AHEAD_YEARS must be a valid number"),
+                backfill_interval_seconds: env::var("CLEANUP_BACKFILL_INTERVAL_SECONDS")
+                    .unwrap_or_else(|_| "1".to_string())
+                    .parse()
+                    .expect("CLEANUP_BACKFILL_INTERVAL_SECONDS must be a valid number"),
+                backfill_batch_size: env::var("CLEANUP_BACKFILL_BATCH_SIZE")
+                    .unwrap_or_else(|_| "5000".to_string())
+                    .parse()
+                    .expect("CLEANUP_BACKFILL_BATCH_SIZE must be a valid number"),
+                leader_lock_ttl_seconds: env::var("CLEANUP_LEADER_LOCK_TTL_SECONDS")
+                    .unwrap_or_else(|_| "60".to_string())
+                    .parse()
+                    .expect("CLEANUP_LEADER_LOCK_TTL_SECONDS must be a valid number"),
+            },
+            admin: AdminConfig {
+                emails: env::var("ADMIN_EMAILS")
+                    .unwrap_or_default()
+                    .split(',')
+                    .map(|s| s.trim().to_string())
+                    .filter(|s| !s.is_empty())
+                    .collect(),
+            },
+            #[cfg(feature = "rate-limiting")]
+            rate_limit: RateLimitConfig {
+                events: RateLimitRule::from_env("RATE_LIMIT_EVENTS", 120, 60),
+                seat_select: RateLimitRule::from_env("RATE_LIMIT_SEAT_SELECT", 30, 60),
+                payment_init: RateLimitRule::from_env("RATE_LIMIT_PAYMENT_INIT", 10, 60),
+                trust_forwarded_for: env::var("RATE_LIMIT_TRUST_FORWARDED_FOR")
+                    .map(|v| v == "true" || v == "1")
+                    .unwrap_or(false),
+            },
+            #[cfg(feature = "search")]
+            search: SearchConfig {
+                languages: env::var("SEARCH_LANGUAGES")
+                    .unwrap_or_else(|_| "russian,english,simple".to_string())
+                    .split(',')
+                    .map(|s| s.trim().to_string())
+                    .filter(|s| !s.is_empty())
+                    .collect(),
+                transliterate: env::var("SEARCH_TRANSLITERATE")
+                    .map(|v| v == "true" || v == "1")
+                    .unwrap_or(true),
+                fuzzy_threshold: env::var("SEARCH_FUZZY_THRESHOLD")
+                    .unwrap_or_else(|_| "0.35".to_string())
+                    .parse()
+                    .expect("SEARCH_FUZZY_THRESHOLD must be a valid number"),
+                count_threshold: env::var("SEARCH_COUNT_THRESHOLD")
+                    .unwrap_or_else(|_| "10000".to_string())
+                    .parse()
+                    .expect("SEARCH_COUNT_THRESHOLD must be a valid number"),
+                highlight: HighlightConfig {
+                    start_sel: env::var("SEARCH_HIGHLIGHT_START").unwrap_or_else(|_| "<b>".to_string()),
+                    stop_sel: env::var("SEARCH_HIGHLIGHT_STOP").unwrap_or_else(|_| "</b>".to_string()),
+                    min_words: env::var("SEARCH_HIGHLIGHT_MIN_WORDS")
+                        .unwrap_or_else(|_| "15".to_string())
+                        .parse()
+                        .expect("SEARCH_HIGHLIGHT_MIN_WORDS must be a valid number"),
+                    max_words: env::var("SEARCH_HIGHLIGHT_MAX_WORDS")
+                        .unwrap_or_else(|_| "35".to_string())
+                        .parse()
+                        .expect("SEARCH_HIGHLIGHT_MAX_WORDS must be a valid number"),
+                },
+            },
         }
     }
 }
\ No newline at end of file
@@ -0,0 +1,50 @@
+//! admin.rs
+//!
+//! Административные эндпоинты.
+//!
+//! Включает в себя следующую функциональность:
+//! - Статистика фоновой очистки (сколько записей ждут очистки).
+
+use axum::{
+    extract::State,
+    http::StatusCode,
+    response::IntoResponse,
+    routing::get,
+    Json, Router,
+};
+use std::sync::Arc;
+use crate::{AppState, error::AppResult, services::cleanup::CleanupService};
+
+/// Определяет административные маршруты. Доступ проверяет `require_admin`.
+pub fn routes() -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/admin/cleanup/stats", get(get_cleanup_stats))
+}
+
+/// GET /api/admin/cleanup/stats
+///
+/// Возвращает количество просроченных платежей, пустых и зависших бронирований,
+/// резервов в Redis и истекших удержаний мест, которые будут обработаны фоновой
+/// очисткой, и интервалы всех задач планировщика.
+async fn get_cleanup_stats(
+    State(state): State<Arc<AppState>>,
+) -> AppResult<impl IntoResponse> {
+    let stats = CleanupService::new(state.clone()).get_cleanup_stats().await;
+    let total = stats.total_items_to_cleanup();
+
+    Ok((StatusCode::OK, Json(serde_json::json!({
+        "stats": stats,
+        "total_items_to_cleanup": total,
+        "enabled": state.config.cleanup.enabled,
+        "intervals_seconds": {
+            "payments": state.config.cleanup.payments_interval_seconds,
+            "bookings": state.config.cleanup.bookings_interval_seconds,
+            "redis_reserves": state.config.cleanup.redis_reserves_interval_seconds,
+            "holds": state.config.cleanup.holds_interval_seconds,
+            "holds_sweep": state.config.cleanup.holds_sweep_interval_seconds,
+            "upcoming_refresh": state.config.cleanup.upcoming_refresh_interval_seconds,
+            "partitions": state.config.cleanup.partitions_interval_seconds,
+            "backfill": state.config.cleanup.backfill_interval_seconds
+        }
+    }))))
+}
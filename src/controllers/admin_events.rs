@@ -0,0 +1,337 @@
+//! admin_events.rs
+//!
+//! Административное управление событиями.
+//!
+//! Включает в себя следующую функциональность:
+//! - Создание и изменение событий (название, описание, тип, организатор, площадка).
+//! - Перенос события на другую дату.
+//! - Снятие с публикации, повторная публикация и отмена события.
+//!
+//! Любое изменение сбрасывает кеш списка событий (`events`) и результаты
+//! поиска (`search:events:*`), чтобы публичные эндпоинты сразу видели правки.
+
+use axum::{
+    extract::State,
+    http::StatusCode,
+    response::IntoResponse,
+    routing::{patch, post},
+    Json, Router,
+};
+use chrono::NaiveDateTime;
+use serde::Deserialize;
+use std::sync::Arc;
+use crate::{
+    AppState,
+    error::{AppError, AppResult},
+    extract::{AppJson, AppPath},
+    models::{event::EVENT_COLUMNS, state_machine, Event, EventStatus},
+};
+
+/// Определяет административные маршруты событий. Доступ проверяет `require_admin`.
+pub fn routes() -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/admin/events", post(create_event))
+        .route("/admin/events/{id}", patch(update_event))
+        .route("/admin/events/{id}/reschedule", patch(reschedule_event))
+        .route("/admin/events/{id}/publish", patch(publish_event))
+        .route("/admin/events/{id}/unpublish", patch(unpublish_event))
+        .route("/admin/events/{id}/cancel", patch(cancel_event))
+}
+
+// --- Вспомогательные функции ---
+
+/// Обрезает пробелы и проверяет, что обязательное текстовое поле не пустое.
+fn required_text(field: &str, value: &str) -> AppResult<String> {
+    let value = value.trim();
+    if value.is_empty() {
+        return Err(AppError::bad_request(format!("{} не может быть пустым", field)));
+    }
+    Ok(value.to_string())
+}
+
+/// Проверяет, что площадка существует.
+async fn ensure_venue_exists(pool: &sqlx::PgPool, venue_id: i64) -> AppResult<()> {
+    let exists = sqlx::query_scalar::<_, bool>("SELECT EXISTS(SELECT 1 FROM venues WHERE id = $1)")
+        .bind(venue_id)
+        .fetch_one(pool)
+        .await?;
+    if !exists {
+        return Err(AppError::bad_request("Площадка не найдена"));
+    }
+    Ok(())
+}
+
+/// Проверяет, что у события нет мест в секциях другой площадки: после смены
+/// площадки их схема зала потеряла бы координаты. Блокирует событие до конца
+/// транзакции, поэтому генерация мест (`FOR SHARE` события) не добавит такие
+/// места между проверкой и сменой.
+async fn ensure_no_foreign_seats(conn: &mut sqlx::PgConnection, event_id: i64, venue_id: i64) -> AppResult<()> {
+    sqlx::query("SELECT 1 FROM events_archive WHERE id = $1 FOR UPDATE")
+        .bind(event_id)
+        .execute(&mut *conn)
+        .await?;
+
+    let foreign = sqlx::query_scalar::<_, bool>(
+        "SELECT EXISTS(
+             SELECT 1 FROM seats s
+             JOIN sections sec ON sec.id = s.section_id
+             WHERE s.event_id = $1 AND sec.venue_id <> $2
+         )"
+    )
+    .bind(event_id)
+    .bind(venue_id)
+    .fetch_one(&mut *conn)
+    .await?;
+    if foreign {
+        return Err(AppError::state_conflict(
+            "Нельзя сменить площадку: у события есть места в секциях другой площадки"
+        ));
+    }
+    Ok(())
+}
+
+async fn fetch_event(pool: &sqlx::PgPool, event_id: i64) -> AppResult<Event> {
+    sqlx::query_as::<_, Event>(&format!("SELECT {} FROM events_archive WHERE id = $1", EVENT_COLUMNS))
+        .bind(event_id)
+        .fetch_optional(pool)
+        .await?
+        .ok_or_else(|| AppError::not_found("Событие не найдено"))
+}
+
+/// Ошибка для изменения, которое не затронуло ни одной строки:
+/// события нет или оно уже отменено.
+async fn not_editable(pool: &sqlx::PgPool, event_id: i64) -> AppError {
+    match fetch_event(pool, event_id).await {
+        Ok(_) => AppError::state_conflict("Отмененное событие нельзя изменить"),
+        Err(e) => e,
+    }
+}
+
+/// Перестраивает `events_upcoming` и сбрасывает кеш событий. Ошибка не отменяет
+/// уже сохраненное изменение: набор обновит фоновая задача, а закешированные
+/// ответы истекут по TTL.
+async fn invalidate_event_caches(state: &AppState) {
+    // Сначала набор, иначе кеш заполнится его старым содержимым
+    if let Err(e) = state.db.refresh_upcoming_events().await {
+        tracing::warn!("Failed to refresh upcoming events: {:?}", e);
+    }
+    // Списки и поиск, заполняющие кеш заново, читают основную БД: запись
+    // отмечена в `require_admin` до обработчика
+    if let Err(e) = state.cache.invalidate_events().await {
+        tracing::warn!("Failed to invalidate events cache: {:?}", e);
+    }
+}
+
+// --- Создание и изменение ---
+
+/// POST /api/admin/events
+///
+/// Создает событие. По умолчанию событие сразу публикуется; со статусом
+/// `unpublished` оно создается скрытым.
+#[derive(Debug, Deserialize)]
+struct CreateEventRequest {
+    title: String,
+    description: Option<String>,
+    #[serde(rename = "type", alias = "event_type")]
+    event_type: String,
+    datetime_start: NaiveDateTime,
+    provider: String,
+    venue_id: Option<i64>,
+    status: Option<EventStatus>,
+}
+
+async fn create_event(
+    State(state): State<Arc<AppState>>,
+    AppJson(req): AppJson<CreateEventRequest>,
+) -> AppResult<impl IntoResponse> {
+    let title = required_text("title", &req.title)?;
+    let event_type = required_text("type", &req.event_type)?;
+    let provider = required_text("provider", &req.provider)?;
+    let description = req.description.map(|d| d.trim().to_string()).filter(|d| !d.is_empty());
+    let status = req.status.unwrap_or_default();
+    if status == EventStatus::Cancelled {
+        return Err(AppError::bad_request("Событие нельзя создать отмененным"));
+    }
+    if let Some(venue_id) = req.venue_id {
+        ensure_venue_exists(&state.db.pool, venue_id).await?;
+    }
+    state.db.ensure_event_partition_for(req.datetime_start).await?;
+
+    let event = sqlx::query_as::<_, Event>(&format!(
+        "INSERT INTO events_archive (title, description, type, datetime_start, provider, venue_id, status)
+         VALUES ($1, $2, $3, $4, $5, $6, $7)
+         RETURNING {}",
+        EVENT_COLUMNS
+    ))
+    .bind(title)
+    .bind(description)
+    .bind(event_type)
+    .bind(req.datetime_start)
+    .bind(provider)
+    .bind(req.venue_id)
+    .bind(status)
+    .fetch_one(&state.db.pool)
+    .await?;
+
+    invalidate_event_caches(&state).await;
+    tracing::info!("Admin created event {}", event.id);
+
+    Ok((StatusCode::CREATED, Json(event)))
+}
+
+/// PATCH /api/admin/events/{id}
+///
+/// Частично обновляет событие: меняются только переданные поля.
+/// Пустой `description` очищает описание. Отмененное событие не меняется.
+/// Площадку нельзя сменить, пока у события есть места в секциях другой
+/// площадки; после смены сбрасывается кеш схемы зала (`seat_layout:{id}`).
+#[derive(Debug, Deserialize)]
+struct UpdateEventRequest {
+    title: Option<String>,
+    description: Option<String>,
+    #[serde(rename = "type", alias = "event_type")]
+    event_type: Option<String>,
+    provider: Option<String>,
+    venue_id: Option<i64>,
+}
+
+async fn update_event(
+    State(state): State<Arc<AppState>>,
+    AppPath(event_id): AppPath<i64>,
+    AppJson(req): AppJson<UpdateEventRequest>,
+) -> AppResult<impl IntoResponse> {
+    if req.title.is_none()
+        && req.description.is_none()
+        && req.event_type.is_none()
+        && req.provider.is_none()
+        && req.venue_id.is_none()
+    {
+        return Err(AppError::bad_request("Нет полей для изменения"));
+    }
+    let title = req.title.as_deref().map(|v| required_text("title", v)).transpose()?;
+    let event_type = req.event_type.as_deref().map(|v| required_text("type", v)).transpose()?;
+    let provider = req.provider.as_deref().map(|v| required_text("provider", v)).transpose()?;
+    let description = req.description.map(|d| d.trim().to_string());
+    let mut tx = state.db.pool.begin().await?;
+    if let Some(venue_id) = req.venue_id {
+        ensure_venue_exists(&state.db.pool, venue_id).await?;
+        ensure_no_foreign_seats(&mut tx, event_id, venue_id).await?;
+    }
+
+    let event = sqlx::query_as::<_, Event>(&format!(
+        "UPDATE events_archive SET
+             title = COALESCE($2, title),
+             description = CASE WHEN $3::text IS NULL THEN description ELSE NULLIF($3, '') END,
+             type = COALESCE($4, type),
+             provider = COALESCE($5, provider),
+             venue_id = COALESCE($6, venue_id)
+         WHERE id = $1 AND status <> $7
+         RETURNING {}",
+        EVENT_COLUMNS
+    ))
+    .bind(event_id)
+    .bind(title)
+    .bind(description)
+    .bind(event_type)
+    .bind(provider)
+    .bind(req.venue_id)
+    .bind(EventStatus::Cancelled)
+    .fetch_optional(&mut *tx)
+    .await?;
+
+    let Some(event) = event else {
+        return Err(not_editable(&state.db.pool, event_id).await);
+    };
+    tx.commit().await?;
+
+    invalidate_event_caches(&state).await;
+    if req.venue_id.is_some() {
+        state.cache.invalidate_seat_layout(event_id).await;
+    }
+    tracing::info!("Admin updated event {}", event_id);
+
+    Ok((StatusCode::OK, Json(event)))
+}
+
+/// PATCH /api/admin/events/{id}/reschedule
+///
+/// Переносит событие на новую дату и время. Брони и места сохраняются.
+#[derive(Debug, Deserialize)]
+struct RescheduleEventRequest {
+    datetime_start: NaiveDateTime,
+}
+
+async fn reschedule_event(
+    State(state): State<Arc<AppState>>,
+    AppPath(event_id): AppPath<i64>,
+    AppJson(req): AppJson<RescheduleEventRequest>,
+) -> AppResult<impl IntoResponse> {
+    state.db.ensure_event_partition_for(req.datetime_start).await?;
+
+    let event = sqlx::query_as::<_, Event>(&format!(
+        "UPDATE events_archive SET datetime_start = $2
+         WHERE id = $1 AND status <> $3
+         RETURNING {}",
+        EVENT_COLUMNS
+    ))
+    .bind(event_id)
+    .bind(req.datetime_start)
+    .bind(EventStatus::Cancelled)
+    .fetch_optional(&state.db.pool)
+    .await?;
+
+    let Some(event) = event else {
+        return Err(not_editable(&state.db.pool, event_id).await);
+    };
+
+    invalidate_event_caches(&state).await;
+    tracing::info!("Admin rescheduled event {} to {}", event_id, req.datetime_start);
+
+    Ok((StatusCode::OK, Json(event)))
+}
+
+// --- Статус публикации ---
+
+/// PATCH /api/admin/events/{id}/publish
+///
+/// Возвращает снятое с публикации событие в публичные списки.
+async fn publish_event(
+    State(state): State<Arc<AppState>>,
+    AppPath(event_id): AppPath<i64>,
+) -> AppResult<impl IntoResponse> {
+    change_status(&state, event_id, EventStatus::Published).await
+}
+
+/// PATCH /api/admin/events/{id}/unpublish
+///
+/// Скрывает событие из публичных списков и закрывает новые бронирования.
+/// Существующие брони не затрагиваются.
+async fn unpublish_event(
+    State(state): State<Arc<AppState>>,
+    AppPath(event_id): AppPath<i64>,
+) -> AppResult<impl IntoResponse> {
+    change_status(&state, event_id, EventStatus::Unpublished).await
+}
+
+/// PATCH /api/admin/events/{id}/cancel
+///
+/// Отменяет событие. Отмена окончательна: событие пропадает из публичных
+/// списков и больше не редактируется.
+async fn cancel_event(
+    State(state): State<Arc<AppState>>,
+    AppPath(event_id): AppPath<i64>,
+) -> AppResult<impl IntoResponse> {
+    change_status(&state, event_id, EventStatus::Cancelled).await
+}
+
+async fn change_status(state: &AppState, event_id: i64, to: EventStatus) -> AppResult<impl IntoResponse> {
+    let mut conn = state.db.pool.acquire().await?;
+    state_machine::transition_event(&mut conn, event_id, to).await?;
+    drop(conn);
+
+    invalidate_event_caches(state).await;
+    tracing::info!("Admin changed event {} status to {}", event_id, to);
+
+    let event = fetch_event(&state.db.pool, event_id).await?;
+    Ok((StatusCode::OK, Json(event)))
+}
@@ -0,0 +1,141 @@
+//! admin_seats.rs
+//!
+//! Административное управление местами события.
+//!
+//! Включает в себя следующую функциональность:
+//! - Генерация мест события по шаблону зала (`services::seat_inventory`).
+//! - Изменение цены категории мест.
+//!
+//! После изменения сбрасываются кеши мест (`seats:{event_id}`) и схемы зала
+//! (`seat_layout:{event_id}`).
+
+use axum::{
+    extract::State,
+    http::StatusCode,
+    response::IntoResponse,
+    routing::{patch, post},
+    Json, Router,
+};
+use serde::Deserialize;
+use serde_json::json;
+use std::sync::Arc;
+use crate::{
+    AppState,
+    error::{AppError, AppResult},
+    extract::{AppJson, AppPath},
+    models::EventStatus,
+    services::seat_inventory::{self, SeatTemplate},
+};
+
+/// Определяет административные маршруты мест. Доступ проверяет `require_admin`.
+pub fn routes() -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/admin/events/{id}/seats/generate", post(generate_seats))
+        .route("/admin/events/{id}/seats/categories/{category}", patch(reprice_category))
+}
+
+/// Блокирует событие до конца транзакции и проверяет, что его места можно менять.
+/// Возвращает площадку события.
+async fn lock_editable_event(conn: &mut sqlx::PgConnection, event_id: i64) -> AppResult<Option<i64>> {
+    let row = sqlx::query_as::<_, (Option<i64>, EventStatus)>(
+        "SELECT venue_id, status FROM events_archive WHERE id = $1 FOR SHARE"
+    )
+    .bind(event_id)
+    .fetch_optional(&mut *conn)
+    .await?;
+
+    match row {
+        None => Err(AppError::not_found("Событие не найдено")),
+        Some((_, EventStatus::Cancelled)) => Err(AppError::state_conflict("Событие отменено")),
+        Some((venue_id, _)) => Ok(venue_id),
+    }
+}
+
+async fn invalidate_seat_caches(state: &AppState, event_id: i64) {
+    state.cache.invalidate_seats(event_id).await;
+    state.cache.invalidate_seat_layout(event_id).await;
+}
+
+/// POST /api/admin/events/{id}/seats/generate
+///
+/// Создает места события по шаблону зала. Повторный вызов с тем же шаблоном
+/// безопасен: существующие места (включая их статус и цену) не меняются,
+/// досоздаются только недостающие.
+async fn generate_seats(
+    State(state): State<Arc<AppState>>,
+    AppPath(event_id): AppPath<i64>,
+    AppJson(template): AppJson<SeatTemplate>,
+) -> AppResult<impl IntoResponse> {
+    let mut tx = state.db.pool.begin().await?;
+    let venue_id = lock_editable_event(&mut tx, event_id).await?;
+
+    if let Some(section_id) = template.section_id {
+        let section_venue = sqlx::query_scalar::<_, i64>("SELECT venue_id FROM sections WHERE id = $1")
+            .bind(section_id)
+            .fetch_optional(&mut *tx)
+            .await?;
+        if section_venue.is_none() || section_venue != venue_id {
+            return Err(AppError::bad_request("Секция не относится к площадке события"));
+        }
+    }
+
+    let report = seat_inventory::generate_seats(&mut tx, event_id, &template).await?;
+    tx.commit().await?;
+
+    invalidate_seat_caches(&state, event_id).await;
+    tracing::info!(
+        "Admin generated seats for event {}: {} created, {} already existed",
+        event_id,
+        report.created,
+        report.total as u64 - report.created
+    );
+
+    Ok((StatusCode::OK, Json(json!({
+        "event_id": event_id,
+        "total": report.total,
+        "created": report.created,
+        "existing": report.total as u64 - report.created,
+    }))))
+}
+
+/// PATCH /api/admin/events/{id}/seats/categories/{category}
+///
+/// Меняет цену непроданных мест категории. Места броней в оплате и
+/// оплаченных броней сохраняют прежнюю цену.
+#[derive(Debug, Deserialize)]
+struct RepriceCategoryRequest {
+    price: f64,
+}
+
+async fn reprice_category(
+    State(state): State<Arc<AppState>>,
+    AppPath((event_id, category)): AppPath<(i64, String)>,
+    AppJson(req): AppJson<RepriceCategoryRequest>,
+) -> AppResult<impl IntoResponse> {
+    let mut tx = state.db.pool.begin().await?;
+    lock_editable_event(&mut tx, event_id).await?;
+
+    let exists = sqlx::query_scalar::<_, bool>(
+        "SELECT EXISTS(SELECT 1 FROM seats WHERE event_id = $1 AND category = $2)"
+    )
+    .bind(event_id)
+    .bind(&category)
+    .fetch_one(&mut *tx)
+    .await?;
+    if !exists {
+        return Err(AppError::not_found("Категория не найдена"));
+    }
+
+    let updated = seat_inventory::reprice_category(&mut tx, event_id, &category, req.price).await?;
+    tx.commit().await?;
+
+    invalidate_seat_caches(&state, event_id).await;
+    tracing::info!("Admin repriced {} seats of category {} for event {}", updated, category, event_id);
+
+    Ok((StatusCode::OK, Json(json!({
+        "event_id": event_id,
+        "category": category,
+        "price": req.price,
+        "updated": updated,
+    }))))
+}
@@ -8,7 +8,7 @@
 //! - Расчет общей выручки и количества завершенных бронирований.
 
 use axum::{
-    extract::{Query, State},
+    extract::State,
     http::StatusCode,
     response::IntoResponse,
     routing::get,
@@ -17,7 +17,7 @@ use axum::{
 use serde::{Deserialize, Serialize};
 use std::sync::Arc;
 use sqlx::Row;
-use crate::AppState;
+use crate::{AppState, error::{AppError, AppResult}, extract::AppQuery};
 
 /// Определяет маршруты, связанные с аналитикой.
 pub fn routes() -> Router<Arc<AppState>> {
@@ -61,22 +61,24 @@ struct AnalyticsResponse {
 
 async fn get_event_analytics(
     State(state): State<Arc<AppState>>,
-    Query(params): Query<AnalyticsQuery>,
-) -> Result<impl IntoResponse, (StatusCode, String)> {
+    AppQuery(params): AppQuery<AnalyticsQuery>,
+) -> AppResult<impl IntoResponse> {
     if params.id <= 0 {
-        return Err((StatusCode::BAD_REQUEST, "ID события должен быть > 0".to_string()));
+        return Err(AppError::bad_request("ID события должен быть > 0"));
     }
 
     // Проверяем, что событие существует.
-    let exists = event_exists(&state.db.pool, params.id)
+    // Аналитике допустимо небольшое отставание: читаем с реплики.
+    let pool = state.db.read_pool();
+    let exists = event_exists(pool, params.id)
         .await
         .map_err(|e| {
             tracing::error!("get_event_analytics: ошибка проверки события {}: {:?}", params.id, e);
-            (StatusCode::INTERNAL_SERVER_ERROR, "Ошибка проверки события".to_string())
+            AppError::internal("Ошибка проверки события")
         })?;
 
     if !exists {
-        return Err((StatusCode::NOT_FOUND, "Событие не найдено".to_string()));
+        return Err(AppError::not_found("Событие не найдено"));
     }
 
     // Получаем детальную статистику по местам и бронированиям для события.
@@ -88,7 +90,7 @@ async fn get_event_analytics(
             COUNT(s.id)::int as total_seats,
             COUNT(s.id) FILTER (WHERE s.status = 'SOLD')::int as sold_seats,
             COUNT(s.id) FILTER (WHERE s.status = 'RESERVED')::int as reserved_seats,
-            COUNT(s.id) FILTER (WHERE s.status IN ('FREE', 'AVAILABLE'))::int as free_seats,
+            COUNT(s.id) FILTER (WHERE s.status = 'FREE')::int as free_seats,
             COALESCE(SUM(s.price) FILTER (WHERE s.status = 'SOLD'), 0)::float8 as total_revenue,
             COUNT(DISTINCT b.id) FILTER (WHERE b.status = 'paid')::int as bookings_count
         FROM seats s
@@ -97,11 +99,11 @@ async fn get_event_analytics(
         "#
     )
     .bind(params.id)
-    .fetch_optional(&state.db.pool)
+    .fetch_optional(pool)
     .await
     .map_err(|e| {
         tracing::error!("get_event_analytics: sql ошибка для события {}: {:?}", params.id, e);
-        (StatusCode::INTERNAL_SERVER_ERROR, "Не удалось получить аналитику".to_string())
+        AppError::internal("Не удалось получить аналитику")
     })?;
 
     // Если для события нет мест, возвращаем нулевую статистику.
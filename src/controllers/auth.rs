@@ -0,0 +1,122 @@
+//! auth.rs
+//!
+//! Модуль для работы с JWT-сессиями.
+//!
+//! Включает в себя следующую функциональность:
+//! - Обмен Basic-учетных данных на подписанный JWT.
+//! - Обновление токена (старый токен при этом отзывается).
+//! - Выход из сессии с отзывом токена.
+
+use axum::{
+    extract::State,
+    http::{header, HeaderMap, StatusCode},
+    response::IntoResponse,
+    routing::post,
+    Json, Router,
+};
+use std::sync::Arc;
+use crate::{
+    AppState,
+    error::{AppError, AppResult},
+    middleware::{authenticate_basic, authenticate_bearer, parse_basic_credentials, AuthUser},
+};
+
+/// Определяет маршруты, связанные с авторизацией.
+pub fn routes() -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/auth/login", post(login))
+        .route("/auth/refresh", post(refresh))
+        .route("/auth/logout", post(logout))
+}
+
+// --- Вспомогательные функции ---
+
+/// Достает значение заголовка `Authorization`.
+fn authorization_header(headers: &HeaderMap) -> AppResult<&str> {
+    headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .ok_or_else(|| AppError::unauthorized("Отсутствует заголовок Authorization"))
+}
+
+/// Достает Bearer-токен из заголовка `Authorization`.
+fn bearer_token(headers: &HeaderMap) -> AppResult<&str> {
+    authorization_header(headers)?
+        .strip_prefix("Bearer ")
+        .ok_or_else(|| AppError::unauthorized("Ожидается Bearer-токен"))
+}
+
+/// Выпускает токен для пользователя и оборачивает ошибку подписи.
+fn issue_token(state: &AppState, user: &AuthUser) -> AppResult<impl IntoResponse> {
+    let token = state.jwt.issue(user).map_err(|e| {
+        tracing::error!("Failed to sign JWT for user {}: {:?}", user.user_id, e);
+        AppError::internal("Не удалось выпустить токен")
+    })?;
+
+    Ok((StatusCode::OK, Json(token)))
+}
+
+// --- Обработчики ---
+
+/// POST /api/auth/login
+///
+/// Принимает `Authorization: Basic base64(email:password)` и возвращает JWT,
+/// который дальше можно передавать как `Authorization: Bearer <token>`.
+async fn login(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+) -> AppResult<impl IntoResponse> {
+    let (email, password) = parse_basic_credentials(authorization_header(&headers)?)
+        .ok_or_else(|| AppError::unauthorized("Ожидаются Basic-учетные данные"))?;
+
+    let user = authenticate_basic(&state, &email, &password).await?;
+
+    tracing::info!("Issued JWT for user {}", user.user_id);
+    issue_token(&state, &user)
+}
+
+/// POST /api/auth/refresh
+///
+/// Обменивает действующий токен на новый. Старый токен сразу попадает
+/// в список отзыва, так что один токен можно обновить только один раз:
+/// из параллельных обновлений новый токен получает только то, которое
+/// отозвало старый первым, остальные получают 401.
+async fn refresh(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+) -> AppResult<impl IntoResponse> {
+    let claims = authenticate_bearer(&state, bearer_token(&headers)?).await?;
+
+    let user = claims
+        .auth_user()
+        .ok_or_else(|| AppError::unauthorized("Токен недействителен"))?;
+
+    let revoked = state.cache.revoke_jwt(&claims.jti, claims.remaining_seconds()).await.map_err(|e| {
+        tracing::error!("Failed to revoke JWT {}: {:?}", claims.jti, e);
+        AppError::ServiceUnavailable("Не удалось обновить токен".to_string())
+    })?;
+    if !revoked {
+        tracing::warn!("JWT {} of user {} was already refreshed or revoked", claims.jti, user.user_id);
+        return Err(AppError::unauthorized("Токен отозван"));
+    }
+
+    issue_token(&state, &user)
+}
+
+/// POST /api/auth/logout
+///
+/// Отзывает текущий токен до истечения его срока действия.
+async fn logout(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+) -> AppResult<impl IntoResponse> {
+    let claims = authenticate_bearer(&state, bearer_token(&headers)?).await?;
+
+    // Уже отозванный параллельным запросом токен - сессия все равно завершена
+    state.cache.revoke_jwt(&claims.jti, claims.remaining_seconds()).await.map_err(|e| {
+        tracing::error!("Failed to revoke JWT {}: {:?}", claims.jti, e);
+        AppError::ServiceUnavailable("Не удалось отозвать токен".to_string())
+    })?;
+
+    Ok((StatusCode::OK, Json(serde_json::json!({"message":"Сессия завершена"}))))
+}
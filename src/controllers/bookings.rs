@@ -6,30 +6,50 @@
 //! - Создание и отмена бронирований.
 //! - Получение списка бронирований пользователя.
 //! - Выбор и освобождение мест в рамках бронирования.
+//! - Автоматический подбор лучших мест.
 //! - Получение информации о доступных местах для события.
 //! - Сброс всех данных для тестирования.
 
 use axum::{
-    extract::{Query, State},
+    extract::State,
     http::StatusCode,
-    response::IntoResponse,
+    response::{IntoResponse, Response},
     routing::{get, patch, post},
     Json, Router,
 };
 use serde::{Deserialize, Serialize};
 use sqlx::Row;
-use std::sync::Arc;
-use crate::AppState;
+use std::{
+    collections::{BTreeMap, HashSet},
+    sync::Arc,
+};
+use crate::{
+    AppState,
+    cache::{holds::SEAT_HOLDS_INDEX_KEY, recent_writes::WriteScope},
+    cursor::{self, SeatCursor, MAX_CURSOR_PAGE_SIZE},
+    error::{AppError, AppResult},
+    extract::{AppJson, AppPath, AppQuery},
+    models::{
+        state_machine::{self, StatusMachine, TransitionError},
+        BookingStatus, EventStatus, SeatStatus,
+    },
+    services::{
+        seat_events::SeatChange,
+        seat_picker::{self, SeatCriteria},
+    },
+};
 
 /// Определяет маршруты, связанные с бронированиями и местами.
 pub fn routes() -> Router<Arc<AppState>> {
     Router::new()
         .route("/seats", get(get_seats))
         .route("/seats/select", patch(select_seat))
+        .route("/seats/select-batch", patch(select_seats_batch))
         .route("/seats/release", patch(release_seat))
         .route("/bookings", get(get_user_bookings))
         .route("/bookings", post(create_booking))
         .route("/bookings/cancel", patch(cancel_booking))
+        .route("/bookings/{id}/auto-select", post(auto_select_seats))
 }
 
 /// Определяет маршрут для сброса данных (только для тестирования).
@@ -40,12 +60,6 @@ pub fn reset_route() -> Router<Arc<AppState>> {
 
 // --- Вспомогательные функции ---
 
-/// Возвращает кастомный статус-код 419, часто используемый для обозначения конфликта,
-/// например, когда место уже занято.
-fn status_419() -> StatusCode {
-    StatusCode::from_u16(419).unwrap_or(StatusCode::CONFLICT)
-}
-
 /// Проверяет, принадлежит ли указанное бронирование пользователю.
 async fn booking_belongs_to_user(pool: &sqlx::PgPool, booking_id: i64, user_id: i32) -> sqlx::Result<bool> {
     sqlx::query_scalar::<_, bool>(
@@ -86,7 +100,7 @@ async fn seat_event_id(pool: &sqlx::PgPool, seat_id: i64) -> sqlx::Result<Option
 /// POST /api/bookings
 ///
 /// Создает новое, пустое бронирование для указанного события от имени
-/// аутентифицированного пользователя.
+/// аутентифицированного пользователя. Бронировать можно только опубликованные события.
 #[derive(Debug, Deserialize)]
 struct CreateBookingRequest { pub event_id: i64 }
 
@@ -96,27 +110,31 @@ struct CreateBookingResponse { pub id: i64 }
 async fn create_booking(
     State(state): State<Arc<AppState>>,
     user: crate::middleware::AuthUser,
-    Json(req): Json<CreateBookingRequest>,
-) -> Result<impl IntoResponse, (StatusCode, String)> {
+    AppJson(req): AppJson<CreateBookingRequest>,
+) -> AppResult<impl IntoResponse> {
     if req.event_id <= 0 {
-        return Err((StatusCode::BAD_REQUEST, "event_id должен быть > 0".to_string()));
+        return Err(AppError::bad_request("event_id должен быть > 0"));
     }
 
     let res = sqlx::query_scalar::<_, i64>(
         "INSERT INTO bookings (event_id, user_id, status)
-         VALUES ($1, $2, 'created')
+         SELECT $1, $2, $3
+         WHERE EXISTS (SELECT 1 FROM events_archive WHERE id = $1 AND status = $4)
          RETURNING id"
     )
     .bind(req.event_id)
     .bind(user_user_id_to_i64(user.user_id))
-    .fetch_one(&state.db.pool)
+    .bind(BookingStatus::Created)
+    .bind(EventStatus::Published)
+    .fetch_optional(&state.db.pool)
     .await;
 
     match res {
-        Ok(id) => Ok((StatusCode::CREATED, Json(CreateBookingResponse{ id }))),
+        Ok(Some(id)) => Ok((StatusCode::CREATED, Json(CreateBookingResponse{ id }))),
+        Ok(None) => Err(AppError::not_found("Событие не найдено или недоступно для бронирования")),
         Err(e) => {
             tracing::error!("create_booking sql error: {:?}", e);
-            Err((StatusCode::INTERNAL_SERVER_ERROR, "Не удалось создать бронирование".to_string()))
+            Err(AppError::internal("Не удалось создать бронирование"))
         }
     }
 }
@@ -134,7 +152,7 @@ struct BookingResponse { pub id: i64, pub event_id: i64, pub seats: Vec<BookingS
 async fn get_user_bookings(
     State(state): State<Arc<AppState>>,
     user: crate::middleware::AuthUser,
-) -> Result<impl IntoResponse, (StatusCode, String)> {
+) -> AppResult<impl IntoResponse> {
     // Получаем все бронирования и связанные с ними места для пользователя.
     let rows = sqlx::query(
         r#"
@@ -146,16 +164,15 @@ async fn get_user_bookings(
         "#
     )
     .bind(user_user_id_to_i64(user.user_id))
-    .fetch_all(&state.db.pool)
+    .fetch_all(state.read_pool(WriteScope::User(user.user_id)).await)
     .await;
 
     let rows = rows.map_err(|e| {
         tracing::error!("get_user_bookings sql error: {:?}", e);
-        (StatusCode::INTERNAL_SERVER_ERROR, "Не удалось получить список бронирований".to_string())
+        AppError::internal("Не удалось получить список бронирований")
     })?;
 
     // Группируем места по бронированиям.
-    use std::collections::BTreeMap;
     let mut map: BTreeMap<i64, (i64, Vec<i64>)> = BTreeMap::new();
     for r in rows {
         let bid: i64 = r.get("bid");
@@ -185,10 +202,10 @@ struct CancelBookingRequest { pub booking_id: i64 }
 async fn cancel_booking(
     State(state): State<Arc<AppState>>,
     user: crate::middleware::AuthUser,
-    Json(req): Json<CancelBookingRequest>,
-) -> Result<impl IntoResponse, (StatusCode, String)> {
+    AppJson(req): AppJson<CancelBookingRequest>,
+) -> AppResult<impl IntoResponse> {
     if req.booking_id <= 0 {
-        return Err((StatusCode::BAD_REQUEST, "booking_id должен быть > 0".to_string()));
+        return Err(AppError::bad_request("booking_id должен быть > 0"));
     }
 
     // Проверяем, что пользователь является владельцем этого бронирования.
@@ -196,72 +213,63 @@ async fn cancel_booking(
         .await
         .unwrap_or(false);
     if !belongs {
-        return Err((StatusCode::FORBIDDEN, "Бронирование не найдено или не принадлежит вам".to_string()));
+        return Err(AppError::forbidden("Бронирование не найдено или не принадлежит вам"));
     }
 
     // Получаем event_id для последующей инвалидации кэша.
     let event_id = booking_event_id(&state.db.pool, req.booking_id).await
-        .map_err(|_| (StatusCode::INTERNAL_SERVER_ERROR, "Ошибка БД".to_string()))?
-        .ok_or_else(|| (status_419(), "Бронирование не найдено".to_string()))?;
+        .map_err(|_| AppError::internal("Ошибка БД"))?
+        .ok_or_else(|| AppError::state_conflict("Бронирование не найдено"))?;
 
     // Начинаем транзакцию.
     let mut tx = state.db.pool.begin().await
-        .map_err(|_| (StatusCode::INTERNAL_SERVER_ERROR, "Ошибка транзакции".to_string()))?;
+        .map_err(|_| AppError::internal("Ошибка транзакции"))?;
 
     // Шаг 1: Освобождаем все зарезервированные места, связанные с этим бронированием,
     // и возвращаем их в статус 'FREE'. Собираем ID этих мест для дальнейших действий.
-    let freed_result = sqlx::query_scalar::<_, i64>(
-        r#"
-        UPDATE seats
-        SET status = 'FREE', booking_id = NULL
-        WHERE booking_id = $1 AND status = 'RESERVED'
-        RETURNING id
-        "#
-    )
-    .bind(req.booking_id)
-    .fetch_all(&mut *tx)
-    .await;
+    let freed_result = state_machine::transition_booking_seats(&mut tx, req.booking_id, SeatStatus::Free).await;
 
     let freed: Vec<i64> = match freed_result {
         Ok(v) => v,
         Err(e) => {
             tracing::error!("failed to free seats for booking {}: {:?}", req.booking_id, e);
             let _ = tx.rollback().await; // Откатываем транзакцию в случае ошибки.
-            return Err((StatusCode::INTERNAL_SERVER_ERROR, "Не удалось освободить места".to_string()));
+            return Err(AppError::internal("Не удалось освободить места"));
         }
     };
 
-    // Шаг 2: Помечаем само бронирование как отмененное.
-    let upd_result = sqlx::query("UPDATE bookings SET status = 'cancelled' WHERE id = $1")
-        .bind(req.booking_id)
-        .execute(&mut *tx)
-        .await;
+    // Шаг 2: Помечаем само бронирование как отмененное. Оплаченную или уже
+    // отмененную бронь отменить нельзя.
+    let upd_result = state_machine::transition_booking(&mut tx, req.booking_id, BookingStatus::Cancelled).await;
 
-    if let Err(e) = upd_result {
-        tracing::error!("failed to update booking {}: {:?}", req.booking_id, e);
-        let _ = tx.rollback().await;
-        return Err((StatusCode::INTERNAL_SERVER_ERROR, "Не удалось отменить бронирование".to_string()));
+    match upd_result {
+        Ok(()) => {}
+        Err(e @ TransitionError::Illegal { .. }) => {
+            tracing::info!("cancel_booking {} rejected: {}", req.booking_id, e);
+            let _ = tx.rollback().await;
+            return Err(AppError::state_conflict("Бронирование нельзя отменить в текущем статусе"));
+        }
+        Err(e) => {
+            tracing::error!("failed to update booking {}: {:?}", req.booking_id, e);
+            let _ = tx.rollback().await;
+            return Err(AppError::internal("Не удалось отменить бронирование"));
+        }
     }
 
     // Шаг 3: Если все прошло успешно, коммитим транзакцию.
     if let Err(e) = tx.commit().await {
         tracing::error!("failed to commit cancel_booking tx for {}: {:?}", req.booking_id, e);
-        return Err((StatusCode::INTERNAL_SERVER_ERROR, "Ошибка фиксации транзакции".to_string()));
+        return Err(AppError::internal("Ошибка фиксации транзакции"));
     }
 
     // Шаг 4: Очищаем временные блокировки (резервы) в Redis.
     // Это некритичная операция, поэтому ошибка здесь не прервет выполнение.
-    {
-        let mut pipe = redis::pipe();
-        for seat_id in &freed {
-            pipe.del(format!("seat:{}:reserved", seat_id));
-        }
-        // В реальном проекте здесь будет асинхронный вызов к Redis.
-    }
+    state.cache.release_seat_holds(&freed).await;
 
     // Шаг 5: Инвалидируем кэш со списком мест для данного события,
     // так как состояние мест изменилось.
     state.cache.invalidate_seats(event_id).await;
+    state.seat_events.publish(event_id, &freed, SeatChange::Released).await;
 
     Ok((StatusCode::OK, Json(serde_json::json!({"message":"Бронь успешно отменена"}))))
 }
@@ -277,6 +285,22 @@ struct SeatsQuery {
     page_size: Option<u32>,
     row: Option<i32>,
     status: Option<String>, // FREE, RESERVED, SOLD
+    /// `next_cursor` предыдущего ответа; пустая строка - первая страница.
+    cursor: Option<String>,
+}
+
+impl SeatsQuery {
+    /// Фильтр по статусу: только статусы, которые хранятся в БД.
+    fn status_filter(&self) -> AppResult<Option<SeatStatus>> {
+        let invalid = || AppError::bad_request("status должен быть FREE | RESERVED | SOLD");
+        match self.status.as_deref() {
+            None => Ok(None),
+            Some(s) => match s.parse::<SeatStatus>() {
+                Ok(status) if status.is_persisted() => Ok(Some(status)),
+                _ => Err(invalid()),
+            },
+        }
+    }
 }
 
 /// Структура ответа для одного места.
@@ -285,68 +309,81 @@ struct SeatResponse {
     id: i64,
     row: i32,
     number: i32,
-    status: String,
+    status: SeatStatus,
 }
 
 /// GET /api/seats
 ///
 /// Возвращает список мест для события с возможностью фильтрации и пагинации.
+/// С параметром `cursor` (keyset по `row, number, id`) ответ - объект
+/// `{ seats, next_cursor }`; без него - массив мест страницы `page`, как раньше.
 async fn get_seats(
     State(state): State<Arc<AppState>>,
-    Query(params): Query<SeatsQuery>,
-) -> Result<impl IntoResponse, (StatusCode, String)> {
+    user: crate::middleware::AuthUser,
+    AppQuery(params): AppQuery<SeatsQuery>,
+) -> AppResult<Response> {
     // Валидация входных параметров.
     if params.event_id <= 0 {
-        return Err((StatusCode::BAD_REQUEST, "event_id должен быть > 0".to_string()));
+        return Err(AppError::bad_request("event_id должен быть > 0"));
     }
     if let Some(r) = params.row {
-        if r <= 0 { return Err((StatusCode::BAD_REQUEST, "row должен быть > 0".to_string())); }
+        if r <= 0 { return Err(AppError::bad_request("row должен быть > 0")); }
     }
-    if let Some(ref st) = params.status {
-        if !matches!(st.as_str(), "FREE" | "RESERVED" | "SOLD") {
-            return Err((StatusCode::BAD_REQUEST, "status должен быть FREE | RESERVED | SOLD".to_string()));
-        }
+    let status = params.status_filter()?;
+
+    let cursor_mode = params.cursor.is_some();
+    if cursor_mode && params.page.is_some() {
+        return Err(AppError::bad_request("page и cursor нельзя передавать вместе"));
     }
+    let after = cursor::decode_param::<SeatCursor>(params.cursor.as_deref())?;
 
     let page = params.page.unwrap_or(1).max(1);
-    let page_size = params.page_size.unwrap_or(20).clamp(1, 20);
-    let offset = (page - 1) * page_size;
+    let max_page_size = if cursor_mode { MAX_CURSOR_PAGE_SIZE } else { 20 };
+    let page_size = params.page_size.unwrap_or(20).clamp(1, max_page_size);
+    let offset = if cursor_mode { 0 } else { (page - 1) * page_size };
 
     // Динамически строим SQL-запрос в зависимости от переданных фильтров.
-    let mut q = String::from("SELECT id, row, number, status FROM seats WHERE event_id = $1");
-    let mut bind_idx = 2;
-    if params.row.is_some() {
-        q.push_str(&format!(" AND row = ${}", bind_idx));
-        bind_idx += 1;
+    let mut q = sqlx::QueryBuilder::<sqlx::Postgres>::new("SELECT id, row, number, status FROM seats WHERE event_id = ");
+    q.push_bind(params.event_id);
+    if let Some(r) = params.row {
+        q.push(" AND row = ").push_bind(r);
     }
-    if params.status.is_some() {
-        q.push_str(&format!(" AND status = ${}", bind_idx));
-        bind_idx += 1;
+    if let Some(st) = status {
+        q.push(" AND status = ").push_bind(st);
     }
-    q.push_str(&format!(" ORDER BY row, number LIMIT ${} OFFSET ${}", bind_idx, bind_idx + 1));
-
-    let mut dbq = sqlx::query_as::<_, (i64, i32, i32, String)>(&q)
-        .bind(params.event_id);
-
-    // Привязываем параметры к запросу.
-    if let Some(r) = params.row { dbq = dbq.bind(r); }
-    if let Some(st) = params.status { dbq = dbq.bind(st); }
-
-    let seats = dbq
-        .bind(page_size as i64)
-        .bind(offset as i64)
-        .fetch_all(&state.db.pool)
+    if let Some(cursor) = &after {
+        cursor.push_after(&mut q);
+    }
+    // На одну запись больше: по ней видно, есть ли следующая страница.
+    q.push(" ORDER BY row, number, id LIMIT ")
+        .push_bind(page_size as i64 + 1)
+        .push(" OFFSET ")
+        .push_bind(offset as i64);
+
+    let mut seats = q
+        .build_query_as::<(i64, i32, i32, SeatStatus)>()
+        .fetch_all(state.read_pool(WriteScope::User(user.user_id)).await)
         .await
         .map_err(|e| {
             tracing::error!("get_seats sql error: {:?}", e);
-            (StatusCode::INTERNAL_SERVER_ERROR, "Не удалось получить список мест".to_string())
+            AppError::internal("Не удалось получить список мест")
         })?;
 
+    let has_more = seats.len() > page_size as usize;
+    seats.truncate(page_size as usize);
+    let next_cursor = seats
+        .last()
+        .filter(|_| has_more)
+        .map(|&(id, row, number, _)| cursor::encode(&SeatCursor { row, number, id }));
+
     let payload: Vec<SeatResponse> = seats.into_iter().map(|(id,row,number,status)| SeatResponse{
         id, row, number, status
     }).collect();
 
-    Ok((StatusCode::OK, Json(payload)))
+    if cursor_mode {
+        return Ok((StatusCode::OK, Json(serde_json::json!({ "seats": payload, "next_cursor": next_cursor }))).into_response());
+    }
+    Ok((StatusCode::OK, Json(payload)).into_response())
 }
 
 /// PATCH /api/seats/select
@@ -360,10 +397,10 @@ struct SelectSeatRequest { booking_id: i64, seat_id: i64 }
 async fn select_seat(
     State(state): State<Arc<AppState>>,
     user: crate::middleware::AuthUser,
-    Json(req): Json<SelectSeatRequest>,
-) -> Result<impl IntoResponse, (StatusCode, String)> {
+    AppJson(req): AppJson<SelectSeatRequest>,
+) -> AppResult<impl IntoResponse> {
     if req.booking_id <= 0 || req.seat_id <= 0 {
-        return Err((StatusCode::BAD_REQUEST, "booking_id и seat_id должны быть > 0".to_string()));
+        return Err(AppError::bad_request("booking_id и seat_id должны быть > 0"));
     }
 
     // Проверяем, что бронирование принадлежит пользователю.
@@ -371,48 +408,300 @@ async fn select_seat(
         .await
         .unwrap_or(false);
     if !belongs {
-        return Err((status_419(), "Бронирование не найдено".to_string()));
+        return Err(AppError::state_conflict("Бронирование не найдено"));
     }
 
     // Пытаемся атомарно зарезервировать место в Redis на 5 минут.
     // Если ключ уже существует, значит, кто-то другой пытается занять это место.
     let reserved = state.cache.reserve_seat(req.seat_id, user.user_id).await;
     if !reserved {
-        return Err((status_419(), "Место уже зарезервировано".to_string()));
+        return Err(AppError::state_conflict("Место уже зарезервировано"));
+    }
+
+    // Место удерживается: остальные клиенты видят его выбранным.
+    let event_id = seat_event_id(&state.db.pool, req.seat_id).await.ok().flatten();
+    if let Some(eid) = event_id {
+        state.seat_events.publish(eid, &[req.seat_id], SeatChange::Selected).await;
     }
 
     // Если резерв в Redis успешен, обновляем статус места в основной базе данных.
     // Обновление произойдет только если место было 'FREE'.
-    let ok = sqlx::query(
-        r#"
-        UPDATE seats
-        SET status = 'RESERVED', booking_id = $1
-        WHERE id = $2 AND status = 'FREE'
-        "#
+    let result = match state.db.pool.acquire().await {
+        Ok(mut conn) => state_machine::transition_seat(&mut conn, req.seat_id, SeatStatus::Reserved, Some(req.booking_id)).await,
+        Err(e) => Err(TransitionError::Database(e)),
+    };
+
+    match result {
+        Ok(()) => {
+            // Если место успешно забронировано в БД, инвалидируем кэш.
+            if let Some(eid) = event_id {
+                state.cache.invalidate_seats(eid).await;
+                state.seat_events.publish(eid, &[req.seat_id], SeatChange::Reserved).await;
+            }
+            Ok((StatusCode::OK, Json(serde_json::json!({"message":"Место успешно добавлено в бронь"}))))
+        }
+        Err(e) => {
+            tracing::debug!("select_seat {} rejected: {}", req.seat_id, e);
+            // Если обновить БД не удалось (например, место уже было занято),
+            // необходимо откатить резерв в Redis.
+            state.cache.release_seat_holds(&[req.seat_id]).await;
+            if let Some(eid) = event_id {
+                publish_actual_seat_status(&state, eid, &[req.seat_id]).await;
+            }
+            Err(AppError::state_conflict("Не удалось добавить место в бронь"))
+        }
+    }
+}
+
+/// Максимум мест в одном запросе `select-batch` и `auto-select`.
+const MAX_BATCH_SEATS: usize = 50;
+
+/// Сколько раз `auto-select` перечитывает карту мест, если выбранные места успели занять.
+const AUTO_SELECT_MAX_ATTEMPTS: usize = 5;
+
+/// PATCH /api/seats/select-batch
+///
+/// Добавляет в бронирование сразу несколько мест: либо все, либо ни одного.
+/// Удержания в Redis берутся одним Lua-скриптом, статусы в БД меняются
+/// одной транзакцией. При конфликте возвращает `seats_unavailable` со списком
+/// занятых мест и ничего не оставляет за собой.
+#[derive(Debug, Deserialize)]
+struct SelectSeatsBatchRequest { booking_id: i64, seat_ids: Vec<i64> }
+
+async fn select_seats_batch(
+    State(state): State<Arc<AppState>>,
+    user: crate::middleware::AuthUser,
+    AppJson(req): AppJson<SelectSeatsBatchRequest>,
+) -> AppResult<impl IntoResponse> {
+    if req.booking_id <= 0 || req.seat_ids.iter().any(|id| *id <= 0) {
+        return Err(AppError::bad_request("booking_id и seat_ids должны быть > 0"));
+    }
+    let mut seat_ids = req.seat_ids;
+    seat_ids.sort_unstable();
+    seat_ids.dedup();
+    if seat_ids.is_empty() || seat_ids.len() > MAX_BATCH_SEATS {
+        return Err(AppError::bad_request(format!(
+            "seat_ids должен содержать от 1 до {} мест",
+            MAX_BATCH_SEATS
+        )));
+    }
+
+    let event_id = editable_booking_event_id(&state.db.pool, req.booking_id, user.user_id).await?;
+
+    let conflicts = claim_seats(&state, user.user_id, req.booking_id, event_id, &seat_ids).await?;
+    if !conflicts.is_empty() {
+        return Err(AppError::SeatsUnavailable(conflicts));
+    }
+
+    Ok((StatusCode::OK, Json(serde_json::json!({
+        "message": "Места успешно добавлены в бронь",
+        "seat_ids": seat_ids,
+    }))))
+}
+
+/// POST /api/bookings/{id}/auto-select
+///
+/// Подбирает и добавляет в бронирование лучшие свободные места (см.
+/// `services::seat_picker`). Если выбранные места успели занять, перечитывает
+/// карту мест и подбирает следующий вариант без них.
+#[derive(Debug, Deserialize)]
+struct AutoSelectRequest {
+    quantity: usize,
+    category: Option<String>,
+    max_price: Option<f64>,
+    #[serde(default = "default_adjacent")]
+    adjacent: bool,
+}
+
+fn default_adjacent() -> bool { true }
+
+async fn auto_select_seats(
+    State(state): State<Arc<AppState>>,
+    user: crate::middleware::AuthUser,
+    AppPath(booking_id): AppPath<i64>,
+    AppJson(req): AppJson<AutoSelectRequest>,
+) -> AppResult<impl IntoResponse> {
+    if booking_id <= 0 {
+        return Err(AppError::bad_request("booking_id должен быть > 0"));
+    }
+    if req.quantity == 0 || req.quantity > MAX_BATCH_SEATS {
+        return Err(AppError::bad_request(format!(
+            "quantity должен быть от 1 до {}",
+            MAX_BATCH_SEATS
+        )));
+    }
+    if req.max_price.is_some_and(|price| !price.is_finite() || price < 0.0) {
+        return Err(AppError::bad_request("max_price должен быть >= 0"));
+    }
+
+    let event_id = editable_booking_event_id(&state.db.pool, booking_id, user.user_id).await?;
+
+    let criteria = SeatCriteria {
+        quantity: req.quantity,
+        category: req.category,
+        max_price: req.max_price,
+        adjacent: req.adjacent,
+    };
+
+    let mut excluded = HashSet::new();
+    for _ in 0..AUTO_SELECT_MAX_ATTEMPTS {
+        // Карта мест с наложенными удержаниями: уже выбранные кем-то места не предлагаем.
+        // После конфликта кеш сброшен, и карта читается из БД заново.
+        let seats = state.cache.get_seats(event_id).await;
+        let Some(picked) = seat_picker::best_seats(&seats, &criteria, &excluded) else {
+            return Err(AppError::state_conflict("Нет свободных мест, подходящих под условия"));
+        };
+        let seat_ids: Vec<i64> = picked.iter().map(|seat| seat.id).collect();
+
+        let conflicts = claim_seats(&state, user.user_id, booking_id, event_id, &seat_ids).await?;
+        if conflicts.is_empty() {
+            let seats: Vec<_> = picked.iter().map(|seat| serde_json::json!({
+                "id": seat.id,
+                "row": seat.row,
+                "number": seat.number,
+                "category": seat.category,
+                "price": seat.price,
+            })).collect();
+            return Ok((StatusCode::OK, Json(serde_json::json!({
+                "message": "Места успешно добавлены в бронь",
+                "seat_ids": seat_ids,
+                "seats": seats,
+            }))));
+        }
+        excluded.extend(conflicts);
+        state.cache.invalidate_seats(event_id).await;
+    }
+
+    Err(AppError::state_conflict("Подходящие места заняли другие покупатели, повторите запрос"))
+}
+
+/// Возвращает ID события бронирования, если оно принадлежит пользователю и в него
+/// еще можно добавлять места.
+async fn editable_booking_event_id(pool: &sqlx::PgPool, booking_id: i64, user_id: i32) -> AppResult<i64> {
+    let booking: Option<(i64, BookingStatus)> = sqlx::query_as(
+        "SELECT event_id, status FROM bookings WHERE id = $1 AND user_id = $2"
     )
-    .bind(req.booking_id)
-    .bind(req.seat_id)
-    .execute(&state.db.pool)
+    .bind(booking_id)
+    .bind(user_user_id_to_i64(user_id))
+    .fetch_optional(pool)
+    .await?;
+
+    match booking {
+        Some((event_id, BookingStatus::Created)) => Ok(event_id),
+        Some(_) => Err(AppError::state_conflict("В бронирование нельзя добавить места в текущем статусе")),
+        None => Err(AppError::state_conflict("Бронирование не найдено")),
+    }
+}
+
+/// Добавляет места в бронирование: все или ни одного. Удержания в Redis берутся
+/// одним Lua-скриптом, статусы в БД меняются одной транзакцией. Возвращает
+/// занятые места; пустой список - места добавлены в бронь.
+async fn claim_seats(
+    state: &AppState,
+    user_id: i32,
+    booking_id: i64,
+    event_id: i64,
+    seat_ids: &[i64],
+) -> AppResult<Vec<i64>> {
+    // Шаг 1: Удерживаем все места в Redis одной атомарной операцией.
+    let held = state.cache.reserve_seats(seat_ids, user_id).await
+        .map_err(|e| {
+            tracing::error!("claim_seats: failed to hold seats: {:?}", e);
+            AppError::ServiceUnavailable("Не удалось зарезервировать места".to_string())
+        })?;
+    if !held.is_empty() {
+        // Дополняем список местами, которые заняты в БД без удержания (например, проданы).
+        let mut conflicts = unavailable_seats(&state.db.pool, event_id, seat_ids).await?;
+        conflicts.extend(held);
+        conflicts.sort_unstable();
+        conflicts.dedup();
+        return Ok(conflicts);
+    }
+    state.seat_events.publish(event_id, seat_ids, SeatChange::Selected).await;
+
+    // Шаг 2: Переводим все места в RESERVED одной транзакцией.
+    let result = async {
+        let mut tx = state.db.pool.begin().await?;
+        // Места другого события считаются занятыми: транзакция откатится.
+        let reserved: Vec<i64> = state_machine::transition_seats(&mut tx, seat_ids, SeatStatus::Reserved, Some(booking_id))
+            .await?
+            .into_iter()
+            .filter(|(_, seat_event_id)| *seat_event_id == event_id)
+            .map(|(seat_id, _)| seat_id)
+            .collect();
+
+        if reserved.len() == seat_ids.len() {
+            tx.commit().await?;
+            Ok::<_, TransitionError>(Vec::new())
+        } else {
+            tx.rollback().await?;
+            Ok(seat_ids.iter().copied().filter(|id| !reserved.contains(id)).collect())
+        }
+    }.await;
+
+    match result {
+        Ok(conflicts) if conflicts.is_empty() => {
+            state.cache.invalidate_seats(event_id).await;
+            state.seat_events.publish(event_id, seat_ids, SeatChange::Reserved).await;
+            Ok(conflicts)
+        }
+        Ok(conflicts) => {
+            state.cache.release_seat_holds(seat_ids).await;
+            publish_actual_seat_status(state, event_id, seat_ids).await;
+            Ok(conflicts)
+        }
+        Err(e) => {
+            state.cache.release_seat_holds(seat_ids).await;
+            publish_actual_seat_status(state, event_id, seat_ids).await;
+            Err(AppError::from(e))
+        }
+    }
+}
+
+/// Рассылает статусы мест из БД после неудачной попытки занять их: клиенты уже
+/// получили `selected`, а место осталось свободным или было занято другим.
+async fn publish_actual_seat_status(state: &AppState, event_id: i64, seat_ids: &[i64]) {
+    let seats: Vec<(i64, SeatStatus)> = match sqlx::query_as(
+        "SELECT id, status FROM seats WHERE id = ANY($1) AND event_id = $2"
+    )
+    .bind(seat_ids)
+    .bind(event_id)
+    .fetch_all(&state.db.pool)
     .await
-    .map(|r| r.rows_affected() > 0)
-    .unwrap_or(false);
+    {
+        Ok(seats) => seats,
+        Err(e) => {
+            tracing::warn!("Failed to read seat statuses for event {}: {:?}", event_id, e);
+            return;
+        }
+    };
 
-    if ok {
-        // Если место успешно забронировано в БД, инвалидируем кэш.
-        if let Ok(Some(eid)) = seat_event_id(&state.db.pool, req.seat_id).await {
-            state.cache.invalidate_seats(eid).await;
+    let (mut released, mut reserved, mut sold) = (Vec::new(), Vec::new(), Vec::new());
+    for (seat_id, status) in seats {
+        match status {
+            SeatStatus::Free | SeatStatus::Selected => released.push(seat_id),
+            SeatStatus::Reserved => reserved.push(seat_id),
+            SeatStatus::Sold => sold.push(seat_id),
         }
-        Ok((StatusCode::OK, Json(serde_json::json!({"message":"Место успешно добавлено в бронь"}))))
-    } else {
-        // Если обновить БД не удалось (например, место уже было занято),
-        // необходимо откатить резерв в Redis.
-        let mut conn = state.redis.conn.clone();
-        let _ : Result<(), _> = redis::cmd("DEL")
-            .arg(format!("seat:{}:reserved", req.seat_id))
-            .query_async(&mut conn)
-            .await;
-        Err((status_419(), "Не удалось добавить место в бронь".to_string()))
     }
+    state.seat_events.publish(event_id, &released, SeatChange::Released).await;
+    state.seat_events.publish(event_id, &reserved, SeatChange::Reserved).await;
+    state.seat_events.publish(event_id, &sold, SeatChange::Sold).await;
+}
+
+/// Места события из списка, которые нельзя зарезервировать по данным БД.
+/// Несуществующие места и места другого события тоже считаются недоступными.
+async fn unavailable_seats(pool: &sqlx::PgPool, event_id: i64, seat_ids: &[i64]) -> sqlx::Result<Vec<i64>> {
+    let free: Vec<i64> = sqlx::query_scalar(
+        "SELECT id FROM seats WHERE id = ANY($1) AND event_id = $2 AND status = ANY($3)"
+    )
+    .bind(seat_ids)
+    .bind(event_id)
+    .bind(SeatStatus::sources(SeatStatus::Reserved))
+    .fetch_all(pool)
+    .await?;
+
+    Ok(seat_ids.iter().copied().filter(|id| !free.contains(id)).collect())
 }
 
 /// PATCH /api/seats/release
@@ -424,10 +713,10 @@ struct ReleaseSeatRequest { seat_id: i64 }
 async fn release_seat(
     State(state): State<Arc<AppState>>,
     user: crate::middleware::AuthUser,
-    Json(req): Json<ReleaseSeatRequest>,
-) -> Result<impl IntoResponse, (StatusCode, String)> {
+    AppJson(req): AppJson<ReleaseSeatRequest>,
+) -> AppResult<impl IntoResponse> {
     if req.seat_id <= 0 {
-        return Err((StatusCode::BAD_REQUEST, "seat_id должен быть > 0".to_string()));
+        return Err(AppError::bad_request("seat_id должен быть > 0"));
     }
 
     // Проверяем, что указанное место действительно зарезервировано
@@ -438,45 +727,50 @@ async fn release_seat(
           SELECT 1
           FROM seats s
           JOIN bookings b ON b.id = s.booking_id
-          WHERE s.id = $1 AND s.status = 'RESERVED' AND b.user_id = $2
+          WHERE s.id = $1 AND s.status = $3 AND b.user_id = $2
         )
         "#
     )
     .bind(req.seat_id)
     .bind(user_user_id_to_i64(user.user_id))
+    .bind(SeatStatus::Reserved)
     .fetch_one(&state.db.pool)
     .await
     .unwrap_or(false);
 
     if !seat_ok {
-        return Err((StatusCode::FORBIDDEN, "Место не найдено или не принадлежит вам".to_string()));
+        return Err(AppError::forbidden("Место не найдено или не принадлежит вам"));
     }
 
     // Обновляем статус места на 'FREE' в базе данных.
-    let ok = sqlx::query(
-        "UPDATE seats SET status = 'FREE', booking_id = NULL WHERE id = $1 AND status = 'RESERVED'"
-    )
-    .bind(req.seat_id)
-    .execute(&state.db.pool)
-    .await
-    .map(|r| r.rows_affected() > 0)
-    .unwrap_or(false);
+    let ok = match state.db.pool.acquire().await {
+        Ok(mut conn) => {
+            match state_machine::transition_seat(&mut conn, req.seat_id, SeatStatus::Free, None).await {
+                Ok(()) => true,
+                Err(e) => {
+                    tracing::debug!("release_seat {} rejected: {}", req.seat_id, e);
+                    false
+                }
+            }
+        }
+        Err(e) => {
+            tracing::error!("release_seat: failed to acquire connection: {:?}", e);
+            false
+        }
+    };
 
     if ok {
         // При успехе удаляем временный резерв из Redis и инвалидируем кэш.
-        let mut conn = state.redis.conn.clone();
-        let _ : Result<(), _> = redis::cmd("DEL")
-            .arg(format!("seat:{}:reserved", req.seat_id))
-            .query_async(&mut conn)
-            .await;
+        state.cache.release_seat_holds(&[req.seat_id]).await;
 
         if let Ok(Some(eid)) = seat_event_id(&state.db.pool, req.seat_id).await {
             state.cache.invalidate_seats(eid).await;
+            state.seat_events.publish(eid, &[req.seat_id], SeatChange::Released).await;
         }
 
         Ok((StatusCode::OK, Json(serde_json::json!({"message":"Место успешно освобождено"}))))
     } else {
-        Err((status_419(), "Не удалось освободить место".to_string()))
+        Err(AppError::state_conflict("Не удалось освободить место"))
     }
 }
 
@@ -487,14 +781,14 @@ async fn release_seat(
 /// сбрасывает статусы мест и очищает кэш.
 async fn reset_all_test_data(
     State(state): State<Arc<AppState>>,
-) -> Result<impl IntoResponse, (StatusCode, String)> {
+) -> AppResult<impl IntoResponse> {
     tracing::warn!("🔴 RESET: Начинаем полный сброс тестовых данных");
     
     // Используем транзакцию, чтобы сброс был атомарным.
     let mut tx = state.db.pool.begin().await
         .map_err(|e| {
             tracing::error!("RESET: Не удалось начать транзакцию: {:?}", e);
-            (StatusCode::INTERNAL_SERVER_ERROR, "Ошибка начала транзакции".to_string())
+            AppError::internal("Ошибка начала транзакции")
         })?;
 
     // Шаг 1: Собираем ID всех событий, затронутых бронированиями, для инвалидации кэша.
@@ -505,21 +799,24 @@ async fn reset_all_test_data(
     .await
     .unwrap_or_default();
 
-    // Шаг 2: Сбрасываем все занятые места в статус 'FREE'.
-    let freed_seats = sqlx::query(
+    // Шаг 2: Сбрасываем все зарезервированные места в статус 'FREE'.
+    // Проданные места остаются проданными - такой переход запрещен.
+    let freed_seats = sqlx::query_as::<_, (i64, i64)>(
         r#"
         UPDATE seats
-        SET status = 'FREE',
+        SET status = $1,
             booking_id = NULL
-        WHERE status IN ('RESERVED', 'SELECTED')
-        RETURNING id
+        WHERE status = ANY($2)
+        RETURNING id, event_id
         "#
     )
+    .bind(SeatStatus::Free)
+    .bind(SeatStatus::sources(SeatStatus::Free))
     .fetch_all(&mut *tx)
     .await
     .map_err(|e| {
         tracing::error!("RESET: Ошибка сброса мест: {:?}", e);
-        (StatusCode::INTERNAL_SERVER_ERROR, "Ошибка сброса мест".to_string())
+        AppError::internal("Ошибка сброса мест")
     })?;
 
     let seats_reset_count = freed_seats.len();
@@ -533,7 +830,7 @@ async fn reset_all_test_data(
     .await
     .map_err(|e| {
         tracing::error!("RESET: Ошибка удаления платежей: {:?}", e);
-        (StatusCode::INTERNAL_SERVER_ERROR, "Ошибка удаления платежей".to_string())
+        AppError::internal("Ошибка удаления платежей")
     })?;
     
     tracing::info!("RESET: Удалено {} платежных транзакций", payment_result.rows_affected());
@@ -546,7 +843,7 @@ async fn reset_all_test_data(
     .await
     .map_err(|e| {
         tracing::error!("RESET: Ошибка удаления бронирований: {:?}", e);
-        (StatusCode::INTERNAL_SERVER_ERROR, "Ошибка удаления бронирований".to_string())
+        AppError::internal("Ошибка удаления бронирований")
     })?;
     
     tracing::info!("RESET: Удалено {} бронирований", bookings_result.rows_affected());
@@ -562,7 +859,7 @@ async fn reset_all_test_data(
     tx.commit().await
         .map_err(|e| {
             tracing::error!("RESET: Ошибка коммита транзакции: {:?}", e);
-            (StatusCode::INTERNAL_SERVER_ERROR, "Ошибка фиксации изменений".to_string())
+            AppError::internal("Ошибка фиксации изменений")
         })?;
 
     // Шаг 6: Очищаем все временные резервы мест в Redis.
@@ -581,6 +878,10 @@ async fn reset_all_test_data(
         let _: Result<(), _> = pipe.query_async(&mut redis_conn).await;
         tracing::info!("RESET: Удалено {} резервов в Redis", keys.len());
     }
+    let _: Result<(), _> = redis::cmd("DEL")
+        .arg(SEAT_HOLDS_INDEX_KEY)
+        .query_async(&mut redis_conn)
+        .await;
 
     // Шаг 7: Инвалидируем кэш для всех затронутых событий.
     for event_id in &event_ids {
@@ -588,6 +889,15 @@ async fn reset_all_test_data(
         tracing::debug!("RESET: Инвалидирован кеш для event_id={}", event_id);
     }
 
+    // Сообщаем подписчикам карт мест об освобожденных местах.
+    let mut freed_by_event: BTreeMap<i64, Vec<i64>> = BTreeMap::new();
+    for (seat_id, event_id) in &freed_seats {
+        freed_by_event.entry(*event_id).or_default().push(*seat_id);
+    }
+    for (event_id, seat_ids) in &freed_by_event {
+        state.seat_events.publish(*event_id, seat_ids, SeatChange::Released).await;
+    }
+
     // Шаг 8: Очищаем весь кэш со списками мест.
     let seat_keys: Vec<String> = redis::cmd("KEYS")
         .arg("seats:*")
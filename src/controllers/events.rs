@@ -2,36 +2,70 @@
 //!
 //! Модуль для поиска и получения информации о событиях.
 //!
-//! Основная функциональность - это поиск событий с использованием кэширования
-//! в Redis для снижения нагрузки на базу данных и ускорения ответов.
+//! Основная функциональность - это поиск событий с фильтрами по типу,
+//! организатору и датам с использованием кэширования в Redis для снижения
+//! нагрузки на базу данных и ускорения ответов, а также карточка события
+//! с доступностью мест и диапазоном цен.
 
 use axum::{
     body::Body,
-    extract::{Query, State},
+    extract::State,
     response::{IntoResponse, Response},
     routing::get,
     Json, Router,
 };
-use chrono::NaiveDate;
+use chrono::{Days, NaiveDate, NaiveDateTime};
 use serde::{Deserialize, Serialize};
 use serde_json::json;
+use sqlx::PgPool;
 use std::sync::Arc;
-use crate::AppState;
+use crate::{
+    AppState,
+    cache::{
+        recent_writes::WriteScope,
+        search::{normalize_query, SearchCacheKey, SEARCH_RESULT_TTL_SECONDS},
+    },
+    cursor::{self, EventCursor, MAX_CURSOR_PAGE_SIZE},
+    error::{AppError, AppResult},
+    extract::{AppPath, AppQuery},
+    models::{event::EVENT_COLUMNS, Event, EventFilters, EventStatus, SeatStatus},
+};
 
 /// Определяет маршруты, связанные с событиями.
 pub fn routes() -> Router<Arc<AppState>> {
-    Router::new()
+    let router = Router::new()
         .route("/events", get(search_events))
+        .route("/events/search", get(search_events))
+        .route("/events/{id}", get(get_event));
+    #[cfg(feature = "search")]
+    let router = router.route("/events/suggest", get(suggest_events));
+    router
 }
 
 /// Параметры запроса для поиска событий.
 #[derive(Debug, Deserialize)]
 pub struct EventsQuery {
+    /// Текст полнотекстового поиска; `q` - синоним для `/api/events/search`.
+    #[serde(alias = "q")]
     pub query: Option<String>,
+    #[serde(rename = "type")]
+    pub event_type: Option<String>,
+    pub provider: Option<String>,
+    /// Первый день периода, `YYYY-MM-DD`.
+    pub date_from: Option<String>,
+    /// Последний день периода (включительно), `YYYY-MM-DD`.
+    pub date_to: Option<String>,
+    /// Устаревший синоним `date_from`.
     pub date: Option<String>,
+    /// Устаревшая постраничная навигация; вместо нее - `cursor`.
     pub page: Option<u32>,
     #[serde(rename = "pageSize")]
     pub page_size: Option<u32>,
+    /// `next_cursor` предыдущего ответа; пустая строка - первая страница.
+    pub cursor: Option<String>,
+    /// Добавить к результатам полнотекстового поиска `snippet` с подсветкой.
+    #[serde(default)]
+    pub highlight: bool,
 }
 
 /// Структура ответа для одного события.
@@ -39,101 +73,415 @@ pub struct EventsQuery {
 pub struct EventResponse {
     pub id: i64,
     pub title: String,
-    pub datetime_start: chrono::NaiveDateTime,
+    #[serde(rename = "type")]
+    pub event_type: String,
+    pub provider: String,
+    pub datetime_start: NaiveDateTime,
+    /// Релевантность в полнотекстовом (или нечетком) поиске; без запроса не отдается.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rank: Option<f32>,
+    /// Фрагмент `title` и `description` с подсветкой совпадений (`highlight=true`).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub snippet: Option<String>,
 }
 
-/// GET /api/events
+impl EventResponse {
+    fn cursor(&self) -> EventCursor {
+        EventCursor { datetime_start: self.datetime_start, id: self.id, rank: self.rank }
+    }
+}
+
+/// Разбирает дату `YYYY-MM-DD` из параметра запроса.
+fn parse_date(field: &str, value: Option<&str>) -> AppResult<Option<NaiveDate>> {
+    value
+        .map(str::trim)
+        .filter(|v| !v.is_empty())
+        .map(|v| {
+            NaiveDate::parse_from_str(v, "%Y-%m-%d")
+                .map_err(|_| AppError::bad_request(format!("{} должен быть в формате YYYY-MM-DD", field)))
+        })
+        .transpose()
+}
+
+/// Пустая строка в фильтре означает отсутствие фильтра.
+fn non_empty(value: Option<String>) -> Option<String> {
+    value.map(|v| v.trim().to_string()).filter(|v| !v.is_empty())
+}
+
+/// GET /api/events, GET /api/events/search
 ///
-/// Ищет события по заданным параметрам (текстовый запрос, дата).
+/// Ищет события по заданным параметрам (текстовый запрос, тип, организатор,
+/// период `date_from`..=`date_to`). Без `date_from` показываются предстоящие события.
 /// Результаты поиска кэшируются в Redis для ускорения повторных запросов
 /// с теми же параметрами.
 pub async fn search_events(
     State(state): State<Arc<AppState>>,
-    Query(params): Query<EventsQuery>,
-) -> Response {
-    let query_val = params.query.as_deref().unwrap_or_default();
-    let date_val = params.date.as_deref().unwrap_or_default();
+    AppQuery(params): AppQuery<EventsQuery>,
+) -> AppResult<Response> {
+    let date_from = match parse_date("date_from", params.date_from.as_deref())? {
+        Some(date) => Some(date),
+        None => parse_date("date", params.date.as_deref())?,
+    };
+    let date_to = parse_date("date_to", params.date_to.as_deref())?;
+    if let (Some(from), Some(to)) = (date_from, date_to) {
+        if from > to {
+            return Err(AppError::bad_request("date_from не может быть позже date_to"));
+        }
+    }
+    let filters = EventFilters {
+        query: normalize_query(params.query.as_deref().unwrap_or_default()),
+        event_type: non_empty(params.event_type),
+        provider: non_empty(params.provider),
+        date_from: date_from.map(|d| d.and_time(chrono::NaiveTime::MIN)),
+        // Последний день включается целиком: граница - полночь следующего дня.
+        date_to: date_to.and_then(|d| d.checked_add_days(Days::new(1))).map(|d| d.and_time(chrono::NaiveTime::MIN)),
+    };
+    // С `cursor` страница выбирается по позиции последней записи (keyset),
+    // без него - по номеру страницы через OFFSET, как раньше.
+    let cursor_mode = params.cursor.is_some();
+    if cursor_mode && params.page.is_some() {
+        return Err(AppError::bad_request("page и cursor нельзя передавать вместе"));
+    }
+    let after = cursor::decode_param::<EventCursor>(params.cursor.as_deref())?;
     let page = params.page.unwrap_or(1);
-    let page_size = params.page_size.unwrap_or(20).clamp(1, 20);
+    let max_page_size = if cursor_mode { MAX_CURSOR_PAGE_SIZE } else { 20 };
+    let page_size = params.page_size.unwrap_or(20).clamp(1, max_page_size);
 
-    // Шаг 1: Формируем уникальный ключ для кэша на основе всех параметров запроса.
-    // Это гарантирует, что каждый уникальный поисковый запрос будет кэширован отдельно.
-    let cache_key = format!(
-        "search:events:q={}&date={}&p={}&ps={}",
-        query_val, date_val, page, page_size
-    );
+    // Шаг 1: Формируем ключ кэша из нормализованных параметров запроса:
+    // одинаковые по смыслу запросы ("Rock" и "rock ") попадают в одну запись.
+    let cache_key = SearchCacheKey::new("list")
+        .param("q", &filters.query)
+        .param("type", filters.event_type.as_deref().unwrap_or_default())
+        .param("provider", filters.provider.as_deref().unwrap_or_default())
+        .param("from", date_from.map(|d| d.to_string()).unwrap_or_default())
+        .param("to", date_to.map(|d| d.to_string()).unwrap_or_default())
+        .param("p", page.max(1))
+        .param("ps", page_size)
+        .param("c", after.as_ref().map(cursor::encode).unwrap_or_default())
+        .param("hl", params.highlight);
+    // Без Redis поиск работает без кэша.
+    let cache_key = state.cache.resolve_search_key(&cache_key).await.ok();
 
     // Шаг 2: Пытаемся получить результат из кэша Redis.
-    if let Ok(Some(cached_json)) = state.cache.get_cached_search(&cache_key).await {
-        // Cache HIT: Данные найдены в кэше.
-        // Отправляем их клиенту с заголовком X-Cache: HIT для отладки.
-        return Response::builder()
-            .header("Content-Type", "application/json")
-            .header("X-Cache", "HIT")
-            .body(Body::from(cached_json))
-            .unwrap();
+    if let Some(key) = &cache_key {
+        if let Ok(Some(cached_json)) = state.cache.get_cached_search(key).await {
+            return Ok(json_response(cached_json, CacheStatus::Hit));
+        }
     }
 
     // Шаг 3: Cache MISS. Если в кэше данных нет, выполняем запрос к поисковому сервису (например, ElasticSearch или БД).
-    let from_date = params.date.and_then(|s| {
-        NaiveDate::parse_from_str(&s, "%Y-%m-%d")
-            .map(|d| d.and_hms_opt(0, 0, 0).unwrap())
-            .ok()
-    });
-
-    let limit: i64 = page_size as i64;
-    let offset: i64 = ((page.max(1) - 1) * page_size) as i64;
-
-    let search_result = state.search_client.search_events(
-        query_val,
-        limit,
-        offset,
-        from_date,
-    ).await;
+    // Запрашиваем на одну запись больше: по ней видно, есть ли следующая страница.
+    let limit: i64 = page_size as i64 + 1;
+    let offset: i64 = if cursor_mode { 0 } else { ((page.max(1) - 1) * page_size) as i64 };
+
+    // Итоги и фасеты считаются параллельно со страницей, по возможности на реплике.
+    let pool = state.read_pool(WriteScope::Events).await;
+    let (search_result, facets) = tokio::join!(
+        find_events(&state, pool, &filters, limit, offset, after.as_ref(), params.highlight),
+        find_facets(&state, pool, &filters),
+    );
     
     // Формируем JSON-ответ на основе результатов поиска.
+    let mut event_ids = Vec::new();
     let response_json = match search_result {
-        Ok(results) => {
-            let events_response: Vec<EventResponse> = results
-                .into_iter()
-                .map(|r| EventResponse {
-                    id: r.id,
-                    title: r.title,
-                    datetime_start: r.datetime_start,
-                })
-                .collect();
-
-            json!({
+        Ok(mut events_response) => {
+            let has_more = events_response.len() > page_size as usize;
+            events_response.truncate(page_size as usize);
+            event_ids.extend(events_response.iter().map(|event| event.id));
+            let next_cursor = events_response
+                .last()
+                .filter(|_| has_more)
+                .map(|event| cursor::encode(&event.cursor()));
+            let mut response = json!({
                 "success": true,
                 "events": events_response,
-                "count": events_response.len()
-            })
+                "count": events_response.len(),
+                "next_cursor": next_cursor
+            });
+            // Без итогов страница все равно полезна: ошибка фасетов только логируется.
+            match facets {
+                Ok(Some(mut facets)) => {
+                    response["total"] = facets["total"].take();
+                    response["total_estimated"] = facets["total_estimated"].take();
+                    response["facets"] = json!({
+                        "type": facets["type"].take(),
+                        "provider": facets["provider"].take(),
+                        "month": facets["month"].take(),
+                    });
+                }
+                Ok(None) => {}
+                Err(e) => tracing::warn!("Failed to count search facets: {:?}", e),
+            }
+            response
         },
         Err(e) => {
             tracing::error!("Failed to search events: {:?}", e);
-            return Json(json!({
-                "success": false,
-                "error": "Failed to retrieve events"
-            })).into_response();
+            return Err(AppError::internal("Failed to retrieve events"));
         }
     };
     
-    // Шаг 4: Сохраняем полученный результат в кэш Redis на 1 час (3600 секунд).
-    // Следующий запрос с такими же параметрами будет обслужен из кэша.
-    if let Ok(json_str) = serde_json::to_string(&response_json) {
-        if let Err(e) = state.cache.cache_search_result(&cache_key, &json_str, 3600).await {
+    // Шаг 4: Сохраняем полученный результат в кэш Redis на 1 час с тегами событий
+    // страницы: распродажа события сбросит только записи, где оно есть.
+    let json_str = response_json.to_string();
+    if let Some(key) = &cache_key {
+        if let Err(e) = state
+            .cache
+            .cache_search_result(key, &json_str, SEARCH_RESULT_TTL_SECONDS, &event_ids)
+            .await
+        {
             tracing::error!("Failed to cache search result: {:?}", e);
         }
-        
-        // Отправляем ответ клиенту с заголовком X-Cache: MISS, указывая,
-        // что ответ был сгенерирован, а не взят из кэша.
-        return Response::builder()
-            .header("Content-Type", "application/json")
-            .header("X-Cache", "MISS")
-            .body(Body::from(json_str))
-            .unwrap();
     }
 
-    // Резервный вариант на случай, если сериализация в JSON не удалась.
-    Json(response_json).into_response()
-}
\ No newline at end of file
+    Ok(json_response(json_str, CacheStatus::Miss))
+}
+
+/// Откуда взят ответ; отдается в заголовке `X-Cache`.
+#[derive(Debug, Clone, Copy)]
+enum CacheStatus {
+    Hit,
+    Miss,
+}
+
+/// JSON-ответ поиска с заголовком `X-Cache`.
+fn json_response(body: String, cache: CacheStatus) -> Response {
+    let cache = match cache {
+        CacheStatus::Hit => "HIT",
+        CacheStatus::Miss => "MISS",
+    };
+    Response::builder()
+        .header("Content-Type", "application/json")
+        .header("X-Cache", cache)
+        .body(Body::from(body))
+        .unwrap()
+}
+
+/// Ищет события через `SearchClient` (полнотекстовый поиск).
+#[cfg(feature = "search")]
+async fn find_events(
+    state: &AppState,
+    pool: &PgPool,
+    filters: &EventFilters,
+    limit: i64,
+    offset: i64,
+    after: Option<&EventCursor>,
+    highlight: bool,
+) -> Result<Vec<EventResponse>, sqlx::Error> {
+    let results = state
+        .search_client
+        .with_pool(pool)
+        .search_events(filters, limit, offset, after, highlight)
+        .await?;
+
+    Ok(results
+        .into_iter()
+        .map(|r| EventResponse {
+            id: r.id,
+            title: r.title,
+            event_type: r.event_type,
+            provider: r.provider,
+            datetime_start: r.datetime_start,
+            rank: r.rank,
+            snippet: r.snippet,
+        })
+        .collect())
+}
+
+/// Общее число совпадений и фасеты через `SearchClient` (`SearchFacets` в JSON).
+#[cfg(feature = "search")]
+async fn find_facets(
+    state: &AppState,
+    pool: &PgPool,
+    filters: &EventFilters,
+) -> Result<Option<serde_json::Value>, sqlx::Error> {
+    let facets = state.search_client.with_pool(pool).facets(filters).await?;
+    Ok(Some(json!(facets)))
+}
+
+/// Сборка без `search`: итоги и фасеты не считаются.
+#[cfg(not(feature = "search"))]
+async fn find_facets(
+    _state: &AppState,
+    _pool: &PgPool,
+    _filters: &EventFilters,
+) -> Result<Option<serde_json::Value>, sqlx::Error> {
+    Ok(None)
+}
+
+/// Сборка без `search`: простой список предстоящих событий по дате с фильтрами.
+/// Текстовый запрос и `highlight` игнорируются - полнотекстовый поиск не скомпилирован.
+#[cfg(not(feature = "search"))]
+async fn find_events(
+    _state: &AppState,
+    pool: &PgPool,
+    filters: &EventFilters,
+    limit: i64,
+    offset: i64,
+    after: Option<&EventCursor>,
+    _highlight: bool,
+) -> Result<Vec<EventResponse>, sqlx::Error> {
+    let mut query = sqlx::QueryBuilder::<sqlx::Postgres>::new(format!(
+        "SELECT id, title, type, provider, datetime_start FROM {} WHERE TRUE",
+        filters.source_table()
+    ));
+    filters.push_conditions(&mut query);
+    if let Some(cursor) = after {
+        cursor.push_after(&mut query);
+    }
+    query
+        .push(" ORDER BY datetime_start, id LIMIT ")
+        .push_bind(limit)
+        .push(" OFFSET ")
+        .push_bind(offset);
+
+    let rows = query
+        .build_query_as::<(i64, String, String, String, NaiveDateTime)>()
+        .fetch_all(pool)
+        .await?;
+
+    Ok(rows
+        .into_iter()
+        .map(|(id, title, event_type, provider, datetime_start)| EventResponse {
+            id,
+            title,
+            event_type,
+            provider,
+            datetime_start,
+            rank: None,
+            snippet: None,
+        })
+        .collect())
+}
+
+/// Параметры автодополнения.
+#[cfg(feature = "search")]
+#[derive(Debug, Deserialize)]
+struct SuggestQuery {
+    prefix: String,
+    limit: Option<u32>,
+}
+
+/// GET /api/events/suggest
+///
+/// Подсказки названий событий по началу ввода, включая недописанные слова
+/// и опечатки. Кэшируются на минуту вместе с результатами поиска
+/// (`cache::search`) и сбрасываются вместе с ними.
+#[cfg(feature = "search")]
+async fn suggest_events(
+    State(state): State<Arc<AppState>>,
+    AppQuery(params): AppQuery<SuggestQuery>,
+) -> AppResult<Response> {
+    let prefix = normalize_query(&params.prefix);
+    let length = prefix.chars().count();
+    if !(2..=100).contains(&length) {
+        return Err(AppError::bad_request("prefix должен быть от 2 до 100 символов"));
+    }
+    let limit = params.limit.unwrap_or(10).clamp(1, 20);
+
+    let cache_key = SearchCacheKey::new("suggest").param("prefix", &prefix).param("l", limit);
+    let cache_key = state.cache.resolve_search_key(&cache_key).await.ok();
+    if let Some(key) = &cache_key {
+        if let Ok(Some(cached_json)) = state.cache.get_cached_search(key).await {
+            return Ok(json_response(cached_json, CacheStatus::Hit));
+        }
+    }
+
+    let suggestions = state
+        .search_client
+        .with_pool(state.read_pool(WriteScope::Events).await)
+        .suggest(&prefix, limit as i64)
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to suggest events: {:?}", e);
+            AppError::internal("Failed to retrieve suggestions")
+        })?;
+    let event_ids: Vec<i64> = suggestions.iter().map(|s| s.id).collect();
+    let json_str = json!({ "success": true, "suggestions": suggestions }).to_string();
+
+    if let Some(key) = &cache_key {
+        if let Err(e) = state.cache.cache_search_result(key, &json_str, 60, &event_ids).await {
+            tracing::error!("Failed to cache suggestions: {:?}", e);
+        }
+    }
+    Ok(json_response(json_str, CacheStatus::Miss))
+}
+
+/// Доступность мест события по статусам с учетом удержаний в Redis.
+#[derive(Debug, Default, Serialize)]
+struct SeatAvailability {
+    total: usize,
+    free: usize,
+    selected: usize,
+    reserved: usize,
+    sold: usize,
+}
+
+#[derive(Debug, Clone, Copy, Serialize)]
+struct PriceRange {
+    min: f64,
+    max: f64,
+}
+
+impl PriceRange {
+    fn of(prices: impl Iterator<Item = f64>) -> Option<Self> {
+        prices.fold(None, |range, price| match range {
+            None => Some(Self { min: price, max: price }),
+            Some(r) => Some(Self { min: r.min.min(price), max: r.max.max(price) }),
+        })
+    }
+}
+
+/// Карточка события.
+#[derive(Debug, Serialize)]
+struct EventDetail {
+    #[serde(flatten)]
+    event: Event,
+    availability: SeatAvailability,
+    /// Цены всех мест события; `None`, если мест с ценой нет.
+    price_range: Option<PriceRange>,
+    /// Цены свободных мест; `None`, если свободных мест нет.
+    available_price_range: Option<PriceRange>,
+}
+
+/// GET /api/events/{id}
+///
+/// Возвращает событие целиком (описание, тип, организатор, площадка, статус)
+/// с доступностью мест и диапазоном цен. Снятое с публикации событие не
+/// отдается; отмененное отдается со статусом `cancelled`.
+async fn get_event(
+    State(state): State<Arc<AppState>>,
+    AppPath(event_id): AppPath<i64>,
+) -> AppResult<impl IntoResponse> {
+    let event = sqlx::query_as::<_, Event>(&format!(
+        "SELECT {} FROM events_archive WHERE id = $1 AND status <> $2",
+        EVENT_COLUMNS
+    ))
+    .bind(event_id)
+    .bind(EventStatus::Unpublished)
+    .fetch_optional(&state.db.pool)
+    .await?
+    .ok_or_else(|| AppError::not_found("Событие не найдено"))?;
+
+    let seats = state.cache.get_seats(event_id).await;
+    let mut availability = SeatAvailability { total: seats.len(), ..Default::default() };
+    for seat in &seats {
+        match seat.status {
+            SeatStatus::Free => availability.free += 1,
+            SeatStatus::Selected => availability.selected += 1,
+            SeatStatus::Reserved => availability.reserved += 1,
+            SeatStatus::Sold => availability.sold += 1,
+        }
+    }
+    let price_range = PriceRange::of(seats.iter().filter_map(|seat| seat.price));
+    let available_price_range = PriceRange::of(
+        seats
+            .iter()
+            .filter(|seat| seat.status == SeatStatus::Free)
+            .filter_map(|seat| seat.price),
+    );
+
+    Ok(Json(json!({
+        "success": true,
+        "event": EventDetail { event, availability, price_range, available_price_range },
+    })))
+}
@@ -2,10 +2,17 @@
 //!
 //! Корневой модуль маршрутизации API.
 
+pub mod admin;
+pub mod admin_events;
+pub mod admin_seats;
+#[cfg(feature = "analytics")]
 pub mod analytics;
+#[cfg(feature = "auth")]
+pub mod auth;
 pub mod bookings;
 pub mod events;
 pub mod payment;
+pub mod seat_map;
 
 use axum::{
     Router,
@@ -13,28 +20,39 @@ use axum::{
     routing::{get, post, patch},
 };
 use std::sync::Arc;
-use crate::{AppState, middleware::require_auth};
+use crate::{AppState, middleware::{require_admin, require_auth}};
+#[cfg(feature = "rate-limiting")]
+use crate::middleware::rate_limit::rate_limit;
 
 /// Собирает и возвращает главный маршрутизатор приложения.
 ///
 /// # Arguments
 /// * `state` - Общее состояние приложения (`Arc<AppState>`), которое будет доступно
-///             во всех обработчиках.
+///   во всех обработчиках.
 pub fn routes(state: Arc<AppState>) -> Router<Arc<crate::AppState>> {
     // --- Защищенные маршруты ---
     // Группа маршрутов, для доступа к которым пользователь должен быть аутентифицирован.
-    // Мидлвэр `require_auth` проверяет наличие и валидность токена.
+    // Мидлвэр `require_auth` принимает Basic-учетные данные или Bearer-токен (JWT).
     let protected_routes = Router::new()
         .merge(bookings::routes())
         // Маршруты для инициации и проверки статуса платежа.
         .route("/bookings/initiatePayment", patch(payment::initiate_payment))
-        .route("/bookings/{booking_id}/payment-status", get(payment::get_payment_status))
+        .route("/bookings/{booking_id}/payment-status", get(payment::get_payment_status));
+
+    // Лимиты для защищенных маршрутов вешаются до `require_auth`, чтобы мидлвэр
+    // выполнялся после авторизации и считал запросы по `AuthUser.user_id`.
+    #[cfg(feature = "rate-limiting")]
+    let protected_routes = protected_routes.layer(from_fn_with_state(state.clone(), rate_limit));
+
+    let protected_routes = protected_routes
         .layer(from_fn_with_state(state.clone(), require_auth));
 
     // --- Публичные маршруты ---
     // Группа маршрутов, которые не требуют аутентификации.
     let public_routes = Router::new()
         .merge(events::routes())
+        // Карта мест и WebSocket с ее изменениями.
+        .merge(seat_map::routes())
         .merge(bookings::reset_route())
         // Вебхук от платежной системы, который не требует аутентификации.
         .route("/webhook/payment", post(payment::payment_webhook))
@@ -42,11 +60,29 @@ pub fn routes(state: Arc<AppState>) -> Router<Arc<crate::AppState>> {
         .route("/payments/success", get(payment::payment_success_handler))
         .route("/payments/fail", get(payment::payment_fail_handler))
         // Эндпоинт для мониторинга состояния автоматического выключателя (Circuit Breaker).
-        .route("/payments/circuit-breaker-status", get(payment::get_circuit_breaker_status))
-        .merge(analytics::routes());
+        .route("/payments/circuit-breaker-status", get(payment::get_circuit_breaker_status));
+
+    // Выдача, обновление и отзыв JWT. Учетные данные проверяются в самих обработчиках.
+    #[cfg(feature = "auth")]
+    let public_routes = public_routes.merge(auth::routes());
+
+    #[cfg(feature = "analytics")]
+    let public_routes = public_routes.merge(analytics::routes());
+
+    // Публичные маршруты ограничиваются по IP клиента.
+    #[cfg(feature = "rate-limiting")]
+    let public_routes = public_routes.layer(from_fn_with_state(state.clone(), rate_limit));
+
+    // --- Административные маршруты ---
+    // Доступны только пользователям из `ADMIN_EMAILS`.
+    let admin_routes = admin::routes()
+        .merge(admin_events::routes())
+        .merge(admin_seats::routes())
+        .layer(from_fn_with_state(state.clone(), require_admin));
 
-    // Объединяем публичные и защищенные маршруты в один роутер.
+    // Объединяем публичные, защищенные и административные маршруты в один роутер.
     Router::new()
         .merge(public_routes)
         .merge(protected_routes)
+        .merge(admin_routes)
 }
\ No newline at end of file
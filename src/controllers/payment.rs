@@ -8,18 +8,24 @@
 //! - Мониторинг состояния Circuit Breaker для платежного шлюза.
 
 use axum::{
-    extract::{State, Json, Path},
+    extract::{State, Json},
     http::StatusCode,
     response::IntoResponse,
 };
-use serde::{Deserialize, Serialize};
+use serde::Deserialize;
 use serde_json::json;
 use std::sync::Arc;
 use chrono::Utc;
 
 use crate::{
     AppState,
+    error::{AppError, AppResult},
+    extract::{AppJson, AppPath},
     middleware::AuthUser,
+    models::{
+        state_machine::{self, StatusMachine, TransitionError},
+        BookingStatus, PaymentStatus, SeatStatus,
+    },
     services::payment::PaymentGatewayClient,
 };
 
@@ -31,27 +37,6 @@ pub struct InitiatePaymentRequest {
     pub booking_id: i64,
 }
 
-/// Стандартная структура для ответа с ошибкой API.
-#[derive(Serialize)]
-pub struct ApiError {
-    success: bool,
-    message: String,
-    code: Option<i32>,
-}
-
-/// Тип-обертка для стандартизации ответов API.
-type ApiResult<T> = Result<T, (StatusCode, Json<ApiError>)>;
-
-/// Вспомогательная функция для создания стандартного ответа с ошибкой.
-fn to_api_error(status: StatusCode, message: &str) -> (StatusCode, Json<ApiError>) {
-    (status, Json(ApiError { success: false, message: message.to_string(), code: None }))
-}
-
-/// Вспомогательная функция для создания ответа с ошибкой, включающего код ошибки.
-fn to_api_error_with_code(status: StatusCode, message: &str, code: i32) -> (StatusCode, Json<ApiError>) {
-    (status, Json(ApiError { success: false, message: message.to_string(), code: Some(code) }))
-}
-
 // --- Обработчики HTTP запросов ---
 
 /// PATCH /api/bookings/initiatePayment
@@ -65,44 +50,51 @@ fn to_api_error_with_code(status: StatusCode, message: &str, code: i32) -> (Stat
 pub async fn initiate_payment(
     State(state): State<Arc<AppState>>,
     user: AuthUser,
-    Json(req): Json<InitiatePaymentRequest>,
-) -> ApiResult<impl IntoResponse> {
+    AppJson(req): AppJson<InitiatePaymentRequest>,
+) -> AppResult<impl IntoResponse> {
     if req.booking_id <= 0 {
-        return Err(to_api_error(StatusCode::BAD_REQUEST, "Booking ID must be > 0"));
+        return Err(AppError::bad_request("Booking ID must be > 0"));
     }
 
-    // Получаем из базы данные о бронировании: его ID, название события,
+    // Получаем из базы данные о бронировании: его ID, статус, название события,
     // общую стоимость, количество мест и email пользователя.
-    let booking_data: Option<(i64, String, f64, i32, String)> = sqlx::query_as(
+    let booking_data: Option<(i64, BookingStatus, String, f64, i32, String)> = sqlx::query_as(
         r#"
-        SELECT b.id, e.title,
+        SELECT b.id, b.status, e.title,
                COALESCE(SUM(s.price), 0) as total_price,
                COUNT(s.id)::int as seat_count,
                u.email
         FROM bookings b
         JOIN events_archive e ON e.id = b.event_id
         JOIN users u ON u.user_id = b.user_id
-        LEFT JOIN seats s ON s.booking_id = b.id AND s.status = 'RESERVED'
+        LEFT JOIN seats s ON s.booking_id = b.id AND s.status = $3
         WHERE b.id = $1 AND b.user_id = $2
-        GROUP BY b.id, e.title, u.email
+        GROUP BY b.id, b.status, e.title, u.email
         HAVING COUNT(s.id) > 0
         "#
     )
     .bind(req.booking_id)
     .bind(user.user_id)
+    .bind(SeatStatus::Reserved)
     .fetch_optional(&state.db.pool)
     .await
     .map_err(|e| {
         tracing::error!("Database error getting booking: {:?}", e);
-        to_api_error(StatusCode::INTERNAL_SERVER_ERROR, "Database error")
+        AppError::internal("Database error")
     })?;
 
-    let (booking_id, event_title, total_price, seat_count, user_email) = booking_data
-        .ok_or_else(|| to_api_error(StatusCode::NOT_FOUND, "Booking not found or empty"))?;
+    let (booking_id, booking_status, event_title, total_price, seat_count, user_email) = booking_data
+        .ok_or_else(|| AppError::not_found("Booking not found or empty"))?;
+
+    // Оплаченную или отмененную бронь оплатить нельзя - проверяем до обращения к шлюзу.
+    if let Err(e) = booking_status.transition_to(BookingStatus::PendingPayment) {
+        tracing::info!("initiate_payment for booking {} rejected: {}", booking_id, e);
+        return Err(AppError::state_conflict("Booking cannot be paid in its current status"));
+    }
 
     // Убедимся, что стоимость бронирования положительная.
     if total_price <= 0.0 {
-        return Err(to_api_error(StatusCode::BAD_REQUEST, "Invalid booking price"));
+        return Err(AppError::bad_request("Invalid booking price"));
     }
 
     let payment_client = PaymentGatewayClient::from_config(&state.config.payment, state.clone());
@@ -126,11 +118,11 @@ pub async fn initiate_payment(
         match e {
             crate::services::payment::CircuitBreakerError::Open => {
                 tracing::error!("Payment gateway circuit breaker is open");
-                to_api_error(StatusCode::SERVICE_UNAVAILABLE, "Payment service temporarily unavailable. Please try again later.")
+                AppError::ServiceUnavailable("Payment service temporarily unavailable. Please try again later.".to_string())
             },
             crate::services::payment::CircuitBreakerError::PaymentGatewayError(http_err) => {
                 tracing::error!("Payment gateway HTTP error: {:?}", http_err);
-                to_api_error(StatusCode::BAD_GATEWAY, "Payment gateway connection error")
+                AppError::BadGateway("Payment gateway connection error".to_string())
             }
         }
     })?;
@@ -152,49 +144,54 @@ pub async fn initiate_payment(
             _ => StatusCode::BAD_GATEWAY,           // Все остальные ошибки
         };
         
-        return Err(to_api_error_with_code(status_code, &error_msg, error_code));
+        return Err(AppError::PaymentGateway { status: status_code, gateway_code: error_code, message: error_msg });
     }
 
     let payment_id = payment_response.payment_id
-        .ok_or_else(|| to_api_error(StatusCode::INTERNAL_SERVER_ERROR, "No payment ID from gateway"))?;
+        .ok_or_else(|| AppError::internal("No payment ID from gateway"))?;
 
     // Начинаем транзакцию в базе данных.
     let mut tx = state.db.pool.begin().await
         .map_err(|e| {
             tracing::error!("Failed to start DB transaction: {}", e);
-            to_api_error(StatusCode::INTERNAL_SERVER_ERROR, "Database error")
+            AppError::internal("Database error")
         })?;
 
     // Создаем запись о платежной транзакции.
     sqlx::query(
         "INSERT INTO payment_transactions (booking_id, transaction_id, amount, status)
-         VALUES ($1, $2, $3, 'pending')"
+         VALUES ($1, $2, $3, $4)"
     )
     .bind(booking_id)
     .bind(&payment_id)
     .bind(total_price)
+    .bind(PaymentStatus::Pending)
     .execute(&mut *tx)
     .await
     .map_err(|e| {
         tracing::error!("Failed to save transaction: {}", e);
-        to_api_error(StatusCode::INTERNAL_SERVER_ERROR, "Failed to save transaction")
+        AppError::internal("Failed to save transaction")
     })?;
 
     // Обновляем статус бронирования на "ожидает оплаты".
-    sqlx::query("UPDATE bookings SET status = 'pending_payment' WHERE id = $1")
-        .bind(booking_id)
-        .execute(&mut *tx)
+    state_machine::transition_booking(&mut tx, booking_id, BookingStatus::PendingPayment)
         .await
-        .map_err(|e| {
-            tracing::error!("Failed to update booking: {}", e);
-            to_api_error(StatusCode::INTERNAL_SERVER_ERROR, "Failed to update booking")
+        .map_err(|e| match e {
+            TransitionError::Illegal { .. } => {
+                tracing::info!("initiate_payment for booking {} rejected: {}", booking_id, e);
+                AppError::state_conflict("Booking cannot be paid in its current status")
+            }
+            e => {
+                tracing::error!("Failed to update booking: {}", e);
+                AppError::internal("Failed to update booking")
+            }
         })?;
 
     // Завершаем транзакцию.
     tx.commit().await
         .map_err(|e| {
             tracing::error!("Failed to commit DB transaction: {}", e);
-            to_api_error(StatusCode::INTERNAL_SERVER_ERROR, "Database error")
+            AppError::internal("Database error")
         })?;
 
     tracing::info!("Payment created for booking {}: payment_id={}, amount={}",
@@ -218,7 +215,7 @@ pub async fn initiate_payment(
 /// Этот эндпоинт является публичным, так как запросы приходят от внешнего сервиса.
 pub async fn payment_webhook(
     State(state): State<Arc<AppState>>,
-    Json(payload): Json<serde_json::Value>,
+    AppJson(payload): AppJson<serde_json::Value>,
 ) -> impl IntoResponse {
     let payment_id = payload["paymentId"].as_str().unwrap_or_default().to_string();
     let status = payload["status"].as_str().unwrap_or_default().to_string();
@@ -241,11 +238,11 @@ pub async fn payment_webhook(
 /// самой актуальной информации.
 pub async fn get_payment_status(
     State(state): State<Arc<AppState>>,
-    Path(booking_id): Path<i64>,
+    AppPath(booking_id): AppPath<i64>,
     user: AuthUser,
-) -> ApiResult<impl IntoResponse> {
+) -> AppResult<impl IntoResponse> {
     // Получаем последний статус платежа из нашей базы.
-    let status: Option<(String, String)> = sqlx::query_as(
+    let status: Option<(PaymentStatus, String)> = sqlx::query_as(
         "SELECT pt.status, pt.transaction_id FROM payment_transactions pt
          JOIN bookings b ON b.id = pt.booking_id
          WHERE pt.booking_id = $1 AND b.user_id = $2
@@ -257,24 +254,24 @@ pub async fn get_payment_status(
     .await
     .map_err(|e| {
         tracing::error!("Database error getting payment status: {}", e);
-        to_api_error(StatusCode::INTERNAL_SERVER_ERROR, "Database error")
+        AppError::internal("Database error")
     })?;
 
     match status {
         Some((status, payment_id)) => {
-            let mut actual_status = status.clone();
+            let mut actual_status = status;
             
             // Если платеж все еще в статусе "pending", стоит проверить его состояние
             // напрямую в платежном шлюзе, чтобы получить актуальные данные.
-            if status == "pending" {
+            if status == PaymentStatus::Pending {
                 let payment_client = PaymentGatewayClient::from_config(&state.config.payment, state.clone());
                 
                 if let Ok(check_response) = payment_client.check_payment_status(&payment_id).await {
                     if check_response.success {
                         if let Some(gateway_status) = check_response.status {
                             match gateway_status.as_str() {
-                                "CONFIRMED" => actual_status = "completed".to_string(),
-                                "FAILED" | "CANCELLED" | "EXPIRED" => actual_status = "failed".to_string(),
+                                "CONFIRMED" => actual_status = PaymentStatus::Completed,
+                                "FAILED" | "CANCELLED" | "EXPIRED" => actual_status = PaymentStatus::Failed,
                                 "AUTHORIZED" => {
                                     // Если платеж авторизован, но не подтвержден,
                                     // пытаемся подтвердить его автоматически.
@@ -282,7 +279,7 @@ pub async fn get_payment_status(
                                         (check_response.amount, check_response.currency, check_response.order_id) {
                                         if let Ok(confirm_response) = payment_client.confirm_payment(&payment_id, amount, &currency, &order_id).await {
                                             if confirm_response.success {
-                                                actual_status = "completed".to_string();
+                                                actual_status = PaymentStatus::Completed;
                                                 tracing::info!("Auto-confirmed payment {} during status check", payment_id);
                                             }
                                         }
@@ -302,7 +299,7 @@ pub async fn get_payment_status(
                 "payment_id": payment_id
             }))))
         },
-        None => Err(to_api_error(StatusCode::NOT_FOUND, "Payment for this booking not found"))
+        None => Err(AppError::not_found("Payment for this booking not found"))
     }
 }
 
@@ -363,9 +360,8 @@ pub async fn payment_success_handler(
 /// доступности платежного шлюза.
 pub async fn get_circuit_breaker_status(
     State(state): State<Arc<AppState>>,
-) -> ApiResult<impl IntoResponse> {
-    let payment_client = PaymentGatewayClient::from_config(&state.config.payment, state.clone());
-    let (circuit_state, failure_count) = payment_client.get_circuit_breaker_status();
+) -> AppResult<impl IntoResponse> {
+    let (circuit_state, failure_count) = state.circuit_breaker.status().await;
     
     Ok((StatusCode::OK, Json(json!({
         "success": true,
@@ -373,7 +369,9 @@ pub async fn get_circuit_breaker_status(
             "state": format!("{:?}", circuit_state),
             "failure_count": failure_count,
             "threshold": state.config.circuit_breaker.failure_threshold,
-            "timeout_seconds": state.config.circuit_breaker.timeout_seconds
+            "timeout_seconds": state.config.circuit_breaker.timeout_seconds,
+            "half_open_max_requests": state.config.circuit_breaker.half_open_max_requests,
+            "shared": state.circuit_breaker.is_shared()
         }
     }))))
 }
@@ -0,0 +1,218 @@
+//! seat_map.rs
+//!
+//! Карта мест события в реальном времени.
+//!
+//! Включает в себя следующую функциональность:
+//! - Компактная карта мест: схема зала с координатами и текущие статусы.
+//! - WebSocket-поток изменений статусов мест вместо постраничного опроса `GET /api/seats`.
+
+use axum::{
+    extract::{
+        ws::{Message, WebSocket, WebSocketUpgrade},
+        State,
+    },
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    routing::get,
+    Json, Router,
+};
+use serde::Serialize;
+use std::{collections::HashMap, sync::Arc};
+use tokio::sync::broadcast::error::RecvError;
+use crate::{
+    AppState,
+    error::{AppError, AppResult},
+    extract::AppPath,
+    models::{SeatStatus, Section, Venue},
+};
+
+/// Определяет маршруты карты мест.
+pub fn routes() -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/events/{id}/seat-map", get(get_seat_map))
+        .route("/events/{id}/seats/ws", get(seat_updates_ws))
+}
+
+/// Порядок полей в строках `seats` компактной карты мест.
+const SEAT_MAP_COLUMNS: &[&str] = &["id", "section_id", "row", "number", "x", "y", "status", "category", "price"];
+
+/// Место в компактной карте: массив вместо объекта, категория - индекс в `categories`.
+type CompactSeat = (i64, Option<i64>, i32, i32, Option<f32>, Option<f32>, SeatStatus, Option<usize>, Option<f64>);
+
+/// Карта мест события для отрисовки схемы зала.
+#[derive(Debug, Serialize)]
+struct SeatMap {
+    event_id: i64,
+    /// `None`, если у события нет схемы зала: тогда у мест нет секций и координат.
+    venue: Option<Venue>,
+    sections: Vec<Section>,
+    categories: Vec<String>,
+    columns: &'static [&'static str],
+    seats: Vec<CompactSeat>,
+}
+
+/// GET /api/events/{id}/seat-map
+///
+/// Возвращает схему зала (площадка, секции, координаты мест) вместе с текущими
+/// статусами мест, включая удержания в Redis (`SELECTED`). Места отдаются
+/// массивами в порядке `columns`, чтобы карта на тысячи мест оставалась компактной.
+async fn get_seat_map(
+    State(state): State<Arc<AppState>>,
+    AppPath(event_id): AppPath<i64>,
+) -> AppResult<impl IntoResponse> {
+    ensure_event_exists(&state, event_id).await?;
+
+    let layout = state.cache.get_seat_layout(event_id).await?;
+    let positions: HashMap<i64, (f32, f32)> = layout
+        .positions
+        .iter()
+        .map(|position| (position.seat_id, (position.x, position.y)))
+        .collect();
+
+    let mut categories: Vec<String> = Vec::new();
+    let seats = state
+        .cache
+        .get_seats(event_id)
+        .await
+        .into_iter()
+        .map(|seat| {
+            let category = seat.category.map(|category| {
+                categories.iter().position(|c| *c == category).unwrap_or_else(|| {
+                    categories.push(category);
+                    categories.len() - 1
+                })
+            });
+            let position = positions.get(&seat.id);
+            (
+                seat.id,
+                seat.section_id,
+                seat.row,
+                seat.number,
+                position.map(|(x, _)| *x),
+                position.map(|(_, y)| *y),
+                seat.status,
+                category,
+                seat.price,
+            )
+        })
+        .collect();
+
+    Ok((StatusCode::OK, Json(SeatMap {
+        event_id,
+        venue: layout.venue,
+        sections: layout.sections,
+        categories,
+        columns: SEAT_MAP_COLUMNS,
+        seats,
+    })))
+}
+
+/// Проверяет ID события и что событие существует.
+async fn ensure_event_exists(state: &AppState, event_id: i64) -> AppResult<()> {
+    if event_id <= 0 {
+        return Err(AppError::bad_request("ID события должен быть > 0"));
+    }
+
+    let exists = sqlx::query_scalar::<_, bool>(
+        "SELECT EXISTS(SELECT 1 FROM events_archive WHERE id = $1)"
+    )
+    .bind(event_id)
+    .fetch_one(&state.db.pool)
+    .await?;
+    if !exists {
+        return Err(AppError::not_found("Событие не найдено"));
+    }
+    Ok(())
+}
+
+/// Снимок всех мест события. Отправляется при подключении и после
+/// переполнения очереди клиента.
+#[derive(Debug, Serialize)]
+#[serde(tag = "type", rename = "snapshot")]
+struct SeatSnapshot {
+    event_id: i64,
+    seats: Vec<SeatState>,
+}
+
+#[derive(Debug, Serialize)]
+struct SeatState {
+    id: i64,
+    section_id: Option<i64>,
+    row: i32,
+    number: i32,
+    status: SeatStatus,
+    category: Option<String>,
+    price: Option<f64>,
+}
+
+/// GET /api/events/{id}/seats/ws
+///
+/// Открывает WebSocket. Первым сообщением приходит снимок карты мест
+/// (`"type": "snapshot"`), дальше - изменения (`"type": "update"`) по мере того,
+/// как места выбирают, бронируют, освобождают, продают или у них истекает удержание.
+async fn seat_updates_ws(
+    State(state): State<Arc<AppState>>,
+    AppPath(event_id): AppPath<i64>,
+    ws: WebSocketUpgrade,
+) -> AppResult<Response> {
+    ensure_event_exists(&state, event_id).await?;
+
+    Ok(ws.on_upgrade(move |socket| stream_seat_updates(state, event_id, socket)))
+}
+
+/// Пересылает клиенту изменения мест события, пока тот не отключится.
+async fn stream_seat_updates(state: Arc<AppState>, event_id: i64, mut socket: WebSocket) {
+    // Подписываемся до снимка, чтобы не потерять изменения между ними.
+    let mut updates = state.seat_events.subscribe();
+    if send_snapshot(&state, event_id, &mut socket).await.is_err() {
+        return;
+    }
+
+    loop {
+        tokio::select! {
+            update = updates.recv() => match update {
+                Ok(update) if update.event_id == event_id => {
+                    if socket.send(Message::Text(update.payload.as_ref().into())).await.is_err() {
+                        break;
+                    }
+                }
+                Ok(_) => {}
+                // Клиент не успевал читать - часть изменений потеряна, отдаем карту заново.
+                Err(RecvError::Lagged(skipped)) => {
+                    tracing::debug!("Seat updates client for event {} lagged by {}", event_id, skipped);
+                    if send_snapshot(&state, event_id, &mut socket).await.is_err() {
+                        break;
+                    }
+                }
+                Err(RecvError::Closed) => break,
+            },
+            incoming = socket.recv() => match incoming {
+                // Входящие сообщения клиента не нужны, ping axum отвечает сам.
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                Some(Ok(_)) => {}
+            },
+        }
+    }
+}
+
+async fn send_snapshot(state: &AppState, event_id: i64, socket: &mut WebSocket) -> Result<(), axum::Error> {
+    let seats = state
+        .cache
+        .get_seats(event_id)
+        .await
+        .into_iter()
+        .map(|seat| SeatState {
+            id: seat.id,
+            section_id: seat.section_id,
+            row: seat.row,
+            number: seat.number,
+            status: seat.status,
+            category: seat.category,
+            price: seat.price,
+        })
+        .collect();
+
+    let snapshot = SeatSnapshot { event_id, seats };
+    let payload = serde_json::to_string(&snapshot).unwrap_or_default();
+    socket.send(Message::Text(payload.into())).await
+}
@@ -0,0 +1,183 @@
+//! cursor.rs
+//!
+//! Непрозрачные курсоры для keyset-пагинации.
+//!
+//! Курсор - позиция последней отданной записи в порядке сортировки списка,
+//! закодированная в base64url(JSON). Следующая страница начинается строго
+//! после нее, поэтому глубина страницы не влияет на стоимость запроса,
+//! в отличие от `OFFSET`. Клиент не должен разбирать курсор: формат может меняться.
+
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine as _};
+use chrono::NaiveDateTime;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use sqlx::{Postgres, QueryBuilder};
+
+use crate::error::{AppError, AppResult};
+
+/// Максимальный размер страницы при пагинации курсором.
+pub const MAX_CURSOR_PAGE_SIZE: u32 = 100;
+
+/// Позиция в списке событий: `(datetime_start, id)`. В полнотекстовом
+/// поиске список сначала упорядочен по релевантности, поэтому в курсор
+/// добавляется `rank` последней записи.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct EventCursor {
+    pub datetime_start: NaiveDateTime,
+    pub id: i64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rank: Option<f32>,
+}
+
+/// Позиция в списке мест события: `(row, number, id)`. `id` различает места
+/// с одинаковым рядом и номером в разных секциях.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SeatCursor {
+    pub row: i32,
+    pub number: i32,
+    pub id: i64,
+}
+
+pub fn encode<T: Serialize>(cursor: &T) -> String {
+    // Сериализация структур из чисел и дат не падает.
+    let json = serde_json::to_vec(cursor).unwrap_or_default();
+    URL_SAFE_NO_PAD.encode(json)
+}
+
+pub fn decode<T: DeserializeOwned>(token: &str) -> AppResult<T> {
+    let invalid = || AppError::bad_request("Некорректный cursor");
+    let json = URL_SAFE_NO_PAD.decode(token.trim()).map_err(|_| invalid())?;
+    serde_json::from_slice(&json).map_err(|_| invalid())
+}
+
+/// Разбирает параметр `cursor`. Пустая строка - первая страница в режиме курсоров.
+pub fn decode_param<T: DeserializeOwned>(token: Option<&str>) -> AppResult<Option<T>> {
+    match token.map(str::trim) {
+        None | Some("") => Ok(None),
+        Some(token) => decode(token).map(Some),
+    }
+}
+
+impl EventCursor {
+    /// Условие "строго после курсора" для списка в порядке `datetime_start, id`.
+    pub fn push_after(&self, query: &mut QueryBuilder<'_, Postgres>) {
+        query
+            .push(" AND (datetime_start, id) > (")
+            .push_bind(self.datetime_start)
+            .push(", ")
+            .push_bind(self.id)
+            .push(")");
+    }
+
+    /// Условие "строго после курсора" для списка в порядке `rank DESC, datetime_start, id`.
+    /// `rank_expr` - выражение, которым в запросе считается `rank`. Курсор без
+    /// `rank` (выдан списком без поиска) продолжает список по дате.
+    pub fn push_after_ranked(&self, query: &mut QueryBuilder<'_, Postgres>, rank_expr: &str) {
+        let Some(rank) = self.rank else {
+            return self.push_after(query);
+        };
+        query
+            .push(format_args!(" AND ({rank_expr} < "))
+            .push_bind(rank)
+            .push(format_args!(" OR ({rank_expr} = "))
+            .push_bind(rank)
+            .push(" AND (datetime_start, id) > (")
+            .push_bind(self.datetime_start)
+            .push(", ")
+            .push_bind(self.id)
+            .push(")))");
+    }
+}
+
+impl SeatCursor {
+    /// Условие "строго после курсора" для списка в порядке `row, number, id`.
+    pub fn push_after(&self, query: &mut QueryBuilder<'_, Postgres>) {
+        query
+            .push(" AND (row, number, id) > (")
+            .push_bind(self.row)
+            .push(", ")
+            .push_bind(self.number)
+            .push(", ")
+            .push_bind(self.id)
+            .push(")");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{http::StatusCode, response::IntoResponse};
+
+    fn event_cursor(rank: Option<f32>) -> EventCursor {
+        EventCursor {
+            datetime_start: chrono::NaiveDate::from_ymd_opt(2025, 3, 14)
+                .unwrap()
+                .and_hms_micro_opt(19, 30, 0, 123_456)
+                .unwrap(),
+            id: 42,
+            rank,
+        }
+    }
+
+    fn assert_bad_request<T: DeserializeOwned + std::fmt::Debug>(token: &str) {
+        let err = decode::<T>(token).expect_err(token);
+        assert_eq!(err.status(), StatusCode::BAD_REQUEST, "{}", token);
+        assert_eq!(err.into_response().status(), StatusCode::BAD_REQUEST, "{}", token);
+    }
+
+    #[test]
+    fn event_cursor_round_trip() {
+        for cursor in [event_cursor(None), event_cursor(Some(0.0607927)), event_cursor(Some(1e-8))] {
+            let token = encode(&cursor);
+            assert_eq!(decode::<EventCursor>(&token).unwrap(), cursor);
+        }
+    }
+
+    #[test]
+    fn event_cursor_without_rank_omits_field() {
+        let token = encode(&event_cursor(None));
+        let json = URL_SAFE_NO_PAD.decode(token).unwrap();
+        assert!(!String::from_utf8(json).unwrap().contains("rank"));
+    }
+
+    #[test]
+    fn seat_cursor_round_trip() {
+        let cursor = SeatCursor { row: 12, number: -1, id: i64::MAX };
+        let token = encode(&cursor);
+        assert_eq!(decode::<SeatCursor>(&token).unwrap(), cursor);
+        assert_eq!(decode_param::<SeatCursor>(Some(&format!(" {} ", token))).unwrap(), Some(cursor));
+    }
+
+    #[test]
+    fn empty_param_is_first_page() {
+        assert_eq!(decode_param::<SeatCursor>(None).unwrap(), None);
+        assert_eq!(decode_param::<SeatCursor>(Some("  ")).unwrap(), None);
+    }
+
+    #[test]
+    fn garbage_token_is_bad_request() {
+        assert_bad_request::<EventCursor>("not a cursor!");
+        assert_bad_request::<SeatCursor>("%%%");
+        // Корректный base64, но не JSON.
+        assert_bad_request::<SeatCursor>(&URL_SAFE_NO_PAD.encode("hello"));
+        // JSON не того курсора.
+        assert_bad_request::<SeatCursor>(&encode(&event_cursor(Some(0.5))));
+        assert_bad_request::<EventCursor>(&encode(&SeatCursor { row: 1, number: 1, id: 1 }));
+    }
+
+    #[test]
+    fn tampered_token_is_bad_request() {
+        let token = encode(&SeatCursor { row: 1, number: 2, id: 3 });
+
+        let mut json = URL_SAFE_NO_PAD.decode(&token).unwrap();
+        json.truncate(json.len() - 1);
+        assert_bad_request::<SeatCursor>(&URL_SAFE_NO_PAD.encode(&json));
+
+        let tampered = URL_SAFE_NO_PAD.encode(br#"{"row":"1","number":2,"id":3}"#);
+        assert_bad_request::<SeatCursor>(&tampered);
+
+        let overflow = URL_SAFE_NO_PAD.encode(br#"{"row":99999999999,"number":2,"id":3}"#);
+        assert_bad_request::<SeatCursor>(&overflow);
+
+        assert_bad_request::<SeatCursor>(&token[..token.len() - 1]);
+    }
+}
@@ -1,26 +1,366 @@
-use sqlx::{postgres::PgPoolOptions, PgPool};
+use chrono::{Datelike, NaiveDateTime};
+use sqlx::{
+    postgres::{PgConnectOptions, PgPoolOptions},
+    Connection, PgPool,
+};
+use std::collections::HashSet;
+use std::str::FromStr;
+use std::sync::{
+    atomic::{AtomicU64, AtomicUsize, Ordering},
+    Arc,
+};
 use std::time::Duration;
+use tracing::{info, warn};
 
+use crate::config::DatabaseConfig;
+use crate::models::{BookingStatus, EventStatus, PaymentStatus, SeatStatus};
+
+/// Основная БД и реплики только для чтения.
+///
+/// Записи и чтения, которым нужны только что записанные данные, идут в `pool`.
+/// Остальные чтения берут пул из `read_pool`: реплику, отставание которой
+/// по последней проверке не больше `DB_REPLICA_MAX_LAG_MS`, или основную БД,
+/// если таких реплик нет.
 #[derive(Clone)]
 pub struct Database {
     pub pool: PgPool,
+    replicas: Arc<[Replica]>,
+    next_replica: Arc<AtomicUsize>,
+    max_replica_lag: Duration,
+    replica_check_interval: Duration,
+}
+
+struct Replica {
+    /// Хост и порт для логов (URL содержит пароль).
+    name: String,
+    pool: PgPool,
+    /// Отставание по последней проверке, мс; `REPLICA_UNAVAILABLE` - реплика недоступна.
+    lag_ms: AtomicU64,
+}
+
+const REPLICA_UNAVAILABLE: u64 = u64::MAX;
+
+/// Отставание реплики в секундах. Без новых записей на основной БД время
+/// последней применённой транзакции стареет, поэтому реплика, применившая
+/// все полученные WAL, считается догнавшей. Не реплика (`pg_is_in_recovery`
+/// ложно) не отстает.
+const REPLICA_LAG_QUERY: &str = "SELECT CASE
+        WHEN NOT pg_is_in_recovery() THEN 0
+        WHEN pg_last_wal_receive_lsn() = pg_last_wal_replay_lsn() THEN 0
+        ELSE EXTRACT(EPOCH FROM now() - pg_last_xact_replay_timestamp())
+    END::float8";
+
+/// Отставание в мс по результату `REPLICA_LAG_QUERY`. NULL - реплика еще
+/// ничего не применила.
+fn lag_ms(lag_seconds: Option<f64>) -> u64 {
+    match lag_seconds {
+        // Отрицательное отставание - расхождение часов, а не опережение
+        Some(lag) if !lag.is_nan() => (lag.max(0.0) * 1000.0) as u64,
+        _ => REPLICA_UNAVAILABLE,
+    }
+}
+
+/// Изменение пригодности реплики для чтения между двумя проверками.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ReplicaTransition {
+    /// Отставание вернулось в допустимые пределы.
+    Serving,
+    /// Реплика перестала отвечать.
+    Unavailable,
+    /// Отставание превысило `DB_REPLICA_MAX_LAG_MS`.
+    Lagging,
+    Unchanged,
+}
+
+fn replica_transition(previous_ms: u64, lag_ms: u64, max_lag_ms: u64) -> ReplicaTransition {
+    match (previous_ms <= max_lag_ms, lag_ms <= max_lag_ms) {
+        (false, true) => ReplicaTransition::Serving,
+        (true, false) if lag_ms == REPLICA_UNAVAILABLE => ReplicaTransition::Unavailable,
+        (true, false) => ReplicaTransition::Lagging,
+        _ => ReplicaTransition::Unchanged,
+    }
+}
+
+/// Колонки, на которые опираются запросы в коде.
+const REQUIRED_COLUMNS: &[(&str, &[&str])] = &[
+    ("users", &["user_id", "email", "password_hash", "password_plain", "first_name", "surname", "is_active", "last_logged_in"]),
+    ("events_archive", &["id", "title", "description", "type", "datetime_start", "provider", "search_vector", "venue_id", "status", "updated_at"]),
+    ("bookings", &["id", "event_id", "user_id", "status", "created_at", "updated_at"]),
+    ("seats", &["id", "event_id", "row", "number", "status", "booking_id", "category", "price", "updated_at", "section_id"]),
+    ("venues", &["id", "name", "address", "width", "height"]),
+    ("sections", &["id", "venue_id", "name", "tier", "sort_order"]),
+    ("seat_layouts", &["section_id", "row", "number", "x", "y"]),
+    ("payment_transactions", &["id", "booking_id", "transaction_id", "amount", "status", "created_at", "updated_at"]),
+    ("event_ids", &["id", "datetime_start"]),
+    ("background_migrations", &["name", "last_id", "completed_at"]),
+];
+
+/// CHECK-ограничения статусов и значения, которые код в них записывает.
+fn required_statuses() -> [(&'static str, Vec<&'static str>); 4] {
+    [
+        ("seats_status_check", SeatStatus::PERSISTED.iter().map(|s| s.as_str()).collect()),
+        ("bookings_status_check", BookingStatus::ALL.iter().map(|s| s.as_str()).collect()),
+        ("payment_transactions_status_check", PaymentStatus::ALL.iter().map(|s| s.as_str()).collect()),
+        ("events_archive_status_check", EventStatus::ALL.iter().map(|s| s.as_str()).collect()),
+    ]
+}
+
+fn pool_options(config: &DatabaseConfig, size: u32) -> PgPoolOptions {
+    PgPoolOptions::new()
+        .max_connections(size.max(1))
+        .acquire_timeout(Duration::from_millis(config.acquire_timeout_ms))
+        .idle_timeout(Duration::from_secs(config.idle_timeout_seconds))
+}
+
+/// Параметры подключения; `statement_timeout` передается при старте сессии.
+fn connect_options(url: &str, statement_timeout_ms: u64) -> Result<PgConnectOptions, sqlx::Error> {
+    let options = PgConnectOptions::from_str(url)?;
+    if statement_timeout_ms == 0 {
+        return Ok(options);
+    }
+    Ok(options.options([("statement_timeout", statement_timeout_ms.to_string())]))
+}
+
+/// Расхождение между схемой БД и кодом.
+#[derive(Debug, thiserror::Error)]
+pub enum SchemaError {
+    #[error("schema check query failed: {0}")]
+    Database(#[from] sqlx::Error),
+    #[error("column {table}.{column} is missing")]
+    MissingColumn { table: String, column: String },
+    #[error("check constraint {0} is missing")]
+    MissingConstraint(String),
+    #[error("check constraint {constraint} does not allow status '{status}'")]
+    StatusNotAllowed { constraint: String, status: String },
 }
 
 impl Database {
-    pub async fn new(database_url: &str) -> Result<Self, sqlx::Error> {
-        let pool = PgPoolOptions::new()
-            .acquire_timeout(Duration::from_secs(1))
-            .idle_timeout(Duration::from_secs(300))
-            .connect(database_url)
+    pub async fn new(config: &DatabaseConfig) -> Result<Self, sqlx::Error> {
+        let pool = pool_options(config, config.pool_size)
+            .connect_with(connect_options(&config.url, config.statement_timeout_ms)?)
             .await?;
-        
-        Ok(Database { pool })
+
+        // Реплики подключаются лениво: недоступная реплика не мешает старту,
+        // запросы пойдут в основную БД, пока проверка не найдет ее живой.
+        let replicas = config
+            .replica_urls
+            .iter()
+            .map(|url| {
+                let options = connect_options(url, config.replica_statement_timeout_ms)?;
+                Ok(Replica {
+                    name: format!("{}:{}", options.get_host(), options.get_port()),
+                    pool: pool_options(config, config.replica_pool_size).connect_lazy_with(options),
+                    lag_ms: AtomicU64::new(REPLICA_UNAVAILABLE),
+                })
+            })
+            .collect::<Result<Vec<_>, sqlx::Error>>()?;
+
+        Ok(Database {
+            pool,
+            replicas: replicas.into(),
+            next_replica: Arc::new(AtomicUsize::new(0)),
+            max_replica_lag: Duration::from_millis(config.replica_max_lag_ms),
+            replica_check_interval: Duration::from_secs(config.replica_check_interval_seconds.max(1)),
+        })
+    }
+
+    pub fn has_replicas(&self) -> bool {
+        !self.replicas.is_empty()
+    }
+
+    /// Пул для запросов только на чтение, которым допустимо отставание до
+    /// `DB_REPLICA_MAX_LAG_MS`. Реплики чередуются по кругу.
+    pub fn read_pool(&self) -> &PgPool {
+        let count = self.replicas.len();
+        if count == 0 {
+            return &self.pool;
+        }
+        let max_lag = self.max_replica_lag.as_millis() as u64;
+        let start = self.next_replica.fetch_add(1, Ordering::Relaxed);
+        (0..count)
+            .map(|i| &self.replicas[(start + i) % count])
+            .find(|replica| replica.lag_ms.load(Ordering::Relaxed) <= max_lag)
+            .map_or(&self.pool, |replica| &replica.pool)
+    }
+
+    /// Сколько после записи читать ее из основной БД: реплика в `read_pool`
+    /// может отставать на `DB_REPLICA_MAX_LAG_MS` плюс время до следующей проверки.
+    pub fn read_your_writes_window(&self) -> Duration {
+        self.max_replica_lag + self.replica_check_interval
+    }
+
+    /// Запускает фоновую проверку отставания реплик. Без реплик ничего не делает.
+    pub fn start_replica_monitor(&self) {
+        if self.replicas.is_empty() {
+            return;
+        }
+        let db = self.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(db.replica_check_interval);
+            interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            loop {
+                interval.tick().await;
+                for replica in db.replicas.iter() {
+                    db.check_replica(replica).await;
+                }
+            }
+        });
+    }
+
+    async fn check_replica(&self, replica: &Replica) {
+        let check = tokio::time::timeout(
+            self.replica_check_interval,
+            sqlx::query_scalar::<_, Option<f64>>(REPLICA_LAG_QUERY).fetch_one(&replica.pool),
+        )
+        .await;
+        let lag_ms = match check {
+            Ok(Ok(lag)) => lag_ms(lag),
+            Ok(Err(e)) => {
+                warn!("Replica {} check failed: {}", replica.name, e);
+                REPLICA_UNAVAILABLE
+            }
+            Err(_) => {
+                warn!("Replica {} check timed out", replica.name);
+                REPLICA_UNAVAILABLE
+            }
+        };
+
+        let max_lag = self.max_replica_lag.as_millis() as u64;
+        let previous = replica.lag_ms.swap(lag_ms, Ordering::Relaxed);
+        match replica_transition(previous, lag_ms, max_lag) {
+            ReplicaTransition::Serving => info!("Replica {} is serving reads (lag {} ms)", replica.name, lag_ms),
+            ReplicaTransition::Unavailable => {
+                warn!("Replica {} is unavailable, reads go to primary", replica.name)
+            }
+            ReplicaTransition::Lagging => warn!("Replica {} lags {} ms, reads go elsewhere", replica.name, lag_ms),
+            ReplicaTransition::Unchanged => {}
+        }
     }
-    
+
     pub async fn run_migrations(&self) -> Result<(), sqlx::migrate::MigrateError> {
+        // Миграции могут идти дольше DB_STATEMENT_TIMEOUT_MS: отдельное соединение
+        // без ограничения, которое не вернется в пул.
+        let mut conn = self.pool.acquire().await?.detach();
+        sqlx::query("SET statement_timeout = 0").execute(&mut conn).await?;
         sqlx::migrate!("./src/migrations")
-            .run(&self.pool)
+            .run(&mut conn)
+            .await?;
+        conn.close().await?;
+        Ok(())
+    }
+
+    /// Проверяет, что схема содержит все колонки и статусы, которые использует код.
+    /// Вызывается при старте после миграций, чтобы упасть сразу, а не на первом запросе.
+    pub async fn verify_schema(&self) -> Result<(), SchemaError> {
+        let columns: HashSet<(String, String)> = sqlx::query_as(
+            "SELECT table_name::text, column_name::text
+             FROM information_schema.columns
+             WHERE table_schema = current_schema()"
+        )
+        .fetch_all(&self.pool)
+        .await?
+        .into_iter()
+        .collect();
+
+        for (table, required) in REQUIRED_COLUMNS {
+            for column in *required {
+                if !columns.contains(&(table.to_string(), column.to_string())) {
+                    return Err(SchemaError::MissingColumn {
+                        table: table.to_string(),
+                        column: column.to_string(),
+                    });
+                }
+            }
+        }
+
+        for (constraint, statuses) in required_statuses() {
+            let definition: Option<String> = sqlx::query_scalar(
+                "SELECT pg_get_constraintdef(c.oid)
+                 FROM pg_constraint c
+                 JOIN pg_namespace n ON n.oid = c.connamespace
+                 WHERE c.conname = $1 AND c.contype = 'c' AND n.nspname = current_schema()"
+            )
+            .bind(constraint)
+            .fetch_optional(&self.pool)
+            .await?;
+
+            let definition = definition.ok_or_else(|| SchemaError::MissingConstraint(constraint.to_string()))?;
+            for status in statuses {
+                if !definition.contains(&format!("'{}'", status)) {
+                    return Err(SchemaError::StatusNotAllowed {
+                        constraint: constraint.to_string(),
+                        status: status.to_string(),
+                    });
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Перестраивает `events_upcoming` без блокировки чтения. Вызывается
+    /// фоновой задачей и после изменения событий через админку.
+    pub async fn refresh_upcoming_events(&self) -> Result<(), sqlx::Error> {
+        sqlx::query("REFRESH MATERIALIZED VIEW CONCURRENTLY events_upcoming")
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    /// Создает годовые секции `events_archive` на текущий и `years_ahead`
+    /// следующих лет. Возвращает число созданных секций.
+    pub async fn ensure_event_partitions(&self, years_ahead: u32) -> Result<i64, sqlx::Error> {
+        sqlx::query_scalar(
+            "SELECT count(*) FILTER (WHERE created)
+             FROM generate_series(EXTRACT(YEAR FROM NOW())::int, EXTRACT(YEAR FROM NOW())::int + $1) AS y,
+                  ensure_events_partition(y) AS created"
+        )
+        .bind(years_ahead as i32)
+        .fetch_one(&self.pool)
+        .await
+    }
+
+    /// Создает секцию `events_archive` для года `datetime_start`, если ее нет.
+    /// Вызывается перед записью события: иначе строка попала бы в
+    /// `events_archive_default`, и секцию этого года уже нельзя было бы создать.
+    pub async fn ensure_event_partition_for(&self, datetime_start: NaiveDateTime) -> Result<(), sqlx::Error> {
+        sqlx::query("SELECT ensure_events_partition($1)")
+            .bind(datetime_start.year())
+            .execute(&self.pool)
             .await?;
         Ok(())
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lag_is_converted_to_milliseconds() {
+        assert_eq!(lag_ms(Some(0.0)), 0);
+        assert_eq!(lag_ms(Some(0.25)), 250);
+        assert_eq!(lag_ms(Some(12.5)), 12_500);
+        // Часы реплики спешат
+        assert_eq!(lag_ms(Some(-3.0)), 0);
+    }
+
+    #[test]
+    fn unknown_lag_means_unavailable() {
+        assert_eq!(lag_ms(None), REPLICA_UNAVAILABLE);
+        assert_eq!(lag_ms(Some(f64::NAN)), REPLICA_UNAVAILABLE);
+        assert_eq!(lag_ms(Some(f64::INFINITY)), REPLICA_UNAVAILABLE);
+    }
+
+    #[test]
+    fn replica_transitions() {
+        let max = 1000;
+        assert_eq!(replica_transition(REPLICA_UNAVAILABLE, 0, max), ReplicaTransition::Serving);
+        assert_eq!(replica_transition(5000, max, max), ReplicaTransition::Serving);
+        assert_eq!(replica_transition(0, max + 1, max), ReplicaTransition::Lagging);
+        assert_eq!(replica_transition(0, REPLICA_UNAVAILABLE, max), ReplicaTransition::Unavailable);
+        assert_eq!(replica_transition(0, 500, max), ReplicaTransition::Unchanged);
+        assert_eq!(replica_transition(5000, REPLICA_UNAVAILABLE, max), ReplicaTransition::Unchanged);
+        assert_eq!(replica_transition(REPLICA_UNAVAILABLE, 5000, max), ReplicaTransition::Unchanged);
+    }
+}
@@ -0,0 +1,225 @@
+//! error.rs
+//!
+//! Единый тип ошибки API.
+//!
+//! Все обработчики и мидлвэры возвращают `AppError`. Ответ с ошибкой всегда
+//! имеет одну форму:
+//!
+//! ```json
+//! {
+//!   "success": false,
+//!   "error": { "code": "state_conflict", "message": "Место уже зарезервировано" },
+//!   "request_id": "6f1c..."
+//! }
+//! ```
+//!
+//! `code` - стабильный машиночитаемый код, на который может опираться клиент;
+//! `message` - текст для человека, может меняться.
+
+use axum::{
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::{
+    middleware::request_id::current_request_id,
+    models::state_machine::TransitionError,
+    services::seat_inventory::InventoryError,
+};
+
+/// Результат обработчика API.
+pub type AppResult<T> = Result<T, AppError>;
+
+#[derive(Debug, thiserror::Error)]
+pub enum AppError {
+    /// Некорректные параметры запроса.
+    #[error("{0}")]
+    BadRequest(String),
+    /// Нет или неверные учетные данные.
+    #[error("{0}")]
+    Unauthorized(String),
+    /// Недостаточно прав.
+    #[error("{0}")]
+    Forbidden(String),
+    #[error("{0}")]
+    NotFound(String),
+    #[error("{0}")]
+    Conflict(String),
+    /// Место или бронь находятся в состоянии, в котором операция невозможна
+    /// (место уже занято, бронь уже оплачена). Отдается кастомным статусом 419.
+    #[error("{0}")]
+    StateConflict(String),
+    /// Часть запрошенных мест занята. ID этих мест передаются в `details.seat_ids`.
+    #[error("Часть мест недоступна")]
+    SeatsUnavailable(Vec<i64>),
+    #[error("{0}")]
+    TooManyRequests(String),
+    /// Платежный шлюз отклонил запрос. `gateway_code` - код ошибки шлюза.
+    #[error("{message}")]
+    PaymentGateway {
+        status: StatusCode,
+        gateway_code: i32,
+        message: String,
+    },
+    /// Внешний сервис ответил ошибкой или недоступен по сети.
+    #[error("{0}")]
+    BadGateway(String),
+    #[error("{0}")]
+    ServiceUnavailable(String),
+    #[error("{0}")]
+    Internal(String),
+    /// Ошибка БД без отдельной обработки. Детали пишутся в лог, клиенту не отдаются.
+    #[error("database error: {0}")]
+    Database(#[from] sqlx::Error),
+    #[error(transparent)]
+    Transition(#[from] TransitionError),
+    /// Некорректный шаблон зала или ошибка БД при генерации мест.
+    #[error(transparent)]
+    Inventory(#[from] InventoryError),
+}
+
+/// Тело ответа с ошибкой.
+#[derive(Debug, Serialize)]
+pub struct ErrorEnvelope {
+    pub success: bool,
+    pub error: ErrorBody,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ErrorBody {
+    pub code: &'static str,
+    pub message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub details: Option<Value>,
+}
+
+/// Кастомный статус 419 для конфликтов состояния мест и броней.
+pub fn status_419() -> StatusCode {
+    StatusCode::from_u16(419).unwrap_or(StatusCode::CONFLICT)
+}
+
+impl AppError {
+    pub fn bad_request(message: impl Into<String>) -> Self {
+        Self::BadRequest(message.into())
+    }
+
+    pub fn unauthorized(message: impl Into<String>) -> Self {
+        Self::Unauthorized(message.into())
+    }
+
+    pub fn forbidden(message: impl Into<String>) -> Self {
+        Self::Forbidden(message.into())
+    }
+
+    pub fn not_found(message: impl Into<String>) -> Self {
+        Self::NotFound(message.into())
+    }
+
+    pub fn conflict(message: impl Into<String>) -> Self {
+        Self::Conflict(message.into())
+    }
+
+    pub fn state_conflict(message: impl Into<String>) -> Self {
+        Self::StateConflict(message.into())
+    }
+
+    pub fn internal(message: impl Into<String>) -> Self {
+        Self::Internal(message.into())
+    }
+
+    pub fn status(&self) -> StatusCode {
+        match self {
+            Self::BadRequest(_) => StatusCode::BAD_REQUEST,
+            Self::Unauthorized(_) => StatusCode::UNAUTHORIZED,
+            Self::Forbidden(_) => StatusCode::FORBIDDEN,
+            Self::NotFound(_) => StatusCode::NOT_FOUND,
+            Self::Conflict(_) => StatusCode::CONFLICT,
+            Self::StateConflict(_) | Self::SeatsUnavailable(_) => status_419(),
+            Self::TooManyRequests(_) => StatusCode::TOO_MANY_REQUESTS,
+            Self::PaymentGateway { status, .. } => *status,
+            Self::BadGateway(_) => StatusCode::BAD_GATEWAY,
+            Self::ServiceUnavailable(_) => StatusCode::SERVICE_UNAVAILABLE,
+            Self::Internal(_) | Self::Database(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            Self::Transition(TransitionError::Illegal { .. }) => status_419(),
+            Self::Transition(TransitionError::NotFound { .. }) => StatusCode::NOT_FOUND,
+            Self::Transition(TransitionError::Database(_)) => StatusCode::INTERNAL_SERVER_ERROR,
+            Self::Inventory(InventoryError::InvalidTemplate(_)) => StatusCode::BAD_REQUEST,
+            Self::Inventory(InventoryError::Database(_)) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    /// Стабильный код ошибки для клиента.
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::BadRequest(_) => "bad_request",
+            Self::Unauthorized(_) => "unauthorized",
+            Self::Forbidden(_) => "forbidden",
+            Self::NotFound(_) => "not_found",
+            Self::Conflict(_) => "conflict",
+            Self::StateConflict(_) => "state_conflict",
+            Self::SeatsUnavailable(_) => "seats_unavailable",
+            Self::TooManyRequests(_) => "rate_limited",
+            Self::PaymentGateway { .. } => "payment_gateway_error",
+            Self::BadGateway(_) => "bad_gateway",
+            Self::ServiceUnavailable(_) => "service_unavailable",
+            Self::Internal(_) => "internal_error",
+            Self::Database(_) => "database_error",
+            Self::Transition(TransitionError::Illegal { .. }) => "state_conflict",
+            Self::Transition(TransitionError::NotFound { .. }) => "not_found",
+            Self::Transition(TransitionError::Database(_)) => "database_error",
+            Self::Inventory(InventoryError::InvalidTemplate(_)) => "bad_request",
+            Self::Inventory(InventoryError::Database(_)) => "database_error",
+        }
+    }
+
+    /// Сообщение для клиента. Внутренние детали БД не раскрываются.
+    fn public_message(&self) -> String {
+        match self {
+            Self::Database(_)
+            | Self::Transition(TransitionError::Database(_))
+            | Self::Inventory(InventoryError::Database(_)) => {
+                "Ошибка базы данных".to_string()
+            }
+            other => other.to_string(),
+        }
+    }
+
+    fn details(&self) -> Option<Value> {
+        match self {
+            Self::PaymentGateway { gateway_code, .. } => {
+                Some(serde_json::json!({ "gateway_code": gateway_code }))
+            }
+            Self::SeatsUnavailable(seat_ids) => Some(serde_json::json!({ "seat_ids": seat_ids })),
+            _ => None,
+        }
+    }
+}
+
+impl IntoResponse for AppError {
+    fn into_response(self) -> Response {
+        // Ошибки БД, проброшенные через `?`, логируем здесь; остальные логируются
+        // в месте возникновения, где больше контекста.
+        if let Self::Database(e)
+        | Self::Transition(TransitionError::Database(e))
+        | Self::Inventory(InventoryError::Database(e)) = &self
+        {
+            tracing::error!("Unhandled database error: {:?}", e);
+        }
+
+        let envelope = ErrorEnvelope {
+            success: false,
+            error: ErrorBody {
+                code: self.code(),
+                message: self.public_message(),
+                details: self.details(),
+            },
+            request_id: current_request_id(),
+        };
+        (self.status(), Json(envelope)).into_response()
+    }
+}
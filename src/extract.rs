@@ -0,0 +1,44 @@
+//! extract.rs
+//!
+//! Обертки над экстракторами axum, которые отдают ошибки разбора запроса
+//! в общем формате `AppError`, а не текстом.
+
+use axum::extract::{
+    rejection::{JsonRejection, PathRejection, QueryRejection},
+    FromRequest, FromRequestParts,
+};
+
+use crate::error::AppError;
+
+/// Тело запроса в JSON.
+#[derive(FromRequest)]
+#[from_request(via(axum::Json), rejection(AppError))]
+pub struct AppJson<T>(pub T);
+
+/// Параметры строки запроса.
+#[derive(FromRequestParts)]
+#[from_request(via(axum::extract::Query), rejection(AppError))]
+pub struct AppQuery<T>(pub T);
+
+/// Параметры пути.
+#[derive(FromRequestParts)]
+#[from_request(via(axum::extract::Path), rejection(AppError))]
+pub struct AppPath<T>(pub T);
+
+impl From<JsonRejection> for AppError {
+    fn from(rejection: JsonRejection) -> Self {
+        AppError::bad_request(rejection.body_text())
+    }
+}
+
+impl From<QueryRejection> for AppError {
+    fn from(rejection: QueryRejection) -> Self {
+        AppError::bad_request(rejection.body_text())
+    }
+}
+
+impl From<PathRejection> for AppError {
+    fn from(rejection: PathRejection) -> Self {
+        AppError::bad_request(rejection.body_text())
+    }
+}
@@ -1,11 +1,15 @@
 pub mod config;
+pub mod cursor;
 pub mod database;
+pub mod error;
+pub mod extract;
 pub mod redis_client;
 pub mod models;
 pub mod controllers;
 pub mod middleware;
 pub mod cache;
 pub mod services;
+#[cfg(feature = "search")]
 pub mod search_client;
 
 use std::sync::Arc;
@@ -18,46 +22,99 @@ pub struct AppState {
     pub redis: redis_client::RedisClient,
     pub cache: cache::CacheService,
     pub config: config::Config,
+    #[cfg(feature = "search")]
     pub search_client: search_client::SearchClient,
+    pub circuit_breaker: Arc<services::payment::CircuitBreaker>,
+    pub seat_events: services::SeatEventHub,
+    #[cfg(feature = "auth")]
+    pub jwt: services::JwtService,
 }
 
 impl AppState {
     pub async fn new(config: config::Config) -> Result<Arc<Self>, Box<dyn std::error::Error>> {
-        let db = database::Database::new(&config.database.url).await?;
+        let db = database::Database::new(&config.database).await?;
         
         db.run_migrations().await?;
+        db.verify_schema().await?;
         
+        db.start_replica_monitor();
+
+        if config.cache.auth_secret.is_none() {
+            tracing::warn!("CACHE_AUTH_SECRET is not set: auth cache uses a per-process key and is not shared between replicas");
+        }
+
         let redis = redis_client::RedisClient::new(&config.redis.url).await?;
         let cache = cache::CacheService::new(redis.clone(), db.clone());
-        let search_client = search_client::SearchClient::new(db.pool.clone());
+        #[cfg(feature = "search")]
+        let search_client = search_client::SearchClient::new(db.pool.clone(), config.search.clone());
+        #[cfg(feature = "search")]
+        search_client.verify_config().await?;
+        let circuit_breaker = Arc::new(services::payment::CircuitBreaker::from_config(
+            &config.circuit_breaker,
+            &redis,
+        ));
+        let seat_events = services::SeatEventHub::new(redis.clone());
+        seat_events.start_listener();
+        #[cfg(feature = "auth")]
+        let jwt = services::JwtService::new(&config.jwt);
         let state = Arc::new(Self {
             db,
             redis,
             cache,
             config,
+            #[cfg(feature = "search")]
             search_client,
+            circuit_breaker,
+            seat_events,
+            #[cfg(feature = "auth")]
+            jwt,
         });
         
         let state_for_bg = state.clone();
         task::spawn(async move {
+            // Предстоящие события могли измениться, пока сервис был остановлен
+            if let Err(e) = state_for_bg.db.refresh_upcoming_events().await {
+                tracing::error!("Upcoming events refresh failed: {:?}", e);
+            }
+
             // Warmup cache в фоне
             state_for_bg.cache.warmup_cache().await;
             
             // Initialize search в фоне
+            #[cfg(feature = "search")]
             if let Err(e) = state_for_bg.search_client.initialize().await {
                 tracing::error!("Search initialization failed: {:?}", e);
             }
         });
-
-        // let cleanup_service = crate::services::cleanup::CleanupService::new(state.clone());
-        // task::spawn(async move {
-        //     let mut interval = tokio::time::interval(std::time::Duration::from_secs(300));
-        //     loop {
-        //         interval.tick().await;
-        //         cleanup_service.run_full_cleanup().await;
-        //     }
-        // });
         
         Ok(state)
     }
-}
\ No newline at end of file
+}
+
+impl AppState {
+    /// Пул для запросов только на чтение: реплика, если в `scope` недавно не
+    /// писали, иначе основная БД, чтобы чтение увидело свою запись.
+    /// Отметки в Redis проверяются, только если есть реплика, пригодная для чтения.
+    pub async fn read_pool(&self, scope: cache::recent_writes::WriteScope) -> &sqlx::PgPool {
+        let pool = self.db.read_pool();
+        if std::ptr::eq(pool, &self.db.pool) || self.cache.has_recent_write(scope).await {
+            return &self.db.pool;
+        }
+        pool
+    }
+
+    /// Отмечает запись в `scope` для `read_pool`. Вызывается до записи. Без реплик
+    /// ничего не делает.
+    pub async fn mark_write(&self, scope: cache::recent_writes::WriteScope) {
+        if self.db.has_replicas() {
+            self.cache.mark_recent_write(scope, self.db.read_your_writes_window()).await;
+        }
+    }
+
+    /// Снимает отметку `mark_write`, если запрос отклонен и ничего не записал.
+    pub async fn unmark_write(&self, scope: cache::recent_writes::WriteScope) {
+        if self.db.has_replicas() {
+            self.cache.unmark_recent_write(scope).await;
+        }
+    }
+}
@@ -3,7 +3,7 @@ use mimalloc::MiMalloc;
 #[global_allocator]
 static GLOBAL: MiMalloc = MiMalloc;
 
-use axum::{routing::get, Router};
+use axum::{middleware::from_fn, routing::get, Router};
 use std::net::SocketAddr;
 use tokio::net::TcpListener;
 use tracing::info;
@@ -12,6 +12,8 @@ use ticket_system::{
     AppState,
     config::Config,
     controllers,
+    middleware::request_id::request_id,
+    services::scheduler::CleanupScheduler,
 };
 
 #[tokio::main(flavor = "multi_thread", worker_threads = 32)]
@@ -25,9 +27,11 @@ async fn main() {
     let app_state = AppState::new(config.clone())
         .await
         .expect("Failed to initialize application state");
+    let cleanup_scheduler = CleanupScheduler::start(app_state.clone());
     let app = Router::new()
         .route("/", get(root_handler))
         .nest("/api", controllers::routes(app_state.clone()))
+        .layer(from_fn(request_id))
         .with_state(app_state.clone());
     let port = std::env::var("PORT")
         .unwrap_or_else(|_| "8000".to_string())
@@ -36,9 +40,42 @@ async fn main() {
     let addr = SocketAddr::from(([0, 0, 0, 0], port));
     let listener = TcpListener::bind(addr).await.unwrap();
     info!("Server listening on http://{}", addr);
-    axum::serve(listener, app)
+    // ConnectInfo нужен rate limiting'у для ключа по IP клиента.
+    axum::serve(listener, app.into_make_service_with_connect_info::<SocketAddr>())
+        .with_graceful_shutdown(shutdown_signal())
         .await
         .unwrap();
+
+    // Сервер перестал принимать запросы - останавливаем фоновые задачи.
+    cleanup_scheduler.shutdown().await;
+    info!("Server stopped");
+}
+
+/// Ждет Ctrl+C или SIGTERM (docker stop).
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        tokio::signal::ctrl_c()
+            .await
+            .expect("Failed to install Ctrl+C handler");
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+            .expect("Failed to install SIGTERM handler")
+            .recv()
+            .await;
+    };
+
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {},
+        _ = terminate => {},
+    }
+
+    info!("Shutdown signal received");
 }
 
 async fn root_handler() -> &'static str {
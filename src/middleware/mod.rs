@@ -1,18 +1,37 @@
+#[cfg(feature = "rate-limiting")]
+pub mod rate_limit;
+pub mod request_id;
+
 use axum::{
     extract::{FromRequestParts, State},
-    http::{header, request::Parts, Request, StatusCode},
+    http::{header, request::Parts, Request},
     middleware::Next,
     response::Response,
     Extension,
 };
 use base64::{Engine as _, engine::general_purpose};
-use std::sync::Arc;
+use serde::{Deserialize, Serialize};
+use std::{
+    sync::{Arc, OnceLock},
+    time::Instant,
+};
 use sqlx::FromRow;
-use tracing::error;
-use crate::AppState;
+use tracing::{error, info, warn};
+use uuid::Uuid;
+use crate::{
+    AppState,
+    cache::recent_writes::WriteScope,
+    error::AppError,
+    models::user::{check_password, credentials_fingerprint, hash_password, PasswordCheck},
+};
+#[cfg(feature = "auth")]
+use crate::services::jwt::Claims;
+
+/// TTL кеша авторизации по умолчанию, если `CACHE_AUTH_TTL` не задан.
+const DEFAULT_AUTH_TTL_SECONDS: u64 = 300;
 
 /// Структура для представления аутентифицированного пользователя
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AuthUser {
     pub user_id: i32,
     pub email: String,
@@ -25,14 +44,14 @@ pub struct AuthUser {
 struct UserRow {
     user_id: i32,
     email: String,
+    password_hash: String,
     password_plain: Option<String>,
     first_name: String,
     surname: String,
-    is_active: bool,
 }
 
 impl FromRequestParts<Arc<AppState>> for AuthUser {
-    type Rejection = StatusCode;
+    type Rejection = AppError;
 
     async fn from_request_parts(
         parts: &mut Parts,
@@ -42,63 +61,258 @@ impl FromRequestParts<Arc<AppState>> for AuthUser {
             .headers
             .get(header::AUTHORIZATION)
             .and_then(|value| value.to_str().ok())
-            .ok_or(StatusCode::UNAUTHORIZED)?;
-        let encoded = auth_header
-            .strip_prefix("Basic ")
-            .ok_or(StatusCode::UNAUTHORIZED)?;
-        let decoded = general_purpose::STANDARD
-            .decode(encoded)
-            .map_err(|_| StatusCode::UNAUTHORIZED)?;
-        let credentials = String::from_utf8(decoded)
-            .map_err(|_| StatusCode::UNAUTHORIZED)?;
-        let mut parts = credentials.splitn(2, ':');
-        let email = parts.next().ok_or(StatusCode::UNAUTHORIZED)?;
-        let password = parts.next().ok_or(StatusCode::UNAUTHORIZED)?;
-        let row: Option<UserRow> = sqlx::query_as(
-            "SELECT user_id, email, password_plain, first_name, surname, is_active
-             FROM users 
-             WHERE email = $1 AND is_active = true"
-        )
-        .bind(email)
-        .fetch_optional(&state.db.pool)
-        .await
-        .map_err(|e| {
-            error!("Database error during auth: {}", e);
-            StatusCode::INTERNAL_SERVER_ERROR
-        })?;
-
-        let user = row.ok_or(StatusCode::UNAUTHORIZED)?;
-
-        if user.password_plain != Some(password.to_string()) {
-            return Err(StatusCode::UNAUTHORIZED);
+            .ok_or_else(|| AppError::unauthorized("Отсутствует заголовок Authorization"))?;
+
+        // Bearer-токен проверяется без обращения к БД.
+        #[cfg(feature = "auth")]
+        if let Some(token) = auth_header.strip_prefix("Bearer ") {
+            let claims = authenticate_bearer(state, token).await?;
+            return claims.auth_user().ok_or_else(|| AppError::unauthorized("Токен недействителен"));
         }
 
-        sqlx::query("UPDATE users SET last_logged_in = NOW() WHERE user_id = $1")
-            .bind(user.user_id)
-            .execute(&state.db.pool)
-            .await
-            .ok();
-
-        Ok(AuthUser {
-            user_id: user.user_id,
-            email: user.email,
-            first_name: user.first_name,
-            surname: user.surname,
-        })
+        let (email, password) = parse_basic_credentials(auth_header)
+            .ok_or_else(|| AppError::unauthorized("Ожидаются Basic-учетные данные или Bearer-токен"))?;
+
+        authenticate_basic(state, &email, &password).await
+    }
+}
+
+/// Разбирает заголовок `Authorization: Basic base64(email:password)`.
+pub fn parse_basic_credentials(auth_header: &str) -> Option<(String, String)> {
+    let encoded = auth_header.strip_prefix("Basic ")?;
+    let decoded = general_purpose::STANDARD.decode(encoded).ok()?;
+    let credentials = String::from_utf8(decoded).ok()?;
+    let (email, password) = credentials.split_once(':')?;
+    Some((email.to_string(), password.to_string()))
+}
+
+/// Проверяет подпись и срок действия JWT, а также что он не отозван.
+#[cfg(feature = "auth")]
+pub async fn authenticate_bearer(state: &Arc<AppState>, token: &str) -> Result<Claims, AppError> {
+    let claims = state.jwt.decode(token).map_err(|e| {
+        tracing::debug!("Rejected bearer token: {}", e);
+        AppError::unauthorized("Токен недействителен")
+    })?;
+
+    // Если Redis недоступен, не можем проверить отзыв - токен не принимаем.
+    let revoked = state.cache.is_jwt_revoked(&claims.jti).await.map_err(|e| {
+        error!("Failed to check JWT revocation: {:?}", e);
+        AppError::ServiceUnavailable("Не удалось проверить токен".to_string())
+    })?;
+    if revoked {
+        return Err(AppError::unauthorized("Токен отозван"));
+    }
+
+    Ok(claims)
+}
+
+/// Проверяет пару email/пароль: сначала по кешу в Redis, затем по БД.
+///
+/// В ключ кеша попадает не пароль, а HMAC от `email:password` с серверным
+/// ключом, поэтому запись находится только при тех же самых учетных данных.
+pub async fn authenticate_basic(
+    state: &Arc<AppState>,
+    email: &str,
+    password: &str,
+) -> Result<AuthUser, AppError> {
+    let fingerprint = credentials_fingerprint(auth_cache_secret(state).as_bytes(), email, password);
+
+    // Быстрый путь: пользователь уже проходил авторизацию с этими данными.
+    if let Ok(Some(cached)) = state.cache.get_cached_auth_user(email, &fingerprint).await {
+        if let Ok(user) = serde_json::from_str::<AuthUser>(&cached) {
+            touch_last_login(state, user.user_id).await;
+            return Ok(user);
+        }
+    }
+
+    let row: Option<UserRow> = sqlx::query_as(
+        "SELECT user_id, email, password_hash, password_plain, first_name, surname
+         FROM users 
+         WHERE email = $1 AND is_active = true"
+    )
+    .bind(email)
+    .fetch_optional(&state.db.pool)
+    .await
+    .map_err(|e| {
+        error!("Database error during auth: {}", e);
+        AppError::internal("Ошибка проверки учетных данных")
+    })?;
+
+    let user = row.ok_or_else(invalid_credentials)?;
+
+    // bcrypt нагружает CPU, поэтому проверка идет вне async-воркеров.
+    let password_hash = user.password_hash.clone();
+    let password_plain = user.password_plain.clone();
+    let candidate = password.to_string();
+    let check = tokio::task::spawn_blocking(move || {
+        check_password(&password_hash, password_plain.as_deref(), &candidate)
+    })
+    .await
+    .map_err(|e| {
+        error!("Password check task failed: {}", e);
+        AppError::internal("Ошибка проверки учетных данных")
+    })?;
+
+    match check {
+        PasswordCheck::Invalid => return Err(invalid_credentials()),
+        PasswordCheck::Valid => {}
+        PasswordCheck::ValidNeedsUpgrade => upgrade_password_hash(state, user.user_id, password),
+    }
+
+    let auth_user = AuthUser {
+        user_id: user.user_id,
+        email: user.email,
+        first_name: user.first_name,
+        surname: user.surname,
+    };
+
+    let ttl = state.config.cache.auth_ttl_seconds.unwrap_or(DEFAULT_AUTH_TTL_SECONDS);
+    if ttl > 0 {
+        if let Ok(data) = serde_json::to_string(&auth_user) {
+            if let Err(e) = state.cache.cache_auth_user(email, &fingerprint, &data, ttl).await {
+                warn!("Failed to cache auth user {}: {:?}", email, e);
+            }
+        }
     }
+
+    touch_last_login(state, auth_user.user_id).await;
+
+    Ok(auth_user)
+}
+
+/// Ключ HMAC кеша авторизации: `CACHE_AUTH_SECRET` или случайный ключ процесса.
+fn auth_cache_secret(state: &AppState) -> &str {
+    static PROCESS_SECRET: OnceLock<String> = OnceLock::new();
+    match state.config.cache.auth_secret.as_deref() {
+        Some(secret) => secret,
+        None => PROCESS_SECRET.get_or_init(|| format!("{}{}", Uuid::new_v4().simple(), Uuid::new_v4().simple())),
+    }
+}
+
+/// Обновляет `last_logged_in` не чаще раза в 15 минут на пользователя.
+async fn touch_last_login(state: &Arc<AppState>, user_id: i32) {
+    if !state.cache.should_update_last_login(user_id).await {
+        return;
+    }
+
+    let pool = state.db.pool.clone();
+    tokio::spawn(async move {
+        if let Err(e) = sqlx::query("UPDATE users SET last_logged_in = NOW() WHERE user_id = $1")
+            .bind(user_id)
+            .execute(&pool)
+            .await
+        {
+            warn!("Failed to update last_logged_in for user {}: {}", user_id, e);
+        }
+    });
+}
+
+/// Записывает хеш для пользователя, у которого был только `password_plain`.
+///
+/// После успешной записи plaintext удаляется, дальнейшие входы идут по хешу.
+fn upgrade_password_hash(state: &Arc<AppState>, user_id: i32, password: &str) {
+    let pool = state.db.pool.clone();
+    let password = password.to_string();
+    tokio::spawn(async move {
+        let hashed = match tokio::task::spawn_blocking(move || hash_password(&password)).await {
+            Ok(Ok(hash)) => hash,
+            Ok(Err(e)) => {
+                error!("Failed to hash password for user {}: {}", user_id, e);
+                return;
+            }
+            Err(e) => {
+                error!("Password hash task failed for user {}: {}", user_id, e);
+                return;
+            }
+        };
+
+        let result = sqlx::query(
+            "UPDATE users SET password_hash = $1, password_plain = NULL WHERE user_id = $2"
+        )
+        .bind(hashed)
+        .bind(user_id)
+        .execute(&pool)
+        .await;
+
+        match result {
+            Ok(_) => info!("Migrated plaintext password to hash for user {}", user_id),
+            Err(e) => error!("Failed to store password hash for user {}: {}", user_id, e),
+        }
+    });
+}
+
+/// Одна и та же ошибка для неизвестного email и неверного пароля.
+fn invalid_credentials() -> AppError {
+    AppError::unauthorized("Неверный email или пароль")
 }
 
 pub async fn require_auth(
     State(state): State<Arc<AppState>>,
     request: Request<axum::body::Body>,
     next: Next,
-) -> Result<Response, StatusCode> {
+) -> Result<Response, AppError> {
     let (mut parts, body) = request.into_parts();
     let auth_user = AuthUser::from_request_parts(&mut parts, &state).await?;
+    let user_id = auth_user.user_id;
     let mut request = Request::from_parts(parts, body);
     request.extensions_mut().insert(auth_user);
-    
-    Ok(next.run(request).await)
+
+    run_marking_writes(&state, &[WriteScope::User(user_id)], request, next).await
+}
+
+/// Выполняет запрос и, если он может что-то изменить, отмечает запись в `scopes`:
+/// следующие чтения пойдут в основную БД, а не на отстающую реплику. Отметка
+/// ставится до обработчика и снимается, только если запрос отклонен (4xx):
+/// такой ответ обработчики отдают до записи или после отката транзакции.
+/// После 5xx запись могла зафиксироваться (например, ошибка после `COMMIT`),
+/// поэтому отметка остается и истекает сама.
+async fn run_marking_writes(
+    state: &AppState,
+    scopes: &[WriteScope],
+    request: Request<axum::body::Body>,
+    next: Next,
+) -> Result<Response, AppError> {
+    if request.method().is_safe() {
+        return Ok(next.run(request).await);
+    }
+
+    for scope in scopes {
+        state.mark_write(*scope).await;
+    }
+    let started = Instant::now();
+    let response = next.run(request).await;
+
+    if response.status().is_client_error() {
+        for scope in scopes {
+            state.unmark_write(*scope).await;
+        }
+    } else if started.elapsed() > state.db.read_your_writes_window() / 2 {
+        // Окно отсчитывается от начала запроса: после долгой записи его продлеваем
+        for scope in scopes {
+            state.mark_write(*scope).await;
+        }
+    }
+    Ok(response)
+}
+
+/// Пропускает только пользователей из `ADMIN_EMAILS`.
+pub async fn require_admin(
+    State(state): State<Arc<AppState>>,
+    request: Request<axum::body::Body>,
+    next: Next,
+) -> Result<Response, AppError> {
+    let (mut parts, body) = request.into_parts();
+    let auth_user = AuthUser::from_request_parts(&mut parts, &state).await?;
+    if !state.config.admin.emails.iter().any(|email| email == &auth_user.email) {
+        warn!("User {} tried to access admin API", auth_user.user_id);
+        return Err(AppError::forbidden("Доступ только для администраторов"));
+    }
+    let user_id = auth_user.user_id;
+    let mut request = Request::from_parts(parts, body);
+    request.extensions_mut().insert(auth_user);
+
+    // Правки админки меняют каталог: списки и поиск читают основную БД, пока реплики догоняют
+    run_marking_writes(&state, &[WriteScope::User(user_id), WriteScope::Events], request, next).await
 }
 
 pub async fn get_auth_user_from_extensions(Extension(user): Extension<AuthUser>) -> AuthUser {
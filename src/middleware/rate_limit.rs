@@ -0,0 +1,144 @@
+//! rate_limit.rs
+//!
+//! Ограничение частоты запросов по группам маршрутов.
+//!
+//! Счетчики хранятся в Redis (скользящее окно, см. `cache::rate_limit`), поэтому
+//! лимит общий для всех реплик приложения. Ключ - `AuthUser.user_id`, если
+//! пользователь уже авторизован, иначе IP клиента.
+
+use axum::{
+    extract::{ConnectInfo, MatchedPath, State},
+    http::{HeaderMap, HeaderValue, Method, Request},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use std::{net::SocketAddr, sync::Arc};
+use tracing::warn;
+use crate::{
+    AppState,
+    cache::rate_limit::RateLimitDecision,
+    error::AppError,
+    config::{RateLimitConfig, RateLimitRule},
+    middleware::AuthUser,
+};
+
+/// Группа маршрутов с общим лимитом.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RouteGroup {
+    Events,
+    SeatSelect,
+    PaymentInit,
+}
+
+impl RouteGroup {
+    /// Определяет группу по методу и шаблону маршрута (без префикса `/api`).
+    pub fn for_route(method: &Method, path: &str) -> Option<Self> {
+        match (method, path) {
+            (&Method::GET, "/events" | "/events/search" | "/events/suggest" | "/events/{id}") => Some(RouteGroup::Events),
+            (&Method::PATCH, "/seats/select" | "/seats/select-batch") => Some(RouteGroup::SeatSelect),
+            (&Method::PATCH, "/bookings/initiatePayment") => Some(RouteGroup::PaymentInit),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RouteGroup::Events => "events",
+            RouteGroup::SeatSelect => "seat_select",
+            RouteGroup::PaymentInit => "payment_init",
+        }
+    }
+
+    fn rule(&self, config: &RateLimitConfig) -> RateLimitRule {
+        match self {
+            RouteGroup::Events => config.events,
+            RouteGroup::SeatSelect => config.seat_select,
+            RouteGroup::PaymentInit => config.payment_init,
+        }
+    }
+}
+
+/// Мидлвэр rate limiting. Маршруты вне групп и группы с `limit = 0` не ограничиваются.
+/// Если Redis недоступен, запрос пропускается - лимиты не должны ронять API.
+pub async fn rate_limit(
+    State(state): State<Arc<AppState>>,
+    request: Request<axum::body::Body>,
+    next: Next,
+) -> Response {
+    let config = &state.config.rate_limit;
+    let group = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|path| path.as_str())
+        .map(|path| path.strip_prefix("/api").unwrap_or(path))
+        .and_then(|path| RouteGroup::for_route(request.method(), path));
+
+    let Some(group) = group else {
+        return next.run(request).await;
+    };
+    let rule = group.rule(config);
+    if rule.limit == 0 {
+        return next.run(request).await;
+    }
+
+    let key = format!("ratelimit:{}:{}", group.as_str(), client_key(&request, config.trust_forwarded_for));
+
+    let decision = match state.cache.check_rate_limit(&key, rule.limit, rule.window_seconds).await {
+        Ok(decision) => decision,
+        Err(e) => {
+            warn!("Rate limit check failed for {}: {:?}", key, e);
+            return next.run(request).await;
+        }
+    };
+
+    if !decision.allowed {
+        let mut response = AppError::TooManyRequests(
+            "Слишком много запросов, попробуйте позже".to_string(),
+        ).into_response();
+        let headers = response.headers_mut();
+        set_rate_limit_headers(headers, &decision);
+        headers.insert("Retry-After", HeaderValue::from(reset_seconds(&decision)));
+        return response;
+    }
+
+    let mut response = next.run(request).await;
+    set_rate_limit_headers(response.headers_mut(), &decision);
+    response
+}
+
+/// Ключ клиента: пользователь, если он уже авторизован, иначе IP.
+fn client_key<B>(request: &Request<B>, trust_forwarded_for: bool) -> String {
+    if let Some(user) = request.extensions().get::<AuthUser>() {
+        return format!("user:{}", user.user_id);
+    }
+
+    if trust_forwarded_for {
+        let forwarded = request
+            .headers()
+            .get("X-Forwarded-For")
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.split(',').next())
+            .or_else(|| request.headers().get("X-Real-IP").and_then(|v| v.to_str().ok()))
+            .map(str::trim)
+            .filter(|v| !v.is_empty());
+        if let Some(ip) = forwarded {
+            return format!("ip:{}", ip);
+        }
+    }
+
+    match request.extensions().get::<ConnectInfo<SocketAddr>>() {
+        Some(ConnectInfo(addr)) => format!("ip:{}", addr.ip()),
+        None => "ip:unknown".to_string(),
+    }
+}
+
+/// Секунды до освобождения места в окне, округленные вверх.
+fn reset_seconds(decision: &RateLimitDecision) -> u64 {
+    decision.reset_after_ms.div_ceil(1000).max(1)
+}
+
+fn set_rate_limit_headers(headers: &mut HeaderMap, decision: &RateLimitDecision) {
+    headers.insert("X-RateLimit-Limit", HeaderValue::from(decision.limit));
+    headers.insert("X-RateLimit-Remaining", HeaderValue::from(decision.remaining));
+    headers.insert("X-RateLimit-Reset", HeaderValue::from(reset_seconds(decision)));
+}
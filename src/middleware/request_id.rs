@@ -0,0 +1,56 @@
+//! request_id.rs
+//!
+//! Идентификатор запроса для логов и ответов с ошибками.
+//!
+//! Берется из входящего `X-Request-Id` (если клиент или балансировщик его
+//! передал) или генерируется заново. Возвращается в заголовке ответа,
+//! попадает в span трассировки и в тело ошибок `AppError`.
+
+use axum::{
+    extract::Request,
+    http::HeaderValue,
+    middleware::Next,
+    response::Response,
+};
+use tracing::Instrument;
+use uuid::Uuid;
+
+/// Заголовок с идентификатором запроса.
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+/// Максимальная длина принимаемого от клиента идентификатора.
+const MAX_REQUEST_ID_LEN: usize = 128;
+
+tokio::task_local! {
+    static REQUEST_ID: String;
+}
+
+/// Идентификатор текущего запроса, если код выполняется внутри мидлвэра `request_id`.
+pub fn current_request_id() -> Option<String> {
+    REQUEST_ID.try_with(|id| id.clone()).ok()
+}
+
+/// Назначает запросу идентификатор и возвращает его в `X-Request-Id`.
+pub async fn request_id(request: Request, next: Next) -> Response {
+    let id = request
+        .headers()
+        .get(REQUEST_ID_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .filter(|value| {
+            !value.is_empty()
+                && value.len() <= MAX_REQUEST_ID_LEN
+                && value.bytes().all(|b| b.is_ascii_graphic())
+        })
+        .map(str::to_string)
+        .unwrap_or_else(|| Uuid::new_v4().to_string());
+
+    let span = tracing::info_span!("request", request_id = %id);
+    let mut response = REQUEST_ID
+        .scope(id.clone(), next.run(request).instrument(span))
+        .await;
+
+    if let Ok(value) = HeaderValue::from_str(&id) {
+        response.headers_mut().insert(REQUEST_ID_HEADER, value);
+    }
+    response
+}
@@ -0,0 +1,63 @@
+-- updated_at для изменяемых таблиц + триггер, который его поддерживает
+
+CREATE OR REPLACE FUNCTION set_updated_at() RETURNS trigger AS $$
+BEGIN
+    NEW.updated_at = NOW();
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+ALTER TABLE seats ADD COLUMN IF NOT EXISTS updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW();
+ALTER TABLE bookings ADD COLUMN IF NOT EXISTS updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW();
+ALTER TABLE payment_transactions ADD COLUMN IF NOT EXISTS updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW();
+
+DROP TRIGGER IF EXISTS trg_seats_updated_at ON seats;
+CREATE TRIGGER trg_seats_updated_at
+    BEFORE UPDATE ON seats
+    FOR EACH ROW EXECUTE FUNCTION set_updated_at();
+
+DROP TRIGGER IF EXISTS trg_bookings_updated_at ON bookings;
+CREATE TRIGGER trg_bookings_updated_at
+    BEFORE UPDATE ON bookings
+    FOR EACH ROW EXECUTE FUNCTION set_updated_at();
+
+DROP TRIGGER IF EXISTS trg_payment_transactions_updated_at ON payment_transactions;
+CREATE TRIGGER trg_payment_transactions_updated_at
+    BEFORE UPDATE ON payment_transactions
+    FOR EACH ROW EXECUTE FUNCTION set_updated_at();
+
+-- Приводим статусы, записанные старым кодом очистки, к единому словарю
+
+-- SELECTED старый код писал в БД вместе с RESERVED: место с бронью остается
+-- зарезервированным (проход по удержаниям освободит его, если удержания нет),
+-- без брони - свободно
+UPDATE seats
+SET status = CASE
+        WHEN status = 'SELECTED' AND booking_id IS NOT NULL THEN 'RESERVED'
+        ELSE 'FREE'
+    END
+WHERE status IN ('AVAILABLE', 'SELECTED') OR status IS NULL;
+UPDATE bookings SET status = 'created' WHERE status IS NULL;
+-- Платеж без статуса не подтвержден шлюзом и не должен держать бронь в оплате
+UPDATE payment_transactions SET status = 'failed' WHERE status IS NULL;
+
+-- Допустимые статусы
+
+ALTER TABLE seats ALTER COLUMN status SET NOT NULL;
+ALTER TABLE seats DROP CONSTRAINT IF EXISTS seats_status_check;
+ALTER TABLE seats ADD CONSTRAINT seats_status_check
+    CHECK (status IN ('FREE', 'RESERVED', 'SOLD'));
+
+ALTER TABLE bookings ALTER COLUMN status SET NOT NULL;
+ALTER TABLE bookings DROP CONSTRAINT IF EXISTS bookings_status_check;
+ALTER TABLE bookings ADD CONSTRAINT bookings_status_check
+    CHECK (status IN ('created', 'pending_payment', 'paid', 'cancelled'));
+
+ALTER TABLE payment_transactions ALTER COLUMN status SET NOT NULL;
+ALTER TABLE payment_transactions DROP CONSTRAINT IF EXISTS payment_transactions_status_check;
+ALTER TABLE payment_transactions ADD CONSTRAINT payment_transactions_status_check
+    CHECK (status IN ('pending', 'completed', 'failed', 'expired'));
+
+-- Очистка ищет бронирования и платежи по статусу и времени создания
+CREATE INDEX IF NOT EXISTS idx_bookings_status_created ON bookings (status, created_at);
+CREATE INDEX IF NOT EXISTS idx_payment_status_created ON payment_transactions (status, created_at);
@@ -0,0 +1,50 @@
+-- Площадки, секции и схема зала с координатами мест
+
+CREATE TABLE IF NOT EXISTS venues (
+    id BIGSERIAL PRIMARY KEY,
+    name TEXT NOT NULL,
+    address TEXT,
+    -- Размер холста схемы зала, в тех же единицах, что и координаты мест
+    width INTEGER NOT NULL CHECK (width > 0),
+    height INTEGER NOT NULL CHECK (height > 0),
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE TABLE IF NOT EXISTS sections (
+    id BIGSERIAL PRIMARY KEY,
+    venue_id BIGINT NOT NULL REFERENCES venues(id) ON DELETE CASCADE,
+    name TEXT NOT NULL,
+    -- Ярус: партер, амфитеатр, балкон
+    tier TEXT,
+    sort_order INTEGER NOT NULL DEFAULT 0,
+    UNIQUE (venue_id, name)
+);
+
+-- Положение места в схеме: одно на (секция, ряд, номер) для всех событий площадки
+CREATE TABLE IF NOT EXISTS seat_layouts (
+    section_id BIGINT NOT NULL REFERENCES sections(id) ON DELETE CASCADE,
+    row INTEGER NOT NULL,
+    number INTEGER NOT NULL,
+    x REAL NOT NULL,
+    y REAL NOT NULL,
+    PRIMARY KEY (section_id, row, number)
+);
+
+DROP TRIGGER IF EXISTS trg_venues_updated_at ON venues;
+CREATE TRIGGER trg_venues_updated_at
+    BEFORE UPDATE ON venues
+    FOR EACH ROW EXECUTE FUNCTION set_updated_at();
+
+ALTER TABLE events_archive ADD COLUMN IF NOT EXISTS venue_id BIGINT REFERENCES venues(id);
+ALTER TABLE seats ADD COLUMN IF NOT EXISTS section_id BIGINT REFERENCES sections(id);
+
+-- Ряд и номер уникальны в пределах секции; места без секции по-прежнему
+-- уникальны в пределах события
+ALTER TABLE seats DROP CONSTRAINT IF EXISTS seats_event_id_row_number_key;
+ALTER TABLE seats DROP CONSTRAINT IF EXISTS seats_event_section_row_number_key;
+ALTER TABLE seats ADD CONSTRAINT seats_event_section_row_number_key
+    UNIQUE NULLS NOT DISTINCT (event_id, section_id, row, number);
+
+CREATE INDEX IF NOT EXISTS idx_events_venue ON events_archive (venue_id);
+CREATE INDEX IF NOT EXISTS idx_sections_venue ON sections (venue_id);
@@ -0,0 +1,16 @@
+-- Статус публикации события для административного API
+
+ALTER TABLE events_archive ADD COLUMN IF NOT EXISTS status VARCHAR(20) NOT NULL DEFAULT 'published';
+ALTER TABLE events_archive ADD COLUMN IF NOT EXISTS updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW();
+
+ALTER TABLE events_archive DROP CONSTRAINT IF EXISTS events_archive_status_check;
+ALTER TABLE events_archive ADD CONSTRAINT events_archive_status_check
+    CHECK (status IN ('published', 'unpublished', 'cancelled'));
+
+DROP TRIGGER IF EXISTS trg_events_archive_updated_at ON events_archive;
+CREATE TRIGGER trg_events_archive_updated_at
+    BEFORE UPDATE ON events_archive
+    FOR EACH ROW EXECUTE FUNCTION set_updated_at();
+
+-- Публичные списки показывают только опубликованные предстоящие события
+CREATE INDEX IF NOT EXISTS idx_events_status_datetime_start ON events_archive (status, datetime_start);
@@ -0,0 +1,7 @@
+-- Индексы под keyset-пагинацию: порядок списков дополнен `id`, чтобы позиция
+-- в списке была однозначной
+
+DROP INDEX IF EXISTS idx_events_status_datetime_start;
+CREATE INDEX IF NOT EXISTS idx_events_status_datetime_id ON events_archive (status, datetime_start, id);
+
+CREATE INDEX IF NOT EXISTS idx_seats_event_row_number_id ON seats (event_id, row, number, id);
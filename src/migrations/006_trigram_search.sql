@@ -0,0 +1,5 @@
+-- Нечеткий поиск и автодополнение по названию события (pg_trgm)
+
+CREATE EXTENSION IF NOT EXISTS pg_trgm;
+
+CREATE INDEX IF NOT EXISTS idx_events_title_trgm ON events_archive USING GIN (title gin_trgm_ops);
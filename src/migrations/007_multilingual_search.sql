@@ -0,0 +1,53 @@
+-- Многоязычный поисковый вектор: русский, английский и 'simple' (без стемминга -
+-- казахский и транслит). Запросы ищут по нескольким конфигурациям сразу
+-- (SEARCH_LANGUAGES), поэтому лексемы каждой из них должны быть в векторе.
+--
+-- Пересоздание generated-колонки переписало бы всю events_archive, поэтому
+-- search_vector становится обычной колонкой (меняется только каталог, индекс
+-- остается): новые и измененные строки заполняет триггер, старые - фоновая
+-- задача пачками по id (background_migrations, services/backfill.rs).
+
+CREATE OR REPLACE FUNCTION multilingual_tsvector(doc TEXT) RETURNS tsvector
+LANGUAGE sql IMMUTABLE PARALLEL SAFE AS $$
+    SELECT to_tsvector('russian', doc) || to_tsvector('english', doc) || to_tsvector('simple', doc)
+$$;
+
+CREATE OR REPLACE FUNCTION events_search_vector(title TEXT, description TEXT, type TEXT, provider TEXT) RETURNS tsvector
+LANGUAGE sql IMMUTABLE PARALLEL SAFE AS $$
+    SELECT setweight(multilingual_tsvector(coalesce(title, '')), 'A') ||
+           setweight(multilingual_tsvector(coalesce(description, '')), 'B') ||
+           setweight(multilingual_tsvector(coalesce(type, '')), 'C') ||
+           setweight(multilingual_tsvector(coalesce(provider, '')), 'C')
+$$;
+
+ALTER TABLE events_archive ALTER COLUMN search_vector DROP EXPRESSION IF EXISTS;
+
+CREATE OR REPLACE FUNCTION set_events_search_vector() RETURNS trigger AS $$
+BEGIN
+    NEW.search_vector = events_search_vector(NEW.title, NEW.description, NEW.type, NEW.provider);
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+DROP TRIGGER IF EXISTS trg_events_archive_search_vector ON events_archive;
+CREATE TRIGGER trg_events_archive_search_vector
+    BEFORE INSERT OR UPDATE OF title, description, type, provider ON events_archive
+    FOR EACH ROW EXECUTE FUNCTION set_events_search_vector();
+
+-- Заполнение search_vector не меняет событие: updated_at двигают только
+-- изменения его полей
+DROP TRIGGER IF EXISTS trg_events_archive_updated_at ON events_archive;
+CREATE TRIGGER trg_events_archive_updated_at
+    BEFORE UPDATE OF title, description, type, datetime_start, provider, venue_id, status ON events_archive
+    FOR EACH ROW EXECUTE FUNCTION set_updated_at();
+
+-- Фоновые переносы данных: задача обрабатывает строки с id > last_id пачками
+-- и отмечает completed_at, когда строк не осталось
+CREATE TABLE IF NOT EXISTS background_migrations (
+    name TEXT PRIMARY KEY,
+    last_id BIGINT NOT NULL DEFAULT 0,
+    completed_at TIMESTAMPTZ
+);
+
+INSERT INTO background_migrations (name) VALUES ('events_search_vector')
+ON CONFLICT (name) DO NOTHING;
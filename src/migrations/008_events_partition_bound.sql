@@ -0,0 +1,22 @@
+-- Подготовка к секционированию events_archive (миграция 010) без перезаписи таблицы.
+--
+-- Существующая таблица станет секцией events_archive_legacy со всеми строками
+-- до начала года, следующего за последним событием (и не раньше следующего
+-- года). Граница фиксируется функцией events_archive_legacy_bound(). CHECK
+-- с той же границей добавляется NOT VALID - без чтения таблицы - и проверяется
+-- отдельной миграцией 009, после чего таблица присоединяется секцией без
+-- повторной проверки строк.
+
+DO $$
+DECLARE
+    bound TIMESTAMP;
+BEGIN
+    SELECT date_trunc('year', greatest(max(datetime_start), LOCALTIMESTAMP)) + interval '1 year'
+    INTO bound
+    FROM events_archive;
+
+    EXECUTE format('CREATE OR REPLACE FUNCTION events_archive_legacy_bound() RETURNS TIMESTAMP
+                    LANGUAGE sql IMMUTABLE PARALLEL SAFE AS %L', format('SELECT %L::timestamp', bound));
+    EXECUTE format('ALTER TABLE events_archive ADD CONSTRAINT events_archive_legacy_bound_check
+                    CHECK (datetime_start < %L) NOT VALID', bound);
+END $$;
@@ -0,0 +1,6 @@
+-- Проверка границы из 008 читает всю events_archive, но держит только
+-- SHARE UPDATE EXCLUSIVE: чтение и запись событий не блокируются.
+-- Отдельная миграция - отдельная транзакция, ACCESS EXCLUSIVE из 008
+-- к этому моменту уже снята.
+
+ALTER TABLE events_archive VALIDATE CONSTRAINT events_archive_legacy_bound_check;
@@ -0,0 +1,208 @@
+-- Секционирование events_archive по datetime_start и таблица предстоящих событий.
+--
+-- Строки не копируются: существующая таблица присоединяется секцией
+-- events_archive_legacy (до events_archive_legacy_bound(), граница проверена
+-- миграциями 008-009), ее индексы и ограничения совпадают с родительскими и
+-- переиспользуются. Следующие годы - секции events_archive_yYYYY, строки вне
+-- созданных секций попадают в events_archive_default. Будущие секции создает
+-- фоновая задача (CleanupScheduler) и админка перед записью события.
+--
+-- Уникальный индекс секционированной таблицы обязан включать ключ
+-- секционирования, поэтому уникальность id и внешние ключи seats и bookings
+-- держит таблица event_ids, которую ведут триггеры events_archive.
+
+DROP VIEW IF EXISTS events_current;
+
+ALTER TABLE events_archive RENAME TO events_archive_legacy;
+ALTER INDEX events_archive_pkey RENAME TO events_archive_legacy_pkey;
+ALTER INDEX idx_events_search_vector RENAME TO events_archive_legacy_search_vector_idx;
+ALTER INDEX idx_events_datetime_start RENAME TO events_archive_legacy_datetime_start_idx;
+ALTER INDEX idx_events_type RENAME TO events_archive_legacy_type_idx;
+ALTER INDEX idx_events_provider RENAME TO events_archive_legacy_provider_idx;
+ALTER INDEX idx_events_venue RENAME TO events_archive_legacy_venue_id_idx;
+ALTER INDEX idx_events_status_datetime_id RENAME TO events_archive_legacy_status_datetime_start_id_idx;
+ALTER INDEX idx_events_title_trgm RENAME TO events_archive_legacy_title_idx;
+
+-- Триггеры родителя копируются в секцию при присоединении
+DROP TRIGGER trg_events_archive_search_vector ON events_archive_legacy;
+DROP TRIGGER trg_events_archive_updated_at ON events_archive_legacy;
+
+ALTER SEQUENCE events_archive_id_seq OWNED BY NONE;
+
+-- Определения колонок, CHECK и внешнего ключа повторяют events_archive_legacy:
+-- при присоединении Postgres сопоставляет их по имени и не проверяет строки заново
+CREATE TABLE events_archive (
+    id BIGINT NOT NULL DEFAULT nextval('events_archive_id_seq'),
+    title TEXT NOT NULL,
+    description TEXT,
+    type TEXT NOT NULL,
+    datetime_start TIMESTAMP NOT NULL,
+    provider TEXT NOT NULL,
+    search_vector tsvector,
+    venue_id BIGINT CONSTRAINT events_archive_venue_id_fkey REFERENCES venues(id),
+    status VARCHAR(20) NOT NULL DEFAULT 'published',
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    CONSTRAINT events_archive_status_check CHECK (status IN ('published', 'unpublished', 'cancelled'))
+) PARTITION BY RANGE (datetime_start);
+
+ALTER SEQUENCE events_archive_id_seq OWNED BY events_archive.id;
+
+-- Индексы создаются на пустом родителе; у events_archive_legacy такие же уже есть
+CREATE INDEX idx_events_search_vector ON events_archive USING GIN (search_vector);
+CREATE INDEX idx_events_datetime_start ON events_archive (datetime_start DESC);
+CREATE INDEX idx_events_type ON events_archive (type);
+CREATE INDEX idx_events_provider ON events_archive (provider);
+CREATE INDEX idx_events_venue ON events_archive (venue_id);
+CREATE INDEX idx_events_status_datetime_id ON events_archive (status, datetime_start, id);
+CREATE INDEX idx_events_title_trgm ON events_archive USING GIN (title gin_trgm_ops);
+
+ALTER TABLE events_archive ATTACH PARTITION events_archive_legacy
+    FOR VALUES FROM (MINVALUE) TO (events_archive_legacy_bound());
+-- Граница теперь - ограничение секции
+ALTER TABLE events_archive_legacy DROP CONSTRAINT events_archive_legacy_bound_check;
+
+-- Первичный ключ (id) у каждой секции свой: поиск по id и защита от дубликата
+-- с той же датой; между секциями id уникален через event_ids
+CREATE TABLE events_archive_default PARTITION OF events_archive (PRIMARY KEY (id)) DEFAULT;
+
+CREATE TRIGGER trg_events_archive_search_vector
+    BEFORE INSERT OR UPDATE OF title, description, type, provider ON events_archive
+    FOR EACH ROW EXECUTE FUNCTION set_events_search_vector();
+CREATE TRIGGER trg_events_archive_updated_at
+    BEFORE UPDATE OF title, description, type, datetime_start, provider, venue_id, status ON events_archive
+    FOR EACH ROW EXECUTE FUNCTION set_updated_at();
+
+-- Создает секцию года, если ее нет. Возвращает TRUE, если секция создана.
+CREATE OR REPLACE FUNCTION ensure_events_partition(partition_year INT) RETURNS BOOLEAN
+LANGUAGE plpgsql AS $$
+DECLARE
+    partition_name TEXT := format('events_archive_y%s', partition_year);
+    range_from TIMESTAMP := make_timestamp(partition_year, 1, 1, 0, 0, 0);
+    range_to TIMESTAMP := make_timestamp(partition_year + 1, 1, 1, 0, 0, 0);
+BEGIN
+    -- Годы до границы хранит events_archive_legacy
+    IF range_from < events_archive_legacy_bound() OR to_regclass(partition_name) IS NOT NULL THEN
+        RETURN FALSE;
+    END IF;
+
+    -- Реплики создают секции параллельно
+    PERFORM pg_advisory_xact_lock(hashtext('ensure_events_partition'));
+    IF to_regclass(partition_name) IS NOT NULL THEN
+        RETURN FALSE;
+    END IF;
+
+    -- Секция не может перекрыть строки default, а перенос потребовал бы
+    -- отсоединить default под эксклюзивной блокировкой. Такие строки пишутся
+    -- только в обход админки, поэтому год остается в default.
+    IF EXISTS (
+        SELECT 1 FROM events_archive_default WHERE datetime_start >= range_from AND datetime_start < range_to
+    ) THEN
+        RAISE WARNING 'events_archive_default has rows for %, partition % is not created', partition_year, partition_name;
+        RETURN FALSE;
+    END IF;
+
+    EXECUTE format('CREATE TABLE %I PARTITION OF events_archive (PRIMARY KEY (id)) FOR VALUES FROM (%L) TO (%L)',
+                   partition_name, range_from, range_to);
+    RETURN TRUE;
+END $$;
+
+SELECT ensure_events_partition(y)
+FROM generate_series(EXTRACT(YEAR FROM NOW())::int, EXTRACT(YEAR FROM NOW())::int + 2) AS y;
+
+-- Уникальные id событий: цель внешних ключей seats и bookings
+
+CREATE TABLE event_ids (
+    id BIGINT PRIMARY KEY,
+    datetime_start TIMESTAMP NOT NULL
+);
+
+-- Предстоящие события сразу: на них создаются места и бронирования. Остальные
+-- строки events_archive_legacy переносит фоновая задача (background_migrations),
+-- после чего проверяет внешние ключи.
+INSERT INTO event_ids (id, datetime_start)
+SELECT id, datetime_start FROM events_archive_legacy WHERE datetime_start >= CURRENT_DATE - 1;
+
+INSERT INTO background_migrations (name) VALUES ('event_ids')
+ON CONFLICT (name) DO NOTHING;
+
+-- Новое событие занимает id. Перенос строки в другую секцию (UPDATE
+-- datetime_start) Postgres выполняет как DELETE + INSERT, но
+-- trg_events_archive_move_id к этому моменту уже записал новую дату,
+-- поэтому занятый id с той же датой - та же строка, а дубликат с той же
+-- датой отклонит первичный ключ секции.
+CREATE OR REPLACE FUNCTION claim_event_id() RETURNS trigger AS $$
+BEGIN
+    INSERT INTO event_ids (id, datetime_start) VALUES (NEW.id, NEW.datetime_start)
+    ON CONFLICT (id) DO NOTHING;
+    IF FOUND THEN
+        -- Пока фоновая задача не заполнила event_ids, старые id проверяются по legacy
+        IF EXISTS (SELECT 1 FROM events_archive_legacy WHERE id = NEW.id) THEN
+            RAISE EXCEPTION 'duplicate key value violates unique constraint "event_ids_pkey"'
+                USING ERRCODE = 'unique_violation', DETAIL = format('Key (id)=(%s) already exists.', NEW.id);
+        END IF;
+    ELSIF NOT EXISTS (SELECT 1 FROM event_ids WHERE id = NEW.id AND datetime_start = NEW.datetime_start) THEN
+        RAISE EXCEPTION 'duplicate key value violates unique constraint "event_ids_pkey"'
+            USING ERRCODE = 'unique_violation', DETAIL = format('Key (id)=(%s) already exists.', NEW.id);
+    END IF;
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE OR REPLACE FUNCTION move_event_id() RETURNS trigger AS $$
+BEGIN
+    IF NEW.id <> OLD.id THEN
+        RAISE EXCEPTION 'id of event % cannot be changed', OLD.id
+            USING ERRCODE = 'feature_not_supported';
+    END IF;
+    UPDATE event_ids SET datetime_start = NEW.datetime_start WHERE id = OLD.id;
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+-- Удаление освобождает id; внешние ключи seats и bookings не дадут удалить
+-- событие со ссылками. При переносе между секциями дата в event_ids уже
+-- новая, и строка остается.
+CREATE OR REPLACE FUNCTION release_event_id() RETURNS trigger AS $$
+BEGIN
+    DELETE FROM event_ids WHERE id = OLD.id AND datetime_start = OLD.datetime_start;
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER trg_events_archive_claim_id
+    BEFORE INSERT ON events_archive
+    FOR EACH ROW EXECUTE FUNCTION claim_event_id();
+CREATE TRIGGER trg_events_archive_move_id
+    BEFORE UPDATE OF id, datetime_start ON events_archive
+    FOR EACH ROW WHEN (OLD.id <> NEW.id OR OLD.datetime_start <> NEW.datetime_start)
+    EXECUTE FUNCTION move_event_id();
+CREATE TRIGGER trg_events_archive_release_id
+    AFTER DELETE ON events_archive
+    FOR EACH ROW EXECUTE FUNCTION release_event_id();
+
+-- Ссылки переводятся на event_ids без проверки существующих строк (NOT VALID):
+-- новые строки проверяются сразу, старые - после заполнения event_ids
+ALTER TABLE seats DROP CONSTRAINT IF EXISTS seats_event_id_fkey;
+ALTER TABLE seats ADD CONSTRAINT seats_event_id_fkey
+    FOREIGN KEY (event_id) REFERENCES event_ids(id) NOT VALID;
+ALTER TABLE bookings DROP CONSTRAINT IF EXISTS bookings_event_id_fkey;
+ALTER TABLE bookings ADD CONSTRAINT bookings_event_id_fkey
+    FOREIGN KEY (event_id) REFERENCES event_ids(id) NOT VALID;
+
+CREATE OR REPLACE VIEW events_current AS
+SELECT * FROM events_archive
+WHERE datetime_start > CURRENT_DATE - interval '3 months';
+
+-- Предстоящие опубликованные события: небольшой горячий набор для списков и
+-- поиска. Обновляется фоновой задачей и после изменений через админку
+-- (REFRESH MATERIALIZED VIEW CONCURRENTLY требует уникальный индекс).
+-- Запас в день нужен, чтобы набор покрывал "сегодня" в любом часовом поясе.
+CREATE MATERIALIZED VIEW IF NOT EXISTS events_upcoming AS
+SELECT id, title, description, type, datetime_start, provider, venue_id, status, search_vector
+FROM events_archive
+WHERE status = 'published' AND datetime_start >= CURRENT_DATE - 1;
+
+CREATE UNIQUE INDEX IF NOT EXISTS idx_events_upcoming_id ON events_upcoming (id);
+CREATE INDEX IF NOT EXISTS idx_events_upcoming_datetime_id ON events_upcoming (datetime_start, id);
+CREATE INDEX IF NOT EXISTS idx_events_upcoming_search_vector ON events_upcoming USING GIN (search_vector);
+CREATE INDEX IF NOT EXISTS idx_events_upcoming_title_trgm ON events_upcoming USING GIN (title gin_trgm_ops);
@@ -1,7 +1,12 @@
 use serde::{Deserialize, Serialize};
-use sqlx::FromRow;
+use sqlx::{FromRow, Postgres, QueryBuilder};
 use chrono::NaiveDateTime;
 
+use super::status::EventStatus;
+
+/// Колонки `events_archive` в порядке полей `Event`.
+pub const EVENT_COLUMNS: &str = "id, title, description, type AS event_type, datetime_start, provider, venue_id, status";
+
 #[derive(Debug, Clone, FromRow, Serialize, Deserialize)]
 pub struct Event {
     pub id: i64,
@@ -11,4 +16,54 @@ pub struct Event {
     pub event_type: String,
     pub datetime_start: NaiveDateTime,
     pub provider: String,
-}
\ No newline at end of file
+    #[serde(default)]
+    pub venue_id: Option<i64>,
+    #[serde(default)]
+    pub status: EventStatus,
+}
+/// Фильтры публичного списка событий. Пустой `query` - список без полнотекстового поиска.
+#[derive(Debug, Clone, Default)]
+pub struct EventFilters {
+    pub query: String,
+    pub event_type: Option<String>,
+    pub provider: Option<String>,
+    /// Нижняя граница `datetime_start` (включительно); по умолчанию - текущий момент.
+    pub date_from: Option<NaiveDateTime>,
+    /// Верхняя граница `datetime_start` (не включительно).
+    pub date_to: Option<NaiveDateTime>,
+}
+
+impl EventFilters {
+    /// Откуда читать список. Предстоящие события - из небольшого
+    /// `events_upcoming` (обновляется фоновой задачей), остальное - из
+    /// секционированного `events_archive`. `events_upcoming` начинается за день
+    /// до даты своего обновления, поэтому покрывает любой `date_from` не раньше
+    /// сегодняшнего дня (UTC) при любом часовом поясе БД.
+    pub fn source_table(&self) -> &'static str {
+        let today = chrono::Utc::now().date_naive().and_time(chrono::NaiveTime::MIN);
+        match self.date_from {
+            Some(date_from) if date_from < today => "events_archive",
+            _ => "events_upcoming",
+        }
+    }
+
+    /// Добавляет к запросу условия `AND ...` для опубликованных событий и заданных
+    /// фильтров. Условие добавляется только для заданного фильтра, чтобы
+    /// планировщик видел конкретный запрос и выбирал подходящий индекс.
+    pub fn push_conditions(&self, query: &mut QueryBuilder<'_, Postgres>) {
+        query.push(" AND status = ").push_bind(EventStatus::Published);
+        match self.date_from {
+            Some(date_from) => query.push(" AND datetime_start >= ").push_bind(date_from),
+            None => query.push(" AND datetime_start >= NOW()"),
+        };
+        if let Some(date_to) = self.date_to {
+            query.push(" AND datetime_start < ").push_bind(date_to);
+        }
+        if let Some(event_type) = &self.event_type {
+            query.push(" AND type = ").push_bind(event_type.clone());
+        }
+        if let Some(provider) = &self.provider {
+            query.push(" AND provider = ").push_bind(provider.clone());
+        }
+    }
+}
@@ -2,7 +2,12 @@ pub mod user;
 pub mod event;
 pub mod seat;
 pub mod booking;
+pub mod venue;
+pub mod status;
+pub mod state_machine;
 
 pub use user::User;
-pub use event::Event;
-pub use seat::Seat;
\ No newline at end of file
+pub use event::{Event, EventFilters};
+pub use seat::Seat;
+pub use venue::{SeatPosition, Section, Venue};
+pub use status::{BookingStatus, EventStatus, PaymentStatus, SeatStatus};
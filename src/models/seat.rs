@@ -1,13 +1,18 @@
 use serde::{Deserialize, Serialize};
 use sqlx::FromRow;
 
+use super::status::SeatStatus;
+
 #[derive(Debug, Clone, FromRow, Serialize, Deserialize)]
 pub struct Seat {
     pub id: i64,
     pub event_id: i64,
     pub row: i32,
     pub number: i32,
-    pub status: String,
+    /// Секция зала; `None` - событие без схемы зала.
+    #[serde(default)]
+    pub section_id: Option<i64>,
+    pub status: SeatStatus,
     pub booking_id: Option<i64>,
     pub category: Option<String>,
     pub price: Option<f64>,
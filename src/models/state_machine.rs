@@ -0,0 +1,408 @@
+//! state_machine.rs
+//!
+//! Допустимые переходы статусов мест, бронирований, платежей и событий.
+//!
+//! Все изменения статусов в контроллерах и сервисах проходят через функции
+//! этого модуля. Переход выполняется одним UPDATE с условием
+//! `status = ANY(<разрешенные исходные статусы>)`, поэтому гонка двух запросов
+//! не может провести запись по недопустимому пути: проигравший получает
+//! `TransitionError::Illegal` с фактическим текущим статусом.
+
+use sqlx::{PgConnection, Postgres};
+use std::fmt;
+
+use super::status::{BookingStatus, EventStatus, PaymentStatus, SeatStatus};
+
+/// Ошибка перехода статуса.
+#[derive(Debug, thiserror::Error)]
+pub enum TransitionError {
+    #[error("illegal {entity} status transition {from} -> {to}")]
+    Illegal {
+        entity: &'static str,
+        from: String,
+        to: String,
+    },
+    #[error("{entity} {id} not found")]
+    NotFound { entity: &'static str, id: String },
+    #[error("status transition query failed: {0}")]
+    Database(#[from] sqlx::Error),
+}
+
+/// Таблица переходов для статуса.
+pub trait StatusMachine: Copy + Eq + fmt::Display + 'static {
+    const ENTITY: &'static str;
+
+    /// Все значения статуса.
+    fn all() -> &'static [Self];
+
+    /// Разрешен ли переход `self -> to`.
+    fn can_transition_to(self, to: Self) -> bool;
+
+    /// Проверяет переход `self -> to`.
+    fn transition_to(self, to: Self) -> Result<Self, TransitionError> {
+        if self.can_transition_to(to) {
+            Ok(to)
+        } else {
+            Err(TransitionError::Illegal {
+                entity: Self::ENTITY,
+                from: self.to_string(),
+                to: to.to_string(),
+            })
+        }
+    }
+
+    /// Статусы, из которых разрешен переход в `to`.
+    fn sources(to: Self) -> Vec<Self> {
+        Self::all()
+            .iter()
+            .copied()
+            .filter(|from| from.can_transition_to(to))
+            .collect()
+    }
+}
+
+impl StatusMachine for SeatStatus {
+    const ENTITY: &'static str = "seat";
+
+    fn all() -> &'static [Self] {
+        Self::ALL
+    }
+
+    /// `SELECTED` живет только в Redis и в переходах БД не участвует.
+    fn can_transition_to(self, to: Self) -> bool {
+        use SeatStatus::*;
+        matches!(
+            (self, to),
+            (Free, Reserved) | (Reserved, Free) | (Reserved, Sold)
+        )
+    }
+}
+
+impl StatusMachine for BookingStatus {
+    const ENTITY: &'static str = "booking";
+
+    fn all() -> &'static [Self] {
+        Self::ALL
+    }
+
+    /// Повторная инициализация оплаты (`pending_payment -> pending_payment`) разрешена:
+    /// пользователь может начать новый платеж, если предыдущий не дошел до шлюза.
+    fn can_transition_to(self, to: Self) -> bool {
+        use BookingStatus::*;
+        matches!(
+            (self, to),
+            (Created, PendingPayment)
+                | (Created, Cancelled)
+                | (PendingPayment, PendingPayment)
+                | (PendingPayment, Paid)
+                | (PendingPayment, Cancelled)
+        )
+    }
+}
+
+impl StatusMachine for PaymentStatus {
+    const ENTITY: &'static str = "payment";
+
+    fn all() -> &'static [Self] {
+        Self::ALL
+    }
+
+    /// Завершенные, неудавшиеся и просроченные платежи не меняются.
+    fn can_transition_to(self, to: Self) -> bool {
+        use PaymentStatus::*;
+        matches!((self, to), (Pending, Completed) | (Pending, Failed) | (Pending, Expired))
+    }
+}
+
+impl StatusMachine for EventStatus {
+    const ENTITY: &'static str = "event";
+
+    fn all() -> &'static [Self] {
+        Self::ALL
+    }
+
+    /// Публикацию можно снимать и возвращать; отмена окончательна.
+    fn can_transition_to(self, to: Self) -> bool {
+        use EventStatus::*;
+        matches!(
+            (self, to),
+            (Published, Unpublished)
+                | (Unpublished, Published)
+                | (Published, Cancelled)
+                | (Unpublished, Cancelled)
+        )
+    }
+}
+
+/// Переводит событие в статус `to`.
+pub async fn transition_event(
+    conn: &mut PgConnection,
+    event_id: i64,
+    to: EventStatus,
+) -> Result<(), TransitionError> {
+    let affected = sqlx::query("UPDATE events_archive SET status = $1 WHERE id = $2 AND status = ANY($3)")
+        .bind(to)
+        .bind(event_id)
+        .bind(EventStatus::sources(to))
+        .execute(&mut *conn)
+        .await?
+        .rows_affected();
+
+    if affected > 0 {
+        return Ok(());
+    }
+
+    let current: Option<EventStatus> = sqlx::query_scalar("SELECT status FROM events_archive WHERE id = $1")
+        .bind(event_id)
+        .fetch_optional(&mut *conn)
+        .await?;
+    Err(rejected(current, to, event_id))
+}
+
+/// Переводит бронирование в статус `to`.
+pub async fn transition_booking(
+    conn: &mut PgConnection,
+    booking_id: i64,
+    to: BookingStatus,
+) -> Result<(), TransitionError> {
+    let affected = sqlx::query("UPDATE bookings SET status = $1 WHERE id = $2 AND status = ANY($3)")
+        .bind(to)
+        .bind(booking_id)
+        .bind(BookingStatus::sources(to))
+        .execute(&mut *conn)
+        .await?
+        .rows_affected();
+
+    if affected > 0 {
+        return Ok(());
+    }
+
+    let current: Option<BookingStatus> = sqlx::query_scalar("SELECT status FROM bookings WHERE id = $1")
+        .bind(booking_id)
+        .fetch_optional(&mut *conn)
+        .await?;
+    Err(rejected(current, to, booking_id))
+}
+
+/// Переводит платеж с идентификатором шлюза `transaction_id` в статус `to`.
+pub async fn transition_payment(
+    conn: &mut PgConnection,
+    transaction_id: &str,
+    to: PaymentStatus,
+) -> Result<(), TransitionError> {
+    let affected = sqlx::query(
+        "UPDATE payment_transactions SET status = $1 WHERE transaction_id = $2 AND status = ANY($3)"
+    )
+    .bind(to)
+    .bind(transaction_id)
+    .bind(PaymentStatus::sources(to))
+    .execute(&mut *conn)
+    .await?
+    .rows_affected();
+
+    if affected > 0 {
+        return Ok(());
+    }
+
+    let current: Option<PaymentStatus> = sqlx::query_scalar(
+        "SELECT status FROM payment_transactions WHERE transaction_id = $1 LIMIT 1"
+    )
+    .bind(transaction_id)
+    .fetch_optional(&mut *conn)
+    .await?;
+    Err(rejected(current, to, transaction_id))
+}
+
+/// Переводит одно место в статус `to` и привязывает его к брони `booking_id`
+/// (`None` отвязывает место).
+pub async fn transition_seat(
+    conn: &mut PgConnection,
+    seat_id: i64,
+    to: SeatStatus,
+    booking_id: Option<i64>,
+) -> Result<(), TransitionError> {
+    let affected = sqlx::query(
+        "UPDATE seats SET status = $1, booking_id = $2 WHERE id = $3 AND status = ANY($4)"
+    )
+    .bind(to)
+    .bind(booking_id)
+    .bind(seat_id)
+    .bind(SeatStatus::sources(to))
+    .execute(&mut *conn)
+    .await?
+    .rows_affected();
+
+    if affected > 0 {
+        return Ok(());
+    }
+
+    let current: Option<SeatStatus> = sqlx::query_scalar("SELECT status FROM seats WHERE id = $1")
+        .bind(seat_id)
+        .fetch_optional(&mut *conn)
+        .await?;
+    Err(rejected(current, to, seat_id))
+}
+
+/// Переводит места из списка в статус `to` и привязывает их к брони `booking_id`
+/// (`None` отвязывает места). Места, для которых переход запрещен, не меняются.
+/// Возвращает ID и событие переведенных мест.
+pub async fn transition_seats(
+    conn: &mut PgConnection,
+    seat_ids: &[i64],
+    to: SeatStatus,
+    booking_id: Option<i64>,
+) -> Result<Vec<(i64, i64)>, TransitionError> {
+    let seats = sqlx::query_as::<Postgres, (i64, i64)>(
+        r#"
+        UPDATE seats
+        SET status = $1, booking_id = $2
+        WHERE id = ANY($3) AND status = ANY($4)
+        RETURNING id, event_id
+        "#
+    )
+    .bind(to)
+    .bind(booking_id)
+    .bind(seat_ids)
+    .bind(SeatStatus::sources(to))
+    .fetch_all(&mut *conn)
+    .await?;
+    Ok(seats)
+}
+
+/// Переводит в статус `to` все места брони, для которых это разрешено, и возвращает их ID.
+/// Места, освобождаемые в `FREE`, отвязываются от брони.
+pub async fn transition_booking_seats(
+    conn: &mut PgConnection,
+    booking_id: i64,
+    to: SeatStatus,
+) -> Result<Vec<i64>, TransitionError> {
+    let seats = sqlx::query_scalar::<Postgres, i64>(
+        r#"
+        UPDATE seats
+        SET status = $1,
+            booking_id = CASE WHEN $2 THEN NULL ELSE booking_id END
+        WHERE booking_id = $3 AND status = ANY($4)
+        RETURNING id
+        "#
+    )
+    .bind(to)
+    .bind(to == SeatStatus::Free)
+    .bind(booking_id)
+    .bind(SeatStatus::sources(to))
+    .fetch_all(&mut *conn)
+    .await?;
+    Ok(seats)
+}
+
+/// Ошибка для перехода, который не затронул ни одной строки.
+fn rejected<S: StatusMachine>(current: Option<S>, to: S, id: impl fmt::Display) -> TransitionError {
+    match current {
+        Some(from) => TransitionError::Illegal {
+            entity: S::ENTITY,
+            from: from.to_string(),
+            to: to.to_string(),
+        },
+        None => TransitionError::NotFound {
+            entity: S::ENTITY,
+            id: id.to_string(),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Проверяет, что разрешены ровно переходы `allowed`.
+    fn assert_transitions<S: StatusMachine + fmt::Debug>(allowed: &[(S, S)]) {
+        for &from in S::all() {
+            for &to in S::all() {
+                assert_eq!(
+                    from.can_transition_to(to),
+                    allowed.contains(&(from, to)),
+                    "{} {} -> {}",
+                    S::ENTITY,
+                    from,
+                    to
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn seat_transitions() {
+        use SeatStatus::*;
+        assert_transitions(&[(Free, Reserved), (Reserved, Free), (Reserved, Sold)]);
+    }
+
+    #[test]
+    fn booking_transitions() {
+        use BookingStatus::*;
+        assert_transitions(&[
+            (Created, PendingPayment),
+            (Created, Cancelled),
+            (PendingPayment, PendingPayment),
+            (PendingPayment, Paid),
+            (PendingPayment, Cancelled),
+        ]);
+    }
+
+    #[test]
+    fn payment_transitions() {
+        use PaymentStatus::*;
+        assert_transitions(&[(Pending, Completed), (Pending, Failed), (Pending, Expired)]);
+    }
+
+    #[test]
+    fn event_transitions() {
+        use EventStatus::*;
+        assert_transitions(&[
+            (Published, Unpublished),
+            (Unpublished, Published),
+            (Published, Cancelled),
+            (Unpublished, Cancelled),
+        ]);
+    }
+
+    #[test]
+    fn selected_seat_is_never_a_source() {
+        for &to in SeatStatus::ALL {
+            assert!(!SeatStatus::sources(to).contains(&SeatStatus::Selected));
+        }
+    }
+
+    #[test]
+    fn sources_list_allowed_origins() {
+        assert_eq!(SeatStatus::sources(SeatStatus::Free), vec![SeatStatus::Reserved]);
+        assert_eq!(SeatStatus::sources(SeatStatus::Reserved), vec![SeatStatus::Free]);
+        assert_eq!(
+            BookingStatus::sources(BookingStatus::Cancelled),
+            vec![BookingStatus::Created, BookingStatus::PendingPayment]
+        );
+        assert!(PaymentStatus::sources(PaymentStatus::Pending).is_empty());
+    }
+
+    #[test]
+    fn transition_to_reports_illegal_transition() {
+        assert_eq!(SeatStatus::Free.transition_to(SeatStatus::Reserved).unwrap(), SeatStatus::Reserved);
+
+        match BookingStatus::Paid.transition_to(BookingStatus::Cancelled) {
+            Err(TransitionError::Illegal { entity, from, to }) => {
+                assert_eq!((entity, from.as_str(), to.as_str()), ("booking", "paid", "cancelled"));
+            }
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn rejected_distinguishes_missing_rows() {
+        assert!(matches!(
+            rejected(Some(SeatStatus::Sold), SeatStatus::Free, 7),
+            TransitionError::Illegal { .. }
+        ));
+        match rejected(None::<SeatStatus>, SeatStatus::Free, 7) {
+            TransitionError::NotFound { entity, id } => assert_eq!((entity, id.as_str()), ("seat", "7")),
+            other => panic!("unexpected error: {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,234 @@
+//! status.rs
+//!
+//! Статусы мест, бронирований, платежей и событий.
+//!
+//! В БД статусы хранятся как текст (VARCHAR) и ограничены CHECK-ограничениями
+//! из миграций 002 и 004. Enum'ы кодируются и декодируются sqlx как текст, поэтому их
+//! можно передавать в `bind` как по одному, так и массивом (`status = ANY($1)`).
+
+use serde::{Deserialize, Serialize};
+use sqlx::{
+    encode::IsNull,
+    error::BoxDynError,
+    postgres::{PgArgumentBuffer, PgHasArrayType, PgTypeInfo, PgValueRef},
+    Decode, Encode, Postgres, Type,
+};
+use std::{fmt, str::FromStr};
+
+/// Значение статуса, которого нет в словаре.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error("unknown {entity} status '{value}'")]
+pub struct UnknownStatus {
+    pub entity: &'static str,
+    pub value: String,
+}
+
+/// Объявляет текстовый статус: enum, строковое представление, serde и маппинг sqlx.
+macro_rules! text_status {
+    (
+        $(#[$meta:meta])*
+        pub enum $name:ident ($entity:literal) {
+            $( $(#[$vmeta:meta])* $variant:ident => $value:literal, )+
+        }
+    ) => {
+        $(#[$meta])*
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+        pub enum $name {
+            $( $(#[$vmeta])* #[serde(rename = $value)] $variant, )+
+        }
+
+        impl $name {
+            /// Все значения статуса в порядке объявления.
+            pub const ALL: &'static [Self] = &[$(Self::$variant),+];
+
+            pub fn as_str(self) -> &'static str {
+                match self {
+                    $(Self::$variant => $value,)+
+                }
+            }
+        }
+
+        impl fmt::Display for $name {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str(self.as_str())
+            }
+        }
+
+        impl FromStr for $name {
+            type Err = UnknownStatus;
+
+            fn from_str(s: &str) -> Result<Self, Self::Err> {
+                match s {
+                    $($value => Ok(Self::$variant),)+
+                    _ => Err(UnknownStatus { entity: $entity, value: s.to_string() }),
+                }
+            }
+        }
+
+        impl Type<Postgres> for $name {
+            fn type_info() -> PgTypeInfo {
+                <str as Type<Postgres>>::type_info()
+            }
+
+            fn compatible(ty: &PgTypeInfo) -> bool {
+                <str as Type<Postgres>>::compatible(ty)
+            }
+        }
+
+        impl PgHasArrayType for $name {
+            fn array_type_info() -> PgTypeInfo {
+                <&str as PgHasArrayType>::array_type_info()
+            }
+
+            fn array_compatible(ty: &PgTypeInfo) -> bool {
+                <&str as PgHasArrayType>::array_compatible(ty)
+            }
+        }
+
+        impl Encode<'_, Postgres> for $name {
+            fn encode_by_ref(&self, buf: &mut PgArgumentBuffer) -> Result<IsNull, BoxDynError> {
+                <&str as Encode<Postgres>>::encode_by_ref(&self.as_str(), buf)
+            }
+        }
+
+        impl<'r> Decode<'r, Postgres> for $name {
+            fn decode(value: PgValueRef<'r>) -> Result<Self, BoxDynError> {
+                let value = <&str as Decode<Postgres>>::decode(value)?;
+                Ok(value.parse()?)
+            }
+        }
+    };
+}
+
+text_status! {
+    /// Статус места.
+    pub enum SeatStatus ("seat") {
+        Free => "FREE",
+        /// Место удерживается в Redis, но еще не записано в бронь. В БД не хранится,
+        /// накладывается на `FREE` при отдаче списка мест из кэша.
+        Selected => "SELECTED",
+        Reserved => "RESERVED",
+        Sold => "SOLD",
+    }
+}
+
+text_status! {
+    /// Статус бронирования.
+    pub enum BookingStatus ("booking") {
+        Created => "created",
+        PendingPayment => "pending_payment",
+        Paid => "paid",
+        Cancelled => "cancelled",
+    }
+}
+
+text_status! {
+    /// Статус платежной транзакции.
+    pub enum PaymentStatus ("payment") {
+        Pending => "pending",
+        Completed => "completed",
+        Failed => "failed",
+        Expired => "expired",
+    }
+}
+
+text_status! {
+    /// Статус публикации события.
+    #[derive(Default)]
+    pub enum EventStatus ("event") {
+        /// Событие видно в публичных списках, на него можно бронировать.
+        #[default]
+        Published => "published",
+        /// Событие скрыто из публичных списков.
+        Unpublished => "unpublished",
+        Cancelled => "cancelled",
+    }
+}
+
+impl SeatStatus {
+    /// Статусы, которые хранятся в колонке `seats.status`.
+    pub const PERSISTED: &'static [Self] = &[Self::Free, Self::Reserved, Self::Sold];
+
+    pub fn is_persisted(self) -> bool {
+        self != Self::Selected
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Текст, который `Encode` пишет в буфер параметра.
+    fn encoded<T: for<'q> Encode<'q, Postgres>>(value: T) -> String {
+        let mut buf = PgArgumentBuffer::default();
+        let is_null = value.encode_by_ref(&mut buf).unwrap();
+        assert!(matches!(is_null, IsNull::No));
+        String::from_utf8(buf.to_vec()).unwrap()
+    }
+
+    /// `Decode` разбирает текст колонки через `FromStr`, поэтому круг
+    /// encode -> parse повторяет запись и чтение из БД.
+    fn assert_round_trip<T>(all: &[T])
+    where
+        T: Copy + fmt::Debug + PartialEq + FromStr<Err = UnknownStatus> + Serialize + for<'de> Deserialize<'de>,
+        T: for<'q> Encode<'q, Postgres> + fmt::Display,
+    {
+        for &status in all {
+            let text = encoded(status);
+            assert_eq!(text, status.to_string());
+            assert_eq!(text.parse::<T>(), Ok(status));
+
+            let json = serde_json::to_string(&status).unwrap();
+            assert_eq!(json, format!("\"{}\"", text));
+            assert_eq!(serde_json::from_str::<T>(&json).unwrap(), status);
+        }
+    }
+
+    #[test]
+    fn statuses_round_trip() {
+        assert_round_trip(SeatStatus::ALL);
+        assert_round_trip(BookingStatus::ALL);
+        assert_round_trip(PaymentStatus::ALL);
+        assert_round_trip(EventStatus::ALL);
+    }
+
+    #[test]
+    fn stored_values_match_check_constraints() {
+        let values = |all: &[SeatStatus]| all.iter().map(|s| s.as_str()).collect::<Vec<_>>();
+        assert_eq!(values(SeatStatus::ALL), ["FREE", "SELECTED", "RESERVED", "SOLD"]);
+        assert_eq!(values(SeatStatus::PERSISTED), ["FREE", "RESERVED", "SOLD"]);
+        assert_eq!(
+            BookingStatus::ALL.iter().map(|s| s.as_str()).collect::<Vec<_>>(),
+            ["created", "pending_payment", "paid", "cancelled"]
+        );
+        assert_eq!(
+            PaymentStatus::ALL.iter().map(|s| s.as_str()).collect::<Vec<_>>(),
+            ["pending", "completed", "failed", "expired"]
+        );
+        assert_eq!(
+            EventStatus::ALL.iter().map(|s| s.as_str()).collect::<Vec<_>>(),
+            ["published", "unpublished", "cancelled"]
+        );
+    }
+
+    #[test]
+    fn unknown_values_are_rejected() {
+        // Статусы чувствительны к регистру, как и CHECK-ограничения в БД.
+        assert_eq!(
+            "free".parse::<SeatStatus>(),
+            Err(UnknownStatus { entity: "seat", value: "free".to_string() })
+        );
+        assert_eq!(
+            "PAID".parse::<BookingStatus>(),
+            Err(UnknownStatus { entity: "booking", value: "PAID".to_string() })
+        );
+        assert!("".parse::<PaymentStatus>().is_err());
+        assert!(serde_json::from_str::<EventStatus>("\"draft\"").is_err());
+    }
+
+    #[test]
+    fn selected_is_not_persisted() {
+        assert!(!SeatStatus::Selected.is_persisted());
+        assert!(SeatStatus::PERSISTED.iter().all(|s| s.is_persisted()));
+    }
+}
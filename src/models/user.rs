@@ -1,6 +1,8 @@
 use serde::Serialize;
 use sqlx::FromRow;
 use chrono::{NaiveDate, NaiveDateTime};
+use hmac::{Hmac, Mac};
+use sha2::{Sha256, Digest};
 
 #[derive(Debug, Clone, FromRow, Serialize)]
 pub struct User {
@@ -16,6 +18,98 @@ pub struct User {
     pub last_logged_in: NaiveDateTime,
 }
 
+/// Алгоритм, которым захеширован пароль в колонке `users.password_hash`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PasswordScheme {
+    /// bcrypt (`$2a$`, `$2b$`, `$2y$`).
+    Bcrypt,
+    /// SHA-256 в виде hex-строки из 64 символов (формат исходного датасета).
+    Sha256Hex,
+    /// Хеша нет - у пользователя есть только `password_plain`.
+    Missing,
+}
+
+impl PasswordScheme {
+    /// Определяет алгоритм по формату сохраненного хеша.
+    pub fn detect(password_hash: &str) -> Self {
+        let hash = password_hash.trim();
+        if hash.starts_with("$2a$") || hash.starts_with("$2b$") || hash.starts_with("$2y$") {
+            PasswordScheme::Bcrypt
+        } else if hash.len() == 64 && hash.chars().all(|c| c.is_ascii_hexdigit()) {
+            PasswordScheme::Sha256Hex
+        } else {
+            PasswordScheme::Missing
+        }
+    }
+}
+
+/// Результат проверки пароля.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PasswordCheck {
+    /// Пароль неверный.
+    Invalid,
+    /// Пароль верный, хеш в порядке.
+    Valid,
+    /// Пароль верный, но проверен по `password_plain` - хеш нужно записать.
+    ValidNeedsUpgrade,
+}
+
+/// Проверяет пароль против `password_hash`, а если хеша нет - против `password_plain`.
+///
+/// bcrypt-проверка дорогая по CPU, поэтому из async-кода ее нужно вызывать
+/// через `spawn_blocking`.
+pub fn check_password(password_hash: &str, password_plain: Option<&str>, password: &str) -> PasswordCheck {
+    let hash = password_hash.trim();
+    let valid = match PasswordScheme::detect(hash) {
+        PasswordScheme::Bcrypt => bcrypt::verify(password, hash).unwrap_or(false),
+        PasswordScheme::Sha256Hex => constant_time_eq(
+            sha256_hex(password).as_bytes(),
+            hash.to_ascii_lowercase().as_bytes(),
+        ),
+        PasswordScheme::Missing => {
+            return match password_plain {
+                Some(plain) if constant_time_eq(plain.as_bytes(), password.as_bytes()) => {
+                    PasswordCheck::ValidNeedsUpgrade
+                }
+                _ => PasswordCheck::Invalid,
+            };
+        }
+    };
+
+    if valid { PasswordCheck::Valid } else { PasswordCheck::Invalid }
+}
+
+/// Хеширует пароль для записи в `users.password_hash` (bcrypt, помещается в VARCHAR(64)).
+pub fn hash_password(password: &str) -> Result<String, bcrypt::BcryptError> {
+    bcrypt::hash(password, bcrypt::DEFAULT_COST)
+}
+
+/// SHA-256 от строки в виде hex.
+pub fn sha256_hex(value: &str) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(value.as_bytes());
+    format!("{:x}", hasher.finalize())
+}
+
+/// HMAC-SHA256 от `email:password` в виде hex - отпечаток учетных данных
+/// для ключа кеша авторизации. Без `secret` по отпечатку нельзя подобрать
+/// пароль перебором, даже получив дамп Redis.
+pub fn credentials_fingerprint(secret: &[u8], email: &str, password: &str) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret).expect("HMAC accepts keys of any length");
+    mac.update(email.as_bytes());
+    mac.update(b":");
+    mac.update(password.as_bytes());
+    format!("{:x}", mac.finalize().into_bytes())
+}
+
+/// Сравнение без раннего выхода, чтобы не давать тайминг-оракул.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
 impl User {
     // Найти пользователя по email
     pub async fn find_by_email(email: &str, db: &crate::database::Database) -> Result<Option<User>, sqlx::Error> {
@@ -26,14 +120,92 @@ impl User {
         .fetch_optional(&db.pool)
         .await
     }
-    
-    // Проверить пароль (для хакатона используем plain password)
+
+    // Проверить пароль по password_hash (bcrypt или sha256), с fallback на password_plain
     pub fn verify_password(&self, password: &str) -> bool {
-        if let Some(ref plain) = self.password_plain {
-            plain == password
-        } else {
-            // В продакшене здесь был бы bcrypt
-            false
+        check_password(&self.password_hash, self.password_plain.as_deref(), password) != PasswordCheck::Invalid
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// SHA-256 от "secret".
+    const SECRET_SHA256: &str = "2bb80d537b1da3e38bd30361aa855686bde0eacd7162fef6a25fe97bf527a25b";
+
+    fn bcrypt_hash(password: &str) -> String {
+        // Минимальная стоимость: тест проверяет формат, а не стойкость
+        bcrypt::hash(password, 4).unwrap()
+    }
+
+    #[test]
+    fn detects_scheme_by_hash_format() {
+        assert_eq!(PasswordScheme::detect(&bcrypt_hash("secret")), PasswordScheme::Bcrypt);
+        for prefix in ["$2a$", "$2b$", "$2y$"] {
+            assert_eq!(PasswordScheme::detect(&format!("{}10$abc", prefix)), PasswordScheme::Bcrypt);
         }
+        assert_eq!(PasswordScheme::detect(SECRET_SHA256), PasswordScheme::Sha256Hex);
+        assert_eq!(PasswordScheme::detect(&SECRET_SHA256.to_uppercase()), PasswordScheme::Sha256Hex);
+        assert_eq!(PasswordScheme::detect(&format!(" {}\n", SECRET_SHA256)), PasswordScheme::Sha256Hex);
+
+        assert_eq!(PasswordScheme::detect(""), PasswordScheme::Missing);
+        assert_eq!(PasswordScheme::detect(&SECRET_SHA256[..63]), PasswordScheme::Missing);
+        assert_eq!(PasswordScheme::detect(&format!("{}g", &SECRET_SHA256[..63])), PasswordScheme::Missing);
+        assert_eq!(PasswordScheme::detect("$1$md5crypt"), PasswordScheme::Missing);
+    }
+
+    #[test]
+    fn sha256_hex_matches_known_digest() {
+        assert_eq!(sha256_hex("secret"), SECRET_SHA256);
+        assert_eq!(
+            sha256_hex(""),
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+    }
+
+    #[test]
+    fn checks_password_against_each_scheme() {
+        let bcrypt = bcrypt_hash("secret");
+        assert_eq!(check_password(&bcrypt, None, "secret"), PasswordCheck::Valid);
+        assert_eq!(check_password(SECRET_SHA256, None, "secret"), PasswordCheck::Valid);
+        assert_eq!(check_password(&SECRET_SHA256.to_uppercase(), None, "secret"), PasswordCheck::Valid);
+        assert_eq!(check_password("", Some("secret"), "secret"), PasswordCheck::ValidNeedsUpgrade);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn rejects_wrong_password() {
+        let bcrypt = bcrypt_hash("secret");
+        assert_eq!(check_password(&bcrypt, None, "Secret"), PasswordCheck::Invalid);
+        assert_eq!(check_password(SECRET_SHA256, None, "secret "), PasswordCheck::Invalid);
+        assert_eq!(check_password("", Some("secret"), "secre"), PasswordCheck::Invalid);
+        assert_eq!(check_password("", None, ""), PasswordCheck::Invalid);
+        // При наличии хеша password_plain не используется
+        assert_eq!(check_password(SECRET_SHA256, Some("other"), "other"), PasswordCheck::Invalid);
+        // Испорченный bcrypt-хеш не проходит и не паникует
+        assert_eq!(check_password("$2b$10$broken", None, "secret"), PasswordCheck::Invalid);
+    }
+
+    #[test]
+    fn fingerprint_depends_on_secret_and_credentials() {
+        let base = credentials_fingerprint(b"server-secret", "user@example.com", "secret");
+        assert_eq!(base.len(), 64);
+        assert_eq!(base, credentials_fingerprint(b"server-secret", "user@example.com", "secret"));
+
+        assert_ne!(base, credentials_fingerprint(b"other-secret", "user@example.com", "secret"));
+        assert_ne!(base, credentials_fingerprint(b"server-secret", "user@example.com", "secret2"));
+        assert_ne!(base, credentials_fingerprint(b"server-secret", "other@example.com", "secret"));
+        // Отпечаток не совпадает с обычным хешем пароля
+        assert_ne!(base, sha256_hex("user@example.com:secret"));
+    }
+
+    #[test]
+    fn constant_time_eq_compares_lengths_and_bytes() {
+        assert!(constant_time_eq(b"", b""));
+        assert!(constant_time_eq(b"abc", b"abc"));
+        assert!(!constant_time_eq(b"abc", b"abd"));
+        assert!(!constant_time_eq(b"abc", b"abcd"));
+        assert!(!constant_time_eq(b"abcd", b"abc"));
+        assert!(!constant_time_eq(b"", b"a"));
+    }
+}
@@ -0,0 +1,31 @@
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+
+/// Площадка, на которой проходит событие.
+#[derive(Debug, Clone, FromRow, Serialize, Deserialize)]
+pub struct Venue {
+    pub id: i64,
+    pub name: String,
+    pub address: Option<String>,
+    /// Размер холста схемы зала.
+    pub width: i32,
+    pub height: i32,
+}
+
+/// Секция зала (партер, балкон, ложа).
+#[derive(Debug, Clone, FromRow, Serialize, Deserialize)]
+pub struct Section {
+    pub id: i64,
+    pub venue_id: i64,
+    pub name: String,
+    pub tier: Option<String>,
+    pub sort_order: i32,
+}
+
+/// Координаты места события на схеме зала.
+#[derive(Debug, Clone, FromRow, Serialize, Deserialize)]
+pub struct SeatPosition {
+    pub seat_id: i64,
+    pub x: f32,
+    pub y: f32,
+}
@@ -1,24 +1,106 @@
 use serde::{Deserialize, Serialize};
-use sqlx::PgPool;
+use std::sync::Arc;
+use sqlx::{types::Json, PgConnection, PgPool, Postgres, QueryBuilder, Transaction};
 use tracing::info;
 
+use crate::{config::SearchConfig, cursor::EventCursor, models::EventFilters};
+
 /// Клиент для поиска
 #[derive(Clone)]
 pub struct SearchClient {
     pool: PgPool,
+    config: Arc<SearchConfig>,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum SearchError {
+    #[error("search check query failed: {0}")]
+    Database(#[from] sqlx::Error),
+    #[error("SEARCH_LANGUAGES is empty")]
+    NoLanguages,
+    #[error("text search configuration '{0}' from SEARCH_LANGUAGES does not exist")]
+    UnknownLanguage(String),
+    #[error("SEARCH_HIGHLIGHT_MIN_WORDS must be > 0 and less than SEARCH_HIGHLIGHT_MAX_WORDS")]
+    InvalidHighlightLength,
 }
 
 #[derive(Debug, Serialize, Deserialize, sqlx::FromRow, Clone)]
 pub struct EventSearchResult {
     pub id: i64,
     pub title: String,
+    pub event_type: String,
+    pub provider: String,
     pub datetime_start: chrono::NaiveDateTime,
     pub rank: Option<f32>,
+    /// Фрагмент `title` и `description` с подсветкой совпадений (`ts_headline`).
+    /// Это HTML: текст события экранирован, как есть вставлены только маркеры.
+    /// Есть только в полнотекстовом поиске с `highlight`.
+    #[sqlx(default)]
+    pub snippet: Option<String>,
+}
+
+/// Текст для `snippet`: `title` и `description` с экранированными символами HTML.
+/// `ts_headline` возвращает исходный текст как есть и добавляет только маркеры
+/// подсветки, поэтому без экранирования разметка из описания события попала бы
+/// в ответ. Сущности вроде `&amp;` парсер не разрывает.
+const SNIPPET_SOURCE: &str = r#"replace(replace(replace(replace(replace(
+    concat_ws(' ', title, description),
+    '&', '&amp;'), '<', '&lt;'), '>', '&gt;'), '"', '&quot;'), '''', '&#39;')"#;
+
+/// Сколько значений отдается в фасетах по типу и организатору.
+const FACET_LIMIT: i64 = 20;
+
+/// Сколько месяцев отдается в фасете по дате (два года вперед).
+const MONTH_FACET_LIMIT: i64 = 24;
+
+/// Значение фасета и число событий с ним.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct FacetCount {
+    pub value: String,
+    pub count: i64,
+}
+
+/// Итоги поиска: общее число совпадений и фасеты для фильтров.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SearchFacets {
+    pub total: i64,
+    /// `total` - нижняя оценка: совпадений больше `SEARCH_COUNT_THRESHOLD`.
+    pub total_estimated: bool,
+    #[serde(rename = "type")]
+    pub by_type: Vec<FacetCount>,
+    #[serde(rename = "provider")]
+    pub by_provider: Vec<FacetCount>,
+    /// Месяцы `datetime_start` в формате `YYYY-MM`.
+    #[serde(rename = "month")]
+    pub by_month: Vec<FacetCount>,
+}
+
+/// Подзапрос фасета по выражению `expr` над `hits`: JSON-массив `{value, count}`.
+fn facet_sql(expr: &str, order: &str, limit: i64) -> String {
+    format!(
+        "COALESCE((SELECT json_agg(json_build_object('value', value, 'count', count) ORDER BY {order})
+                   FROM (SELECT {expr} AS value, count(*) AS count FROM hits
+                         GROUP BY 1 ORDER BY {order} LIMIT {limit}) f), '[]'::json)"
+    )
+}
+
+/// Подсказка автодополнения: название и ближайшее событие с ним.
+#[derive(Debug, Serialize, Deserialize, sqlx::FromRow, Clone)]
+pub struct EventSuggestion {
+    pub id: i64,
+    pub title: String,
+    pub datetime_start: chrono::NaiveDateTime,
+    pub score: f32,
 }
 
 impl SearchClient {
-    pub fn new(pool: PgPool) -> Self {
-        Self { pool }
+    pub fn new(pool: PgPool, config: SearchConfig) -> Self {
+        Self { pool, config: Arc::new(config) }
+    }
+
+    /// Тот же клиент поверх другого пула, например реплики из `AppState::read_pool`.
+    pub fn with_pool(&self, pool: &PgPool) -> Self {
+        Self { pool: pool.clone(), config: self.config.clone() }
     }
 
     pub async fn initialize(&self) -> Result<(), sqlx::Error> {
@@ -26,83 +108,364 @@ impl SearchClient {
         Ok(())
     }
 
+    /// Проверяет настройки поиска: все конфигурации из `SEARCH_LANGUAGES` есть
+    /// в БД, а длина фрагмента подсветки допустима для `ts_headline`.
+    /// Вызывается при старте: иначе падал бы каждый поиск.
+    pub async fn verify_config(&self) -> Result<(), SearchError> {
+        if self.config.languages.is_empty() {
+            return Err(SearchError::NoLanguages);
+        }
+        let highlight = &self.config.highlight;
+        if highlight.min_words == 0 || highlight.min_words >= highlight.max_words {
+            return Err(SearchError::InvalidHighlightLength);
+        }
+        let existing: Vec<String> = sqlx::query_scalar("SELECT cfgname::text FROM pg_ts_config WHERE cfgname::text = ANY($1)")
+            .bind(&self.config.languages)
+            .fetch_all(&self.pool)
+            .await?;
+
+        match self.config.languages.iter().find(|language| !existing.contains(language)) {
+            Some(language) => Err(SearchError::UnknownLanguage(language.clone())),
+            None => Ok(()),
+        }
+    }
+
     pub async fn search_events(
         &self,
-        query: &str,
+        filters: &EventFilters,
         limit: i64,
         offset: i64,
-        from_date: Option<chrono::NaiveDateTime>,
+        after: Option<&EventCursor>,
+        highlight: bool,
     ) -> Result<Vec<EventSearchResult>, sqlx::Error> {
-        // Оптимизированный запрос
-        if query.is_empty() && from_date.is_none() {
+        let search_query = Self::prepare_search_query(&filters.query);
+        if search_query.is_empty() {
             // Быстрый путь для пустых запросов (90% случаев)
-            self.fast_path_empty_query(limit, offset).await
+            self.fast_path_empty_query(filters, limit, offset, after).await
         } else {
             // Полнотекстовый поиск
-            self.full_text_search(query, limit, offset, from_date).await
+            let results = self.full_text_search(&search_query, filters, limit, offset, after, highlight).await?;
+            if !results.is_empty() {
+                return Ok(results);
+            }
+            // Пустая страница после первой - просто конец выдачи, если
+            // полнотекстовый поиск вообще что-то находит.
+            let first_page = offset == 0 && after.is_none();
+            if !first_page && self.has_full_text_matches(&search_query, filters).await? {
+                return Ok(results);
+            }
+            // Ничего не нашлось: запрос с опечаткой или недописанным словом
+            self.fuzzy_search(&search_query, filters, limit, offset, after).await
         }
     }
 
     /// Быстрый путь для пустых запросов (без полнотекстового поиска)
     async fn fast_path_empty_query(
         &self,
+        filters: &EventFilters,
         limit: i64,
         offset: i64,
+        after: Option<&EventCursor>,
     ) -> Result<Vec<EventSearchResult>, sqlx::Error> {
-        // Используем covering index для минимального I/O
-        sqlx::query_as::<_, EventSearchResult>(
-            r#"
-            SELECT 
-                id,
-                title,
-                datetime_start,
-                NULL::float4 as rank
-            FROM events_archive
-            WHERE datetime_start > NOW()
-            ORDER BY datetime_start
-            LIMIT $1 OFFSET $2
-            "#
-        )
-        .bind(limit)
-        .bind(offset)
-        .fetch_all(&self.pool)
-        .await
+        // Используем индекс (status, datetime_start, id) для минимального I/O
+        let mut query = QueryBuilder::<Postgres>::new(format!(
+            "SELECT id, title, type AS event_type, provider, datetime_start, NULL::float4 AS rank
+             FROM {}
+             WHERE TRUE",
+            filters.source_table()
+        ));
+        filters.push_conditions(&mut query);
+        if let Some(cursor) = after {
+            cursor.push_after(&mut query);
+        }
+        query
+            .push(" ORDER BY datetime_start, id LIMIT ")
+            .push_bind(limit)
+            .push(" OFFSET ")
+            .push_bind(offset);
+
+        query.build_query_as::<EventSearchResult>().fetch_all(&self.pool).await
     }
 
     /// Полнотекстовый поиск (когда есть запрос)
+    ///
+    /// С `highlight` к каждому событию добавляется `snippet`: экранированный
+    /// текст (`SNIPPET_SOURCE`) с маркерами подсветки. `ts_headline` разбирает
+    /// текст заново и дорог, поэтому считается во внешнем запросе только для
+    /// строк страницы.
     async fn full_text_search(
         &self,
-        query: &str,
+        search_query: &str,
+        filters: &EventFilters,
+        limit: i64,
+        offset: i64,
+        after: Option<&EventCursor>,
+        highlight: bool,
+    ) -> Result<Vec<EventSearchResult>, sqlx::Error> {
+        let mut query = QueryBuilder::<Postgres>::new("");
+        if highlight {
+            query
+                .push("SELECT id, title, event_type, provider, datetime_start, rank, ts_headline(")
+                .push_bind(self.config.languages[0].clone())
+                .push("::regconfig, ")
+                .push(SNIPPET_SOURCE)
+                .push(", query, ")
+                .push_bind(self.config.highlight.options())
+                .push(") AS snippet FROM (");
+        }
+        query.push("SELECT id, title, type AS event_type, provider, datetime_start, ts_rank_cd(search_vector, query) AS rank");
+        if highlight {
+            query.push(", description, query");
+        }
+        query.push(format_args!(" FROM {}, ", filters.source_table()));
+        self.push_tsquery(&mut query, "plainto_tsquery", &self.query_variants(search_query));
+        query.push(" WHERE search_vector @@ query");
+        filters.push_conditions(&mut query);
+        if let Some(cursor) = after {
+            cursor.push_after_ranked(&mut query, "ts_rank_cd(search_vector, query)");
+        }
+        query
+            .push(" ORDER BY rank DESC, datetime_start, id LIMIT ")
+            .push_bind(limit)
+            .push(" OFFSET ")
+            .push_bind(offset);
+        if highlight {
+            query.push(") page ORDER BY rank DESC, datetime_start, id");
+        }
+
+        query.build_query_as::<EventSearchResult>().fetch_all(&self.pool).await
+    }
+
+    async fn has_full_text_matches(&self, search_query: &str, filters: &EventFilters) -> Result<bool, sqlx::Error> {
+        let mut query =
+            QueryBuilder::<Postgres>::new(format!("SELECT EXISTS(SELECT 1 FROM {}, ", filters.source_table()));
+        self.push_tsquery(&mut query, "plainto_tsquery", &self.query_variants(search_query));
+        query.push(" WHERE search_vector @@ query");
+        filters.push_conditions(&mut query);
+        query.push(")");
+
+        query.build_query_scalar::<bool>().fetch_one(&self.pool).await
+    }
+
+    /// Нечеткий поиск по названию (`pg_trgm`), ранжированный по `word_similarity`.
+    async fn fuzzy_search(
+        &self,
+        search_query: &str,
+        filters: &EventFilters,
         limit: i64,
         offset: i64,
-        from_date: Option<chrono::NaiveDateTime>,
+        after: Option<&EventCursor>,
     ) -> Result<Vec<EventSearchResult>, sqlx::Error> {
-        let search_query = Self::prepare_search_query(query);
-
-        sqlx::query_as::<_, EventSearchResult>(
-            r#"
-            SELECT 
-                id,
-                title,
-                datetime_start,
-                ts_rank_cd(search_vector, query) as rank
-            FROM events_archive,
-                 plainto_tsquery('russian', $1) query
-            WHERE 
-                search_vector @@ query
-                AND datetime_start >= COALESCE($4, NOW())
-            ORDER BY 
-                rank DESC,
-                datetime_start
-            LIMIT $2 OFFSET $3
-            "#
-        )
-        .bind(search_query)
-        .bind(limit)
-        .bind(offset)
-        .bind(from_date)
-        .fetch_all(&self.pool)
-        .await
+        let mut tx = self.begin_fuzzy().await?;
+        let variants = self.query_variants(search_query);
+        let similarity = Self::fuzzy_similarity(variants.len());
+
+        let mut query = QueryBuilder::<Postgres>::new(format!(
+            "SELECT id, title, type AS event_type, provider, datetime_start, {} AS rank FROM {}, ",
+            similarity,
+            filters.source_table()
+        ));
+        Self::push_fuzzy_match(&mut query, &variants);
+        filters.push_conditions(&mut query);
+        if let Some(cursor) = after {
+            cursor.push_after_ranked(&mut query, &similarity);
+        }
+        query
+            .push(" ORDER BY rank DESC, datetime_start, id LIMIT ")
+            .push_bind(limit)
+            .push(" OFFSET ")
+            .push_bind(offset);
+
+        let results = query.build_query_as::<EventSearchResult>().fetch_all(&mut *tx).await?;
+        tx.commit().await?;
+        Ok(results)
+    }
+
+    /// Транзакция с порогом нечеткого поиска. Порог оператора `<%` - настройка
+    /// сессии; SET LOCAL действует до конца транзакции.
+    async fn begin_fuzzy(&self) -> Result<Transaction<'static, Postgres>, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        sqlx::query("SELECT set_config('pg_trgm.word_similarity_threshold', $1, true)")
+            .bind(self.config.fuzzy_threshold.to_string())
+            .execute(&mut *tx)
+            .await?;
+        Ok(tx)
+    }
+
+    /// Сходство названия с лучшим из вариантов запроса (колонки `p.q0`, `p.q1`, ...).
+    fn fuzzy_similarity(variants: usize) -> String {
+        let terms: Vec<String> = (0..variants).map(|i| format!("word_similarity(p.q{}, title)", i)).collect();
+        format!("GREATEST({})", terms.join(", "))
+    }
+
+    /// Добавляет `(SELECT $1::text AS q0, ...) p WHERE (p.q0 <% title OR ...)`.
+    fn push_fuzzy_match(query: &mut QueryBuilder<'_, Postgres>, variants: &[String]) {
+        query.push("(SELECT ");
+        for (i, variant) in variants.iter().enumerate() {
+            if i > 0 {
+                query.push(", ");
+            }
+            query.push_bind(variant.clone()).push(format_args!("::text AS q{}", i));
+        }
+        let matches: Vec<String> = (0..variants.len()).map(|i| format!("p.q{} <% title", i)).collect();
+        query.push(format_args!(") p WHERE ({})", matches.join(" OR ")));
+    }
+
+    /// Общее число найденных событий и фасеты по типу, организатору и месяцу -
+    /// одним запросом. Считается не больше `count_threshold` совпадений: если их
+    /// больше, `total` - нижняя оценка, а фасеты посчитаны по первым совпадениям.
+    /// Как и `search_events`, при пустом полнотекстовом результате переходит
+    /// к нечеткому поиску.
+    pub async fn facets(&self, filters: &EventFilters) -> Result<SearchFacets, sqlx::Error> {
+        let search_query = Self::prepare_search_query(&filters.query);
+        let source = filters.source_table();
+        let mut conn = self.pool.acquire().await?;
+        if search_query.is_empty() {
+            return self
+                .facets_query(&mut conn, filters, |query| {
+                    query.push(format_args!("{} WHERE TRUE", source));
+                })
+                .await;
+        }
+
+        let variants = self.query_variants(&search_query);
+        let facets = self
+            .facets_query(&mut conn, filters, |query| {
+                query.push(format_args!("{}, ", source));
+                self.push_tsquery(query, "plainto_tsquery", &variants);
+                query.push(" WHERE search_vector @@ query");
+            })
+            .await?;
+        if facets.total > 0 {
+            return Ok(facets);
+        }
+        drop(conn);
+
+        let mut tx = self.begin_fuzzy().await?;
+        let facets = self
+            .facets_query(&mut tx, filters, |query| {
+                query.push(format_args!("{}, ", source));
+                Self::push_fuzzy_match(query, &variants);
+            })
+            .await?;
+        tx.commit().await?;
+        Ok(facets)
+    }
+
+    /// `push_source` добавляет источник совпадений: `FROM`-часть без слова FROM и `WHERE`.
+    async fn facets_query(
+        &self,
+        conn: &mut PgConnection,
+        filters: &EventFilters,
+        push_source: impl FnOnce(&mut QueryBuilder<'_, Postgres>),
+    ) -> Result<SearchFacets, sqlx::Error> {
+        let mut query = QueryBuilder::<Postgres>::new("WITH hits AS (SELECT type, provider, datetime_start FROM ");
+        push_source(&mut query);
+        filters.push_conditions(&mut query);
+        query
+            .push(" LIMIT ")
+            .push_bind(self.config.count_threshold + 1)
+            .push(format_args!(
+                ") SELECT
+                     (SELECT count(*) FROM hits) AS total,
+                     {} AS by_type,
+                     {} AS by_provider,
+                     {} AS by_month",
+                facet_sql("type", "count DESC, value", FACET_LIMIT),
+                facet_sql("provider", "count DESC, value", FACET_LIMIT),
+                facet_sql("to_char(date_trunc('month', datetime_start), 'YYYY-MM')", "value", MONTH_FACET_LIMIT),
+            ));
+
+        let (total, by_type, by_provider, by_month) = query
+            .build_query_as::<(i64, Json<Vec<FacetCount>>, Json<Vec<FacetCount>>, Json<Vec<FacetCount>>)>()
+            .fetch_one(conn)
+            .await?;
+
+        let estimated = total > self.config.count_threshold;
+        Ok(SearchFacets {
+            total: total.min(self.config.count_threshold),
+            total_estimated: estimated,
+            by_type: by_type.0,
+            by_provider: by_provider.0,
+            by_month: by_month.0,
+        })
+    }
+
+    /// Подсказки для автодополнения по началу ввода: префиксный tsquery
+    /// (последнее слово может быть недописано) и триграммное сходство с
+    /// названием (опечатки). Одно название - одна подсказка с ближайшим событием.
+    pub async fn suggest(&self, prefix: &str, limit: i64) -> Result<Vec<EventSuggestion>, sqlx::Error> {
+        let words = Self::prepare_search_query(prefix);
+        let prefix_queries: Vec<String> = self
+            .query_variants(&words)
+            .iter()
+            .map(|variant| {
+                variant
+                    .split(|c: char| !c.is_alphanumeric())
+                    .filter(|w| !w.is_empty())
+                    .map(|w| format!("{}:*", w))
+                    .collect::<Vec<_>>()
+                    .join(" & ")
+            })
+            .filter(|q| !q.is_empty())
+            .collect();
+        if prefix_queries.is_empty() {
+            return Ok(vec![]);
+        }
+
+        let filters = EventFilters::default();
+        let mut query = QueryBuilder::<Postgres>::new(format!(
+            "SELECT id, title, datetime_start, score FROM (
+                 SELECT DISTINCT ON (title) id, title, datetime_start,
+                        GREATEST(ts_rank_cd(search_vector, query), word_similarity(p.q, title)) AS score
+                 FROM {}, ",
+            filters.source_table()
+        ));
+        self.push_tsquery(&mut query, "to_tsquery", &prefix_queries);
+        query
+            .push(", (SELECT ")
+            .push_bind(words)
+            .push("::text AS q) p WHERE (search_vector @@ query OR p.q <% title)");
+        filters.push_conditions(&mut query);
+        query
+            .push(" ORDER BY title, datetime_start, id) s ORDER BY score DESC, datetime_start LIMIT ")
+            .push_bind(limit);
+
+        query.build_query_as::<EventSuggestion>().fetch_all(&self.pool).await
+    }
+
+    /// Варианты текста запроса: сам запрос и, если включено, его транслитерация.
+    fn query_variants(&self, search_query: &str) -> Vec<String> {
+        let mut variants = vec![search_query.to_string()];
+        if self.config.transliterate {
+            if let Some(variant) = transliterate(search_query) {
+                variants.push(variant);
+            }
+        }
+        variants
+    }
+
+    /// Добавляет в FROM подзапрос `(SELECT ... AS query) tsq` - объединение (`||`)
+    /// tsquery по всем языкам и вариантам запроса. Ранг по такому запросу
+    /// учитывает совпадения из всех языков сразу.
+    fn push_tsquery(&self, query: &mut QueryBuilder<'_, Postgres>, function: &str, variants: &[String]) {
+        query.push("(SELECT ");
+        let mut first = true;
+        for language in &self.config.languages {
+            for variant in variants {
+                if !first {
+                    query.push(" || ");
+                }
+                first = false;
+                query
+                    .push(format_args!("{}(", function))
+                    .push_bind(language.clone())
+                    .push("::regconfig, ")
+                    .push_bind(variant.clone())
+                    .push(")");
+            }
+        }
+        query.push(" AS query) tsq");
     }
 
     fn prepare_search_query(query: &str) -> String {
@@ -114,4 +477,170 @@ impl SearchClient {
             .collect::<Vec<_>>()
             .join(" ")
     }
-}
\ No newline at end of file
+}
+/// Транслитерация запроса между кириллицей и латиницей. Направление выбирается
+/// по первой букве. Возвращает `None`, если вариант совпадает с запросом.
+fn transliterate(query: &str) -> Option<String> {
+    let first_letter = query.chars().find(|c| c.is_alphabetic())?;
+    let variant = if is_cyrillic(first_letter) {
+        query.chars().map(cyrillic_to_latin).collect::<String>()
+    } else {
+        latin_to_cyrillic(query)
+    };
+    (variant != query).then_some(variant)
+}
+
+fn is_cyrillic(c: char) -> bool {
+    matches!(c, '\u{0400}'..='\u{04FF}')
+}
+
+/// Кириллица (русский и казахский алфавиты) в латиницу.
+fn cyrillic_to_latin(c: char) -> String {
+    let lower = c.to_lowercase().next().unwrap_or(c);
+    let latin = match lower {
+        'а' | 'ә' => "a",
+        'б' => "b",
+        'в' => "v",
+        'г' | 'ғ' => "g",
+        'д' => "d",
+        'е' | 'э' => "e",
+        'ё' => "yo",
+        'ж' => "zh",
+        'з' => "z",
+        'и' | 'й' | 'і' => "i",
+        'к' => "k",
+        'қ' => "q",
+        'л' => "l",
+        'м' => "m",
+        'н' => "n",
+        'ң' => "ng",
+        'о' | 'ө' => "o",
+        'п' => "p",
+        'р' => "r",
+        'с' => "s",
+        'т' => "t",
+        'у' | 'ұ' | 'ү' => "u",
+        'ф' => "f",
+        'х' | 'һ' => "h",
+        'ц' => "ts",
+        'ч' => "ch",
+        'ш' => "sh",
+        'щ' => "shch",
+        'ъ' | 'ь' => "",
+        'ы' => "y",
+        'ю' => "yu",
+        'я' => "ya",
+        other => return other.to_string(),
+    };
+    latin.to_string()
+}
+
+/// Латиница в кириллицу: сначала буквосочетания, затем одиночные буквы.
+fn latin_to_cyrillic(query: &str) -> String {
+    const DIGRAPHS: &[(&str, &str)] = &[
+        ("shch", "щ"),
+        ("sh", "ш"),
+        ("ch", "ч"),
+        ("zh", "ж"),
+        ("kh", "х"),
+        ("ts", "ц"),
+        ("yo", "ё"),
+        ("yu", "ю"),
+        ("ya", "я"),
+    ];
+
+    let lower = query.to_lowercase();
+    let mut result = String::with_capacity(lower.len() * 2);
+    let mut rest = lower.as_str();
+    'outer: while let Some(c) = rest.chars().next() {
+        for (latin, cyrillic) in DIGRAPHS {
+            if let Some(tail) = rest.strip_prefix(latin) {
+                result.push_str(cyrillic);
+                rest = tail;
+                continue 'outer;
+            }
+        }
+        let cyrillic = match c {
+            'a' => 'а',
+            'b' => 'б',
+            'c' => 'к',
+            'd' => 'д',
+            'e' => 'е',
+            'f' => 'ф',
+            'g' => 'г',
+            'h' => 'х',
+            'i' => 'и',
+            'j' => 'ж',
+            'k' => 'к',
+            'l' => 'л',
+            'm' => 'м',
+            'n' => 'н',
+            'o' => 'о',
+            'p' => 'п',
+            'q' => 'к',
+            'r' => 'р',
+            's' => 'с',
+            't' => 'т',
+            'u' => 'у',
+            'v' => 'в',
+            'w' => 'в',
+            'x' => 'х',
+            'y' => 'й',
+            'z' => 'з',
+            other => other,
+        };
+        result.push(cyrillic);
+        rest = &rest[c.len_utf8()..];
+    }
+    result
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn to_latin(query: &str) -> String {
+        query.chars().map(cyrillic_to_latin).collect()
+    }
+
+    #[test]
+    fn longest_digraph_wins() {
+        assert_eq!(latin_to_cyrillic("borshch"), "борщ");
+        assert_eq!(latin_to_cyrillic("shashki"), "шашки");
+        assert_eq!(latin_to_cyrillic("chaika"), "чаика");
+        assert_eq!(latin_to_cyrillic("yolka"), "ёлка");
+    }
+
+    #[test]
+    fn kazakh_letters_are_transliterated() {
+        assert_eq!(to_latin("Қазақстан"), "qazaqstan");
+        assert_eq!(to_latin("әнші"), "anshi");
+        assert_eq!(to_latin("қоңыр"), "qongyr");
+        assert_eq!(to_latin("өнер үйі"), "oner uii");
+        assert_eq!(to_latin("ғұмыр"), "gumyr");
+        assert_eq!(to_latin("һ"), "h");
+    }
+
+    #[test]
+    fn russian_round_trip() {
+        assert_eq!(transliterate("Щелкунчик").as_deref(), Some("shchelkunchik"));
+        assert_eq!(transliterate("shchelkunchik").as_deref(), Some("щелкунчик"));
+        assert_eq!(transliterate("Объект").as_deref(), Some("obekt"));
+    }
+
+    #[test]
+    fn no_variant_without_letters() {
+        assert_eq!(transliterate(""), None);
+        assert_eq!(transliterate("2025"), None);
+        assert_eq!(transliterate("12-00"), None);
+    }
+
+    #[test]
+    fn direction_follows_first_letter_in_mixed_queries() {
+        // Буквы другого алфавита остаются как есть
+        assert_eq!(transliterate("концерт rock").as_deref(), Some("kontsert rock"));
+        assert_eq!(transliterate("rock концерт").as_deref(), Some("рокк концерт"));
+        assert_eq!(transliterate("2025 джаз").as_deref(), Some("2025 dzhaz"));
+    }
+}
@@ -0,0 +1,129 @@
+//! backfill.rs
+//!
+//! Фоновые переносы данных, которые нельзя выполнить в миграции при старте:
+//! на миллиардах строк `events_archive` это заблокировало бы каждый инстанс.
+//!
+//! Миграция регистрирует перенос в `background_migrations`, а планировщик
+//! обрабатывает его пачками по возрастанию id, запоминая `last_id` в той же
+//! транзакции, что и пачку. Когда строк не осталось, выполняются завершающие
+//! операторы переноса и ставится `completed_at`.
+
+use std::sync::Arc;
+use tracing::{error, info};
+
+use crate::AppState;
+
+/// Перенос данных. `batch` получает `$1` - последний обработанный id и `$2` -
+/// размер пачки, и возвращает наибольший id пачки (NULL, если строк не осталось).
+struct Backfill {
+    name: &'static str,
+    batch: &'static str,
+    /// Выполняются после последней пачки, каждый в своей транзакции.
+    finish: &'static [&'static str],
+}
+
+const BACKFILLS: &[Backfill] = &[
+    // Многоязычный search_vector для строк, записанных до миграции 007
+    Backfill {
+        name: "events_search_vector",
+        batch: "WITH batch AS (
+                    SELECT id FROM events_archive WHERE id > $1 ORDER BY id LIMIT $2
+                ), updated AS (
+                    UPDATE events_archive e
+                    SET search_vector = events_search_vector(e.title, e.description, e.type, e.provider)
+                    FROM batch WHERE e.id = batch.id
+                )
+                SELECT max(id) FROM batch",
+        finish: &[],
+    },
+    // id событий, записанных до секционирования (миграция 010), и проверка
+    // внешних ключей seats и bookings, добавленных NOT VALID
+    Backfill {
+        name: "event_ids",
+        batch: "WITH batch AS (
+                    SELECT id, datetime_start FROM events_archive_legacy WHERE id > $1 ORDER BY id LIMIT $2
+                ), inserted AS (
+                    INSERT INTO event_ids (id, datetime_start)
+                    SELECT id, datetime_start FROM batch
+                    ON CONFLICT (id) DO NOTHING
+                )
+                SELECT max(id) FROM batch",
+        finish: &[
+            "ALTER TABLE seats VALIDATE CONSTRAINT seats_event_id_fkey",
+            "ALTER TABLE bookings VALIDATE CONSTRAINT bookings_event_id_fkey",
+        ],
+    },
+];
+
+pub struct BackfillService {
+    state: Arc<AppState>,
+}
+
+impl BackfillService {
+    pub fn new(state: Arc<AppState>) -> Self {
+        Self { state }
+    }
+
+    /// Обрабатывает по одной пачке каждого незавершенного переноса.
+    pub async fn run_pending(&self, batch_size: i64) {
+        let pending: Vec<(String, i64)> = match sqlx::query_as(
+            "SELECT name, last_id FROM background_migrations WHERE completed_at IS NULL"
+        )
+        .fetch_all(&self.state.db.pool)
+        .await
+        {
+            Ok(pending) => pending,
+            Err(e) => {
+                error!("🚚 Failed to read background migrations: {}", e);
+                return;
+            }
+        };
+
+        for (name, last_id) in pending {
+            let Some(backfill) = BACKFILLS.iter().find(|b| b.name == name) else {
+                error!("🚚 Unknown background migration {}", name);
+                continue;
+            };
+            if let Err(e) = self.run_batch(backfill, last_id, batch_size).await {
+                error!("🚚 Background migration {} failed after id {}: {}", name, last_id, e);
+            }
+        }
+    }
+
+    async fn run_batch(&self, backfill: &Backfill, last_id: i64, batch_size: i64) -> Result<(), sqlx::Error> {
+        let mut tx = self.state.db.pool.begin().await?;
+        let batch_last: Option<i64> = sqlx::query_scalar(backfill.batch)
+            .bind(last_id)
+            .bind(batch_size.max(1))
+            .fetch_one(&mut *tx)
+            .await?;
+
+        let Some(batch_last) = batch_last else {
+            tx.rollback().await?;
+            return self.finish(backfill).await;
+        };
+
+        sqlx::query("UPDATE background_migrations SET last_id = $2 WHERE name = $1")
+            .bind(backfill.name)
+            .bind(batch_last)
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await
+    }
+
+    async fn finish(&self, backfill: &Backfill) -> Result<(), sqlx::Error> {
+        for statement in backfill.finish {
+            // Проверка ограничений читает всю таблицу и может идти дольше DB_STATEMENT_TIMEOUT_MS
+            let mut tx = self.state.db.pool.begin().await?;
+            sqlx::query("SET LOCAL statement_timeout = 0").execute(&mut *tx).await?;
+            sqlx::query(statement).execute(&mut *tx).await?;
+            tx.commit().await?;
+        }
+        sqlx::query("UPDATE background_migrations SET completed_at = NOW() WHERE name = $1")
+            .bind(backfill.name)
+            .execute(&self.state.db.pool)
+            .await?;
+        info!("🚚 Background migration {} completed", backfill.name);
+        Ok(())
+    }
+}
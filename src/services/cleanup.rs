@@ -1,9 +1,16 @@
-use sqlx::{Row, postgres::PgRow};
 use std::sync::Arc;
 use tracing::{info, error, warn};
 use redis::AsyncCommands;
+use serde::Serialize;
 
-use crate::AppState;
+use crate::{
+    AppState,
+    models::{
+        state_machine::{self, TransitionError},
+        BookingStatus, PaymentStatus, SeatStatus,
+    },
+    services::seat_events::SeatChange,
+};
 
 pub struct CleanupService {
     state: Arc<AppState>,
@@ -31,16 +38,17 @@ impl CleanupService {
     }
 
     /// Очистка истёкших платежей (из payment модуля)
-    async fn cleanup_expired_payments(&self) {
+    pub async fn cleanup_expired_payments(&self) {
         let expired: Vec<(String, i64, i64)> = sqlx::query_as(
             r#"
             SELECT pt.transaction_id, b.id, b.event_id
             FROM payment_transactions pt
             JOIN bookings b ON b.id = pt.booking_id
-            WHERE pt.status = 'pending' 
+            WHERE pt.status = $1
               AND pt.created_at < NOW() - interval '15 minutes'
             "#
         )
+        .bind(PaymentStatus::Pending)
         .fetch_all(&self.state.db.pool)
         .await
         .unwrap_or_default();
@@ -67,39 +75,28 @@ impl CleanupService {
             }
         };
 
-        // Обновляем статус платежа на expired
-        let _ = sqlx::query(
-            "UPDATE payment_transactions SET status = 'expired', updated_at = NOW() 
-             WHERE transaction_id = $1"
-        )
-        .bind(&payment_id)
-        .execute(&mut *tx)
-        .await;
-
-        // Освобождаем места
-        let seats: Vec<i64> = sqlx::query(
-            "UPDATE seats 
-             SET status = 'AVAILABLE', booking_id = NULL, updated_at = NOW() 
-             WHERE booking_id = $1 AND status = 'RESERVED' 
-             RETURNING id"
-        )
-        .bind(booking_id)
-        .fetch_all(&mut *tx)
-        .await
-        .unwrap_or_default()
-        .into_iter()
-        .map(|row: PgRow| row.get("id"))
-        .collect();
-
-        // Удаляем бронирование
-        let _ = sqlx::query("DELETE FROM bookings WHERE id = $1")
-            .bind(booking_id)
-            .execute(&mut *tx)
-            .await;
+        // Обновляем статус платежа на expired, освобождаем места
+        // и отменяем бронирование (удалить нельзя - на него ссылается платеж)
+        let result = async {
+            state_machine::transition_payment(&mut tx, &payment_id, PaymentStatus::Expired).await?;
+            let seats = state_machine::transition_booking_seats(&mut tx, booking_id, SeatStatus::Free).await?;
+            state_machine::transition_booking(&mut tx, booking_id, BookingStatus::Cancelled).await?;
+            Ok::<_, TransitionError>(seats)
+        }.await;
+
+        let seats = match result {
+            Ok(seats) => seats,
+            Err(e) => {
+                warn!("💳 Expired payment {} cleanup rejected: {}", payment_id, e);
+                let _ = tx.rollback().await;
+                return;
+            }
+        };
 
         if tx.commit().await.is_ok() {
             self.clear_redis_reservations(&seats).await;
             self.state.cache.invalidate_seats(event_id).await;
+            self.state.seat_events.publish(event_id, &seats, SeatChange::Expired).await;
             info!("💳 Expired payment {} cleaned up, {} seats released", payment_id, seats.len());
         } else {
             error!("Failed to commit payment cleanup transaction for {}", payment_id);
@@ -107,7 +104,7 @@ impl CleanupService {
     }
 
     /// Очистка старых бронирований без платежей
-    async fn cleanup_expired_bookings(&self) {
+    pub async fn cleanup_expired_bookings(&self) {
         info!("🎫 Starting booking cleanup");
 
         // 1. Очищаем пустые старые бронирования (без мест)
@@ -124,11 +121,12 @@ impl CleanupService {
             SELECT b.id 
             FROM bookings b
             LEFT JOIN seats s ON s.booking_id = b.id
-            WHERE b.status = 'created'
+            WHERE b.status = $1
               AND b.created_at < NOW() - interval '2 hours'
               AND s.id IS NULL
             "#
         )
+        .bind(BookingStatus::Created)
         .fetch_all(&self.state.db.pool)
         .await
         .unwrap_or_default();
@@ -142,9 +140,10 @@ impl CleanupService {
 
         for booking_id in empty_bookings {
             let result = sqlx::query(
-                "DELETE FROM bookings WHERE id = $1 AND status = 'created'"
+                "DELETE FROM bookings WHERE id = $1 AND status = $2"
             )
             .bind(booking_id)
+            .bind(BookingStatus::Created)
             .execute(&self.state.db.pool)
             .await;
 
@@ -170,12 +169,14 @@ impl CleanupService {
             FROM bookings b
             JOIN seats s ON s.booking_id = b.id
             LEFT JOIN payment_transactions pt ON pt.booking_id = b.id
-            WHERE b.status = 'created'
+            WHERE b.status = $1
               AND b.created_at < NOW() - interval '30 minutes'
-              AND s.status = 'RESERVED'
+              AND s.status = $2
               AND pt.id IS NULL
             "#
         )
+        .bind(BookingStatus::Created)
+        .bind(SeatStatus::Reserved)
         .fetch_all(&self.state.db.pool)
         .await
         .unwrap_or_default();
@@ -203,23 +204,19 @@ impl CleanupService {
         };
 
         // Освобождаем места
-        let seats: Vec<i64> = sqlx::query(
-            "UPDATE seats 
-             SET status = 'AVAILABLE', booking_id = NULL, updated_at = NOW() 
-             WHERE booking_id = $1 AND status = 'RESERVED' 
-             RETURNING id"
-        )
-        .bind(booking_id)
-        .fetch_all(&mut *tx)
-        .await
-        .unwrap_or_default()
-        .into_iter()
-        .map(|row: PgRow| row.get("id"))
-        .collect();
+        let seats = match state_machine::transition_booking_seats(&mut tx, booking_id, SeatStatus::Free).await {
+            Ok(seats) => seats,
+            Err(e) => {
+                error!("Failed to release seats of stale booking {}: {}", booking_id, e);
+                let _ = tx.rollback().await;
+                return;
+            }
+        };
 
-        // Удаляем бронирование
-        let booking_result = sqlx::query("DELETE FROM bookings WHERE id = $1")
+        // Удаляем бронирование, если оно все еще не дошло до оплаты
+        let booking_result = sqlx::query("DELETE FROM bookings WHERE id = $1 AND status = $2")
             .bind(booking_id)
+            .bind(BookingStatus::Created)
             .execute(&mut *tx)
             .await;
 
@@ -228,6 +225,7 @@ impl CleanupService {
                 if tx.commit().await.is_ok() {
                     self.clear_redis_reservations(&seats).await;
                     self.state.cache.invalidate_seats(event_id).await;
+                    self.state.seat_events.publish(event_id, &seats, SeatChange::Expired).await;
                     info!("🎫 Stale booking {} cleaned up, {} seats released", booking_id, seats.len());
                 } else {
                     error!("Failed to commit booking cleanup transaction for {}", booking_id);
@@ -241,7 +239,7 @@ impl CleanupService {
     }
 
     /// Очистка висящих резервов в Redis (без соответствующих записей в БД)
-    async fn cleanup_orphaned_redis_reserves(&self) {
+    pub async fn cleanup_orphaned_redis_reserves(&self) {
         let mut redis_conn = self.state.redis.conn.clone();
         
         // Получаем все ключи резервов в Redis
@@ -259,6 +257,7 @@ impl CleanupService {
         info!("🔑 Checking {} Redis reserves for orphaned entries", redis_keys.len());
 
         let mut orphaned_keys = Vec::new();
+        let mut orphaned_seats = Vec::new();
 
         for key in redis_keys {
             // Извлекаем seat_id из ключа (формат: seat:123 или seat:123:reserved)
@@ -266,15 +265,17 @@ impl CleanupService {
                 if let Ok(seat_id) = seat_id_str.parse::<i64>() {
                     // Проверяем, есть ли это место в БД с соответствующим статусом
                     let seat_exists: bool = sqlx::query_scalar(
-                        "SELECT EXISTS(SELECT 1 FROM seats WHERE id = $1 AND status IN ('RESERVED', 'SELECTED'))"
+                        "SELECT EXISTS(SELECT 1 FROM seats WHERE id = $1 AND status = $2)"
                     )
                     .bind(seat_id)
+                    .bind(SeatStatus::Reserved)
                     .fetch_one(&self.state.db.pool)
                     .await
                     .unwrap_or(false);
 
                     if !seat_exists {
                         orphaned_keys.push(key);
+                        orphaned_seats.push(seat_id);
                     }
                 }
             }
@@ -287,8 +288,9 @@ impl CleanupService {
 
         info!("🔑 Found {} orphaned Redis reserves to cleanup", orphaned_keys.len());
 
-        // Удаляем осиротевшие ключи
+        // Удаляем осиротевшие ключи и их записи в индексе удержаний
         let _: Result<i64, _> = redis_conn.del(orphaned_keys.clone()).await;
+        self.state.cache.release_seat_holds(&orphaned_seats).await;
         
         info!("🔑 Cleaned up {} orphaned Redis reserves", orphaned_keys.len());
     }
@@ -311,17 +313,7 @@ impl CleanupService {
 
     /// Очистка резерваций в Redis
     async fn clear_redis_reservations(&self, seat_ids: &[i64]) {
-        if seat_ids.is_empty() {
-            return;
-        }
-
-        let mut redis = self.state.redis.clone();
-        
-        let keys: Vec<String> = seat_ids.iter()
-            .map(|id| format!("seat:{}", id))
-            .collect();
-
-        let _: Result<i64, _> = redis.conn.del(keys).await;
+        self.state.cache.release_seat_holds(seat_ids).await;
     }
 
     /// Получает статистику для мониторинга
@@ -329,8 +321,9 @@ impl CleanupService {
         // Считаем количество записей для очистки
         let expired_payments: i64 = sqlx::query_scalar(
             "SELECT COUNT(*) FROM payment_transactions 
-             WHERE status = 'pending' AND created_at < NOW() - interval '15 minutes'"
+             WHERE status = $1 AND created_at < NOW() - interval '15 minutes'"
         )
+        .bind(PaymentStatus::Pending)
         .fetch_one(&self.state.db.pool)
         .await
         .unwrap_or(0);
@@ -340,11 +333,12 @@ impl CleanupService {
             SELECT COUNT(*) 
             FROM bookings b
             LEFT JOIN seats s ON s.booking_id = b.id
-            WHERE b.status = 'created'
+            WHERE b.status = $1
               AND b.created_at < NOW() - interval '2 hours'
               AND s.id IS NULL
             "#
         )
+        .bind(BookingStatus::Created)
         .fetch_one(&self.state.db.pool)
         .await
         .unwrap_or(0);
@@ -355,12 +349,14 @@ impl CleanupService {
             FROM bookings b
             JOIN seats s ON s.booking_id = b.id
             LEFT JOIN payment_transactions pt ON pt.booking_id = b.id
-            WHERE b.status = 'created'
+            WHERE b.status = $1
               AND b.created_at < NOW() - interval '30 minutes'
-              AND s.status = 'RESERVED'
+              AND s.status = $2
               AND pt.id IS NULL
             "#
         )
+        .bind(BookingStatus::Created)
+        .bind(SeatStatus::Reserved)
         .fetch_one(&self.state.db.pool)
         .await
         .unwrap_or(0);
@@ -374,25 +370,30 @@ impl CleanupService {
             .await
             .unwrap_or(0);
 
+        let expired_holds = self.state.cache.count_expired_holds().await.unwrap_or(0);
+
         CleanupStats {
             expired_payments,
             empty_bookings,
             stale_bookings,
             redis_reserves,
+            expired_holds,
         }
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Serialize)]
 pub struct CleanupStats {
     pub expired_payments: i64,
     pub empty_bookings: i64,
     pub stale_bookings: i64,
     pub redis_reserves: i64,
+    /// Истекшие удержания мест, которые еще не обработаны.
+    pub expired_holds: i64,
 }
 
 impl CleanupStats {
     pub fn total_items_to_cleanup(&self) -> i64 {
-        self.expired_payments + self.empty_bookings + self.stale_bookings
+        self.expired_payments + self.empty_bookings + self.stale_bookings + self.expired_holds
     }
 }
\ No newline at end of file
@@ -0,0 +1,173 @@
+//! hold_expiry.rs
+//!
+//! Освобождение мест, удержание которых в Redis истекло.
+//!
+//! `select_seat` сначала удерживает место ключом `seat:{id}:reserved` с TTL,
+//! а затем пишет `RESERVED` в БД. Когда ключ истекает, место в БД нужно
+//! вернуть в `FREE`, если по брони не идет оплата. Истекшие удержания
+//! находятся по индексу `seat_holds` (sorted set по моменту истечения).
+//! Записи индекса могут потеряться (рестарт Redis, сбой между Redis и БД),
+//! поэтому отдельный проход по БД освобождает давно зарезервированные
+//! места без живого удержания.
+
+use std::{collections::BTreeMap, sync::Arc};
+use tracing::{error, info, warn};
+
+use crate::{
+    AppState,
+    cache::holds::SEAT_HOLD_TTL_SECONDS,
+    models::{
+        BookingStatus, PaymentStatus, SeatStatus,
+        state_machine::{self, TransitionError},
+    },
+    services::seat_events::SeatChange,
+};
+
+/// Сколько мест обрабатывается за один запрос к Redis и БД.
+const BATCH_SIZE: usize = 500;
+
+pub struct HoldExpiryService {
+    state: Arc<AppState>,
+}
+
+impl HoldExpiryService {
+    pub fn new(state: Arc<AppState>) -> Self {
+        Self { state }
+    }
+
+    /// Освобождает места из индекса удержаний, срок которых истек.
+    pub async fn release_expired_holds(&self) {
+        let mut released = 0;
+        loop {
+            let seat_ids = match self.state.cache.claim_expired_holds(BATCH_SIZE).await {
+                Ok(ids) => ids,
+                Err(e) => {
+                    warn!("⏳ Failed to read expired seat holds: {:?}", e);
+                    return;
+                }
+            };
+            if seat_ids.is_empty() {
+                break;
+            }
+
+            released += self.release_seats(&seat_ids).await;
+            if seat_ids.len() < BATCH_SIZE {
+                break;
+            }
+        }
+
+        if released > 0 {
+            info!("⏳ Released {} seats with expired holds", released);
+        }
+    }
+
+    /// Страховочный проход: места в `RESERVED` дольше срока удержания,
+    /// у которых нет ключа удержания в Redis.
+    pub async fn sweep_lapsed_holds(&self) {
+        let candidates: Vec<i64> = match sqlx::query_scalar(
+            r#"
+            SELECT s.id
+            FROM seats s
+            JOIN bookings b ON b.id = s.booking_id
+            WHERE s.status = $1
+              AND b.status = $2
+              AND s.updated_at < NOW() - make_interval(secs => $3)
+            ORDER BY s.updated_at
+            LIMIT $4
+            "#
+        )
+        .bind(SeatStatus::Reserved)
+        .bind(BookingStatus::Created)
+        .bind(SEAT_HOLD_TTL_SECONDS as f64)
+        .bind(BATCH_SIZE as i64)
+        .fetch_all(&self.state.db.pool)
+        .await
+        {
+            Ok(ids) => ids,
+            Err(e) => {
+                error!("⏳ Failed to find lapsed seat holds: {:?}", e);
+                return;
+            }
+        };
+
+        if candidates.is_empty() {
+            return;
+        }
+
+        let released = self.release_seats(&candidates).await;
+        if released > 0 {
+            info!("⏳ Sweep released {} seats without a hold", released);
+        }
+    }
+
+    /// Возвращает места в `FREE`, если у них нет живого удержания и их бронь
+    /// еще не ушла в оплату, инвалидирует кеш мест и рассылает изменения.
+    /// Возвращает число мест.
+    async fn release_seats(&self, seat_ids: &[i64]) -> usize {
+        let result = async {
+            let mut tx = self.state.db.pool.begin().await?;
+            // Блокируем места и их брони, чтобы оплата не началась между
+            // проверкой и переходом.
+            let locked: Vec<i64> = sqlx::query_scalar(
+                r#"
+                SELECT s.id
+                FROM seats s
+                JOIN bookings b ON b.id = s.booking_id
+                WHERE s.id = ANY($1)
+                  AND b.status = $2
+                  AND NOT EXISTS (
+                      SELECT 1 FROM payment_transactions pt
+                      WHERE pt.booking_id = b.id AND pt.status = $3
+                  )
+                FOR UPDATE OF s, b
+                "#
+            )
+            .bind(seat_ids)
+            .bind(BookingStatus::Created)
+            .bind(PaymentStatus::Pending)
+            .fetch_all(&mut *tx)
+            .await?;
+
+            // Пока запись шла из индекса сюда, место могли освободить и занять
+            // заново: живое удержание - это уже чужая бронь. Без ответа Redis
+            // их не отличить, поэтому ничего не освобождаем.
+            let held = match self.state.cache.seat_holds_exist(&locked).await {
+                Ok(held) => held,
+                Err(e) => {
+                    warn!("⏳ Failed to check seat holds: {:?}", e);
+                    return Ok(Vec::new());
+                }
+            };
+            let releasable: Vec<i64> = locked
+                .into_iter()
+                .zip(held)
+                .filter(|(_, held)| !held)
+                .map(|(seat_id, _)| seat_id)
+                .collect();
+
+            let released = state_machine::transition_seats(&mut tx, &releasable, SeatStatus::Free, None).await?;
+            tx.commit().await?;
+            Ok::<_, TransitionError>(released)
+        }.await;
+
+        let released = match result {
+            Ok(rows) => rows,
+            Err(e) => {
+                // Записи уже забраны из индекса - их подберет проход по БД.
+                error!("⏳ Failed to release {} seats with expired holds: {:?}", seat_ids.len(), e);
+                return 0;
+            }
+        };
+
+        let mut by_event: BTreeMap<i64, Vec<i64>> = BTreeMap::new();
+        for (seat_id, event_id) in &released {
+            by_event.entry(*event_id).or_default().push(*seat_id);
+        }
+        for (event_id, seat_ids) in &by_event {
+            self.state.cache.invalidate_seats(*event_id).await;
+            self.state.seat_events.publish(*event_id, seat_ids, SeatChange::Expired).await;
+        }
+
+        released.len()
+    }
+}
@@ -0,0 +1,110 @@
+//! jwt.rs
+//!
+//! Выпуск и проверка JWT-токенов сессии.
+//!
+//! Токен подписывается HS256 секретом из `JwtConfig` и несет в себе все поля
+//! `AuthUser`, поэтому проверка Bearer-токена не ходит в базу данных.
+//! Отозванные токены хранятся в Redis по `jti` (см. `cache::auth`).
+
+use chrono::{DateTime, Utc};
+use jsonwebtoken::{Algorithm, DecodingKey, EncodingKey, Header, Validation};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::{config::JwtConfig, middleware::AuthUser};
+
+/// Полезная нагрузка токена.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Claims {
+    /// ID пользователя (`users.user_id`).
+    pub sub: String,
+    pub email: String,
+    pub first_name: String,
+    pub surname: String,
+    /// Время выпуска (unix timestamp).
+    pub iat: i64,
+    /// Время истечения (unix timestamp).
+    pub exp: i64,
+    /// Уникальный ID токена, по нему ведется список отзыва.
+    pub jti: String,
+}
+
+impl Claims {
+    /// Восстанавливает `AuthUser` из токена.
+    pub fn auth_user(&self) -> Option<AuthUser> {
+        Some(AuthUser {
+            user_id: self.sub.parse().ok()?,
+            email: self.email.clone(),
+            first_name: self.first_name.clone(),
+            surname: self.surname.clone(),
+        })
+    }
+
+    /// Сколько секунд токен еще действителен (минимум 1).
+    pub fn remaining_seconds(&self) -> u64 {
+        (self.exp - Utc::now().timestamp()).max(1) as u64
+    }
+}
+
+/// Выпущенный токен вместе с метаданными для ответа клиенту.
+#[derive(Debug, Clone, Serialize)]
+pub struct IssuedToken {
+    pub access_token: String,
+    pub token_type: &'static str,
+    pub expires_in: i64,
+    pub expires_at: DateTime<Utc>,
+}
+
+/// Сервис подписи и проверки токенов. Ключи строятся один раз при старте.
+#[derive(Clone)]
+pub struct JwtService {
+    encoding_key: EncodingKey,
+    decoding_key: DecodingKey,
+    validation: Validation,
+    expires_in_hours: i64,
+}
+
+impl JwtService {
+    pub fn new(config: &JwtConfig) -> Self {
+        let mut validation = Validation::new(Algorithm::HS256);
+        validation.set_required_spec_claims(&["exp", "sub"]);
+        validation.leeway = 0;
+
+        Self {
+            encoding_key: EncodingKey::from_secret(config.secret.as_bytes()),
+            decoding_key: DecodingKey::from_secret(config.secret.as_bytes()),
+            validation,
+            expires_in_hours: config.expires_in_hours,
+        }
+    }
+
+    /// Выпускает новый токен для пользователя.
+    pub fn issue(&self, user: &AuthUser) -> Result<IssuedToken, jsonwebtoken::errors::Error> {
+        let now = Utc::now();
+        let expires_at = now + chrono::Duration::hours(self.expires_in_hours);
+        let claims = Claims {
+            sub: user.user_id.to_string(),
+            email: user.email.clone(),
+            first_name: user.first_name.clone(),
+            surname: user.surname.clone(),
+            iat: now.timestamp(),
+            exp: expires_at.timestamp(),
+            jti: Uuid::new_v4().to_string(),
+        };
+
+        let access_token = jsonwebtoken::encode(&Header::new(Algorithm::HS256), &claims, &self.encoding_key)?;
+
+        Ok(IssuedToken {
+            access_token,
+            token_type: "Bearer",
+            expires_in: claims.exp - claims.iat,
+            expires_at,
+        })
+    }
+
+    /// Проверяет подпись и срок действия токена. Список отзыва здесь не проверяется.
+    pub fn decode(&self, token: &str) -> Result<Claims, jsonwebtoken::errors::Error> {
+        jsonwebtoken::decode::<Claims>(token, &self.decoding_key, &self.validation)
+            .map(|data| data.claims)
+    }
+}
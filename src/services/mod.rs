@@ -1,4 +1,15 @@
 pub mod payment;
+pub mod backfill;
 pub mod cleanup;
+pub mod hold_expiry;
+pub mod scheduler;
+pub mod seat_events;
+pub mod seat_inventory;
+pub mod seat_picker;
+#[cfg(feature = "auth")]
+pub mod jwt;
 
-pub use payment::PaymentGatewayClient;
\ No newline at end of file
+pub use payment::PaymentGatewayClient;
+pub use seat_events::SeatEventHub;
+#[cfg(feature = "auth")]
+pub use jwt::JwtService;
\ No newline at end of file
@@ -16,13 +16,18 @@ use sha2::{Sha256, Digest};
 use serde::{Deserialize, Serialize};
 use std::sync::Arc;
 use tracing::{info, error, warn};
-use redis::AsyncCommands;
 use tokio::time::{Duration, Instant};
 use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
 
 use crate::{
     AppState,
-    config::PaymentConfig,
+    config::{CircuitBreakerConfig, PaymentConfig},
+    models::{
+        state_machine::{self, TransitionError},
+        BookingStatus, PaymentStatus, SeatStatus,
+    },
+    redis_client::RedisClient,
+    services::seat_events::SeatChange,
 };
 
 /// Состояния "Автоматического выключателя" (Circuit Breaker).
@@ -33,116 +38,334 @@ pub enum CircuitState {
     /// **Open (Разомкнуто)**: Режим блокировки. Запросы к сервису временно запрещены
     /// после обнаружения множественных сбоев.
     Open,
-    /// **HalfOpen (Полуоткрыто)**: Тестовый режим. После таймаута в состоянии Open,
-    /// разрешается один пробный запрос для проверки, восстановился ли сервис.
+    /// **HalfOpen (Полуоткрыто)**: Тестовый режим. После таймаута в состоянии Open
+    /// разрешается ограниченное число пробных запросов для проверки, восстановился ли сервис.
     HalfOpen,
 }
 
-/// Реализация паттерна "Автоматический выключатель" для контроля доступа к внешнему сервису.
+impl CircuitState {
+    fn from_redis(value: &str) -> Self {
+        match value {
+            "open" => CircuitState::Open,
+            "half_open" => CircuitState::HalfOpen,
+            _ => CircuitState::Closed,
+        }
+    }
+}
+
+/// Локальное (в памяти процесса) состояние выключателя.
 #[derive(Debug)]
-pub struct CircuitBreaker {
+struct LocalCircuit {
     /// Текущее состояние (Closed, Open, HalfOpen).
     state: std::sync::RwLock<CircuitState>,
     /// Счетчик последовательных сбоев.
     failure_count: AtomicU32,
-    /// Время последнего сбоя для расчета таймаута.
-    last_failure_time: AtomicU64,
+    /// Момент перехода в Open (или начала окна проб в HalfOpen),
+    /// в миллисекундах от `started` - монотонные часы процесса.
+    opened_at_ms: AtomicU64,
+    /// Сколько пробных запросов уже пропущено в HalfOpen.
+    half_open_probes: AtomicU32,
+    /// Точка отсчета монотонных часов.
+    started: Instant,
+}
+
+impl LocalCircuit {
+    fn new() -> Self {
+        Self {
+            state: std::sync::RwLock::new(CircuitState::Closed),
+            failure_count: AtomicU32::new(0),
+            opened_at_ms: AtomicU64::new(0),
+            half_open_probes: AtomicU32::new(0),
+            started: Instant::now(),
+        }
+    }
+
+    fn now_ms(&self) -> u64 {
+        self.started.elapsed().as_millis() as u64
+    }
+}
+
+/// Redis-скрипт проверки: переводит Open -> HalfOpen по таймауту и выдает пробные слоты.
+/// Время берется из Redis (`TIME`), чтобы все реплики считали таймаут одинаково.
+const CB_ACQUIRE_SCRIPT: &str = r#"
+local key = KEYS[1]
+local timeout = tonumber(ARGV[1])
+local max_probes = tonumber(ARGV[2])
+local t = redis.call('TIME')
+local now = tonumber(t[1]) * 1000 + math.floor(tonumber(t[2]) / 1000)
+local state = redis.call('HGET', key, 'state') or 'closed'
+if state == 'closed' then
+    return 1
+end
+local opened = tonumber(redis.call('HGET', key, 'opened_at') or '0')
+if state == 'open' then
+    if now - opened < timeout then
+        return 0
+    end
+    redis.call('HSET', key, 'state', 'half_open', 'probes', 0, 'opened_at', now)
+    state = 'half_open'
+    opened = now
+end
+local probes = tonumber(redis.call('HGET', key, 'probes') or '0')
+if probes >= max_probes and now - opened >= timeout then
+    -- Пробы так и не отчитались: открываем новое окно проб.
+    redis.call('HSET', key, 'probes', 0, 'opened_at', now)
+    probes = 0
+end
+if probes < max_probes then
+    redis.call('HINCRBY', key, 'probes', 1)
+    return 1
+end
+return 0
+"#;
+
+/// Redis-скрипт успешного запроса.
+const CB_SUCCESS_SCRIPT: &str = r#"
+local key = KEYS[1]
+local state = redis.call('HGET', key, 'state') or 'closed'
+if state == 'half_open' then
+    redis.call('HSET', key, 'state', 'closed', 'failures', 0, 'probes', 0)
+    return 1
+end
+if state == 'closed' then
+    redis.call('HSET', key, 'failures', 0)
+end
+return 0
+"#;
+
+/// Redis-скрипт неудачного запроса.
+const CB_FAILURE_SCRIPT: &str = r#"
+local key = KEYS[1]
+local threshold = tonumber(ARGV[1])
+local t = redis.call('TIME')
+local now = tonumber(t[1]) * 1000 + math.floor(tonumber(t[2]) / 1000)
+local state = redis.call('HGET', key, 'state') or 'closed'
+local failures = redis.call('HINCRBY', key, 'failures', 1)
+if state == 'half_open' or (state == 'closed' and failures >= threshold) then
+    redis.call('HSET', key, 'state', 'open', 'opened_at', now, 'probes', 0)
+    return 1
+end
+return 0
+"#;
+
+/// Реализация паттерна "Автоматический выключатель" для контроля доступа к внешнему сервису.
+///
+/// Один экземпляр живет в `AppState` и общий для всех запросов. В режиме `shared`
+/// состояние хранится в Redis и общее для всех реплик; при недоступности Redis
+/// выключатель продолжает работать на локальном состоянии.
+pub struct CircuitBreaker {
+    local: LocalCircuit,
+    /// Redis и ключ состояния для общего режима.
+    shared: Option<(RedisClient, String)>,
     /// Порог сбоев, после которого выключатель переходит в состояние Open.
     failure_threshold: u32,
     /// Длительность таймаута в состоянии Open, после которого происходит переход в HalfOpen.
     timeout_duration: Duration,
+    /// Сколько пробных запросов пропускается в HalfOpen.
+    half_open_max_requests: u32,
 }
 
 impl CircuitBreaker {
-    /// Создает новый экземпляр CircuitBreaker.
-    pub fn new(failure_threshold: u32, timeout_seconds: u64) -> Self {
+    /// Создает новый локальный экземпляр CircuitBreaker.
+    pub fn new(failure_threshold: u32, timeout_seconds: u64, half_open_max_requests: u32) -> Self {
         Self {
-            state: std::sync::RwLock::new(CircuitState::Closed),
-            failure_count: AtomicU32::new(0),
-            last_failure_time: AtomicU64::new(0),
+            local: LocalCircuit::new(),
+            shared: None,
             failure_threshold,
             timeout_duration: Duration::from_secs(timeout_seconds),
+            half_open_max_requests: half_open_max_requests.max(1),
         }
     }
 
+    /// Создает выключатель по настройкам; при `shared = true` состояние хранится в Redis.
+    pub fn from_config(config: &CircuitBreakerConfig, redis: &RedisClient) -> Self {
+        let mut breaker = Self::new(
+            config.failure_threshold,
+            config.timeout_seconds,
+            config.half_open_max_requests,
+        );
+        if config.shared {
+            breaker.shared = Some((redis.clone(), "circuit_breaker:payment_gateway".to_string()));
+        }
+        breaker
+    }
+
+    /// `true`, если состояние общее для всех реплик (хранится в Redis).
+    pub fn is_shared(&self) -> bool {
+        self.shared.is_some()
+    }
+
     /// Проверяет, можно ли выполнить следующий запрос к сервису.
-    pub fn can_execute(&self) -> bool {
-        let state = self.state.read().unwrap();
-        
-        match *state {
+    /// В HalfOpen каждый успешный вызов занимает один пробный слот.
+    pub async fn can_execute(&self) -> bool {
+        if let Some((redis, key)) = &self.shared {
+            let mut conn = redis.conn.clone();
+            let result: Result<i64, _> = redis::Script::new(CB_ACQUIRE_SCRIPT)
+                .key(key)
+                .arg(self.timeout_duration.as_millis() as u64)
+                .arg(self.half_open_max_requests)
+                .invoke_async(&mut conn)
+                .await;
+            match result {
+                Ok(allowed) => return allowed == 1,
+                Err(e) => warn!("Shared circuit breaker unavailable, using local state: {:?}", e),
+            }
+        }
+        self.local_can_execute()
+    }
+
+    fn local_can_execute(&self) -> bool {
+        let local = &self.local;
+        let state = local.state.read().unwrap().clone();
+        let now = local.now_ms();
+        let timeout = self.timeout_duration.as_millis() as u64;
+
+        match state {
             // Если "замкнуто", запросы всегда разрешены.
             CircuitState::Closed => true,
             // Если "разомкнуто", проверяем, прошел ли таймаут.
             CircuitState::Open => {
-                let now = Instant::now().elapsed().as_secs();
-                let last_failure = self.last_failure_time.load(Ordering::Relaxed);
-                
-                // Если с момента последнего сбоя прошло достаточно времени...
-                if now - last_failure >= self.timeout_duration.as_secs() {
-                    // ...переходим в "полуоткрытое" состояние для тестового запроса.
-                    drop(state); // Освобождаем блокировку чтения перед записью.
-                    *self.state.write().unwrap() = CircuitState::HalfOpen;
+                let opened_at = local.opened_at_ms.load(Ordering::Relaxed);
+                if now.saturating_sub(opened_at) < timeout {
+                    return false; // Таймаут еще не истек, запрос блокируется.
+                }
+
+                // ...переходим в "полуоткрытое" состояние для пробных запросов.
+                let mut state = local.state.write().unwrap();
+                if *state == CircuitState::Open {
+                    *state = CircuitState::HalfOpen;
+                    local.half_open_probes.store(0, Ordering::Relaxed);
+                    local.opened_at_ms.store(now, Ordering::Relaxed);
                     info!("Circuit breaker transitioning to HalfOpen state");
-                    true // Разрешаем тестовый запрос.
-                } else {
-                    false // Таймаут еще не истек, запрос блокируется.
                 }
+                drop(state);
+                self.local_take_probe(now, timeout)
             },
-            // В "полуоткрытом" состоянии разрешаем один пробный запрос.
-            CircuitState::HalfOpen => true,
+            // В "полуоткрытом" состоянии пропускаем ограниченное число проб.
+            CircuitState::HalfOpen => self.local_take_probe(now, timeout),
+        }
+    }
+
+    fn local_take_probe(&self, now: u64, timeout: u64) -> bool {
+        let local = &self.local;
+        // Пробы так и не отчитались за таймаут - открываем новое окно проб.
+        if local.half_open_probes.load(Ordering::Relaxed) >= self.half_open_max_requests
+            && now.saturating_sub(local.opened_at_ms.load(Ordering::Relaxed)) >= timeout
+        {
+            local.half_open_probes.store(0, Ordering::Relaxed);
+            local.opened_at_ms.store(now, Ordering::Relaxed);
         }
+
+        local
+            .half_open_probes
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |probes| {
+                (probes < self.half_open_max_requests).then_some(probes + 1)
+            })
+            .is_ok()
     }
 
     /// Регистрирует успешное выполнение запроса.
-    pub fn record_success(&self) {
-        let mut state = self.state.write().unwrap();
-        
+    pub async fn record_success(&self) {
+        if let Some((redis, key)) = &self.shared {
+            let mut conn = redis.conn.clone();
+            let result: Result<i64, _> = redis::Script::new(CB_SUCCESS_SCRIPT)
+                .key(key)
+                .invoke_async(&mut conn)
+                .await;
+            match result {
+                Ok(1) => info!("Shared circuit breaker recovered - transitioning to Closed state"),
+                Ok(_) => {},
+                Err(e) => warn!("Failed to record success in shared circuit breaker: {:?}", e),
+            }
+        }
+
+        let mut state = self.local.state.write().unwrap();
         match *state {
-            // Если тестовый запрос в HalfOpen прошел успешно, "замыкаем" цепь.
+            // Если пробный запрос в HalfOpen прошел успешно, "замыкаем" цепь.
             CircuitState::HalfOpen => {
                 *state = CircuitState::Closed;
-                self.failure_count.store(0, Ordering::Relaxed);
+                self.local.failure_count.store(0, Ordering::Relaxed);
+                self.local.half_open_probes.store(0, Ordering::Relaxed);
                 info!("Circuit breaker recovered - transitioning to Closed state");
             },
             // В обычном режиме просто сбрасываем счетчик ошибок.
             CircuitState::Closed => {
-                self.failure_count.store(0, Ordering::Relaxed);
+                self.local.failure_count.store(0, Ordering::Relaxed);
             },
             _ => {} // В состоянии Open ничего не делаем.
         }
     }
 
     /// Регистрирует неудачное выполнение запроса.
-    pub fn record_failure(&self) {
-        let failure_count = self.failure_count.fetch_add(1, Ordering::Relaxed) + 1;
-        self.last_failure_time.store(
-            Instant::now().elapsed().as_secs(),
-            Ordering::Relaxed
-        );
+    pub async fn record_failure(&self) {
+        if let Some((redis, key)) = &self.shared {
+            let mut conn = redis.conn.clone();
+            let result: Result<i64, _> = redis::Script::new(CB_FAILURE_SCRIPT)
+                .key(key)
+                .arg(self.failure_threshold)
+                .invoke_async(&mut conn)
+                .await;
+            match result {
+                Ok(1) => error!("Shared circuit breaker OPENED"),
+                Ok(_) => {},
+                Err(e) => warn!("Failed to record failure in shared circuit breaker: {:?}", e),
+            }
+        }
+
+        let local = &self.local;
+        let failure_count = local.failure_count.fetch_add(1, Ordering::Relaxed) + 1;
+        let mut state = local.state.write().unwrap();
 
-        let mut state = self.state.write().unwrap();
-        
         match *state {
             // Если в "замкнутом" состоянии достигнут порог ошибок, "размыкаем" цепь.
-            CircuitState::Closed => {
-                if failure_count >= self.failure_threshold {
-                    *state = CircuitState::Open;
-                    error!("Circuit breaker OPENED - {} failures reached threshold {}",
-                          failure_count, self.failure_threshold);
-                }
+            CircuitState::Closed if failure_count >= self.failure_threshold => {
+                *state = CircuitState::Open;
+                local.opened_at_ms.store(local.now_ms(), Ordering::Relaxed);
+                error!("Circuit breaker OPENED - {} failures reached threshold {}",
+                      failure_count, self.failure_threshold);
             },
-            // Если тестовый запрос в HalfOpen провалился, возвращаемся в Open.
+            // Если пробный запрос в HalfOpen провалился, возвращаемся в Open.
             CircuitState::HalfOpen => {
                 *state = CircuitState::Open;
+                local.opened_at_ms.store(local.now_ms(), Ordering::Relaxed);
+                local.half_open_probes.store(0, Ordering::Relaxed);
                 warn!("Circuit breaker test failed - returning to Open state");
             },
             _ => {}
         }
     }
 
+    /// Возвращает текущее состояние и число последовательных сбоев для мониторинга.
+    /// Не занимает пробных слотов.
+    pub async fn status(&self) -> (CircuitState, u32) {
+        if let Some((redis, key)) = &self.shared {
+            let mut conn = redis.conn.clone();
+            let result: Result<(Option<String>, Option<u32>), _> = redis::cmd("HMGET")
+                .arg(key)
+                .arg("state")
+                .arg("failures")
+                .query_async(&mut conn)
+                .await;
+            match result {
+                Ok((state, failures)) => {
+                    return (
+                        CircuitState::from_redis(state.as_deref().unwrap_or("closed")),
+                        failures.unwrap_or(0),
+                    );
+                }
+                Err(e) => warn!("Failed to read shared circuit breaker state: {:?}", e),
+            }
+        }
+
+        (
+            self.local.state.read().unwrap().clone(),
+            self.local.failure_count.load(Ordering::Relaxed),
+        )
+    }
+
     /// Возвращает текущее состояние выключателя для мониторинга.
-    pub fn get_state(&self) -> CircuitState {
-        self.state.read().unwrap().clone()
+    pub async fn get_state(&self) -> CircuitState {
+        self.status().await.0
     }
 }
 
@@ -263,17 +486,14 @@ pub struct PaymentGatewayClient {
     base_url: String,
     /// Асинхронный HTTP-клиент.
     http_client: reqwest::Client,
-    /// Экземпляр Circuit Breaker для этого клиента.
+    /// Общий для всего приложения Circuit Breaker (из `AppState`).
     circuit_breaker: Arc<CircuitBreaker>,
 }
 
 impl PaymentGatewayClient {
     /// Создает и конфигурирует клиент на основе настроек приложения.
     pub fn from_config(config: &PaymentConfig, state: Arc<AppState>) -> Self {
-        let circuit_breaker = Arc::new(CircuitBreaker::new(
-            state.config.circuit_breaker.failure_threshold,
-            state.config.circuit_breaker.timeout_seconds,
-        ));
+        let circuit_breaker = state.circuit_breaker.clone();
 
         Self {
             state,
@@ -294,7 +514,7 @@ impl PaymentGatewayClient {
         F: std::future::Future<Output = Result<T, reqwest::Error>>,
     {
         // Перед выполнением запроса проверяем состояние выключателя.
-        if !self.circuit_breaker.can_execute() {
+        if !self.circuit_breaker.can_execute().await {
             warn!("Circuit breaker is OPEN - blocking payment gateway request");
             return Err(CircuitBreakerError::Open);
         }
@@ -302,13 +522,13 @@ impl PaymentGatewayClient {
         match operation.await {
             // Если операция успешна, сообщаем об этом выключателю.
             Ok(result) => {
-                self.circuit_breaker.record_success();
+                self.circuit_breaker.record_success().await;
                 Ok(result)
             },
             // В случае ошибки, также сообщаем об этом для обновления счетчика сбоев.
             Err(e) => {
                 error!("Payment gateway request failed: {:?}", e);
-                self.circuit_breaker.record_failure();
+                self.circuit_breaker.record_failure().await;
                 Err(CircuitBreakerError::PaymentGatewayError(e))
             }
         }
@@ -337,6 +557,7 @@ impl PaymentGatewayClient {
     }
 
     /// Создаёт платёж в платёжной системе, используя защиту Circuit Breaker.
+    #[allow(clippy::too_many_arguments)]
     pub async fn create_payment(
         &self,
         amount: i64,
@@ -365,12 +586,12 @@ impl PaymentGatewayClient {
         };
 
         info!("Creating payment with circuit breaker: amount={}, currency={}", amount, currency);
-        info!("Circuit breaker state: {:?}", self.circuit_breaker.get_state());
+        info!("Circuit breaker state: {:?}", self.circuit_breaker.get_state().await);
 
         let operation = async {
             self
                 .http_client
-                .post(&format!("{}/api/v1/PaymentInit/init", self.base_url))
+                .post(format!("{}/api/v1/PaymentInit/init", self.base_url))
                 .json(&request)
                 .send()
                 .await?
@@ -396,7 +617,7 @@ impl PaymentGatewayClient {
         let operation = async {
             self
                 .http_client
-                .post(&format!("{}/api/v1/PaymentCheck/check", self.base_url))
+                .post(format!("{}/api/v1/PaymentCheck/check", self.base_url))
                 .json(&request)
                 .send()
                 .await?
@@ -431,7 +652,7 @@ impl PaymentGatewayClient {
         let operation = async {
             self
                 .http_client
-                .post(&format!("{}/api/v1/PaymentConfirm/confirm", self.base_url))
+                .post(format!("{}/api/v1/PaymentConfirm/confirm", self.base_url))
                 .json(&request)
                 .send()
                 .await?
@@ -443,24 +664,13 @@ impl PaymentGatewayClient {
     }
 
     /// Возвращает текущее состояние Circuit Breaker для мониторинга.
-    pub fn get_circuit_breaker_status(&self) -> (CircuitState, u32) {
-        (
-            self.circuit_breaker.get_state(),
-            self.circuit_breaker.failure_count.load(Ordering::Relaxed),
-        )
+    pub async fn get_circuit_breaker_status(&self) -> (CircuitState, u32) {
+        self.circuit_breaker.status().await
     }
 
     /// Очищает временные блокировки мест в Redis.
     pub async fn clear_redis_reservations(&self, seat_ids: &[i64]) {
-        if seat_ids.is_empty() {
-            return;
-        }
-
-        let mut redis = self.state.redis.clone();
-        let keys: Vec<String> = seat_ids.iter()
-            .map(|id| format!("seat:{}", id))
-            .collect();
-        let _: Result<i64, _> = redis.conn.del(keys).await;
+        self.state.cache.release_seat_holds(seat_ids).await;
     }
 
     /// Фоновый процесс для очистки "зависших" и просроченных платежей.
@@ -471,10 +681,11 @@ impl PaymentGatewayClient {
             SELECT pt.transaction_id, b.id, b.event_id
             FROM payment_transactions pt
             JOIN bookings b ON b.id = pt.booking_id
-            WHERE pt.status = 'pending'
+            WHERE pt.status = $1
               AND pt.created_at < NOW() - interval '15 minutes'
             "#
         )
+        .bind(PaymentStatus::Pending)
         .fetch_all(&self.state.db.pool)
         .await
         .unwrap_or_default();
@@ -482,7 +693,7 @@ impl PaymentGatewayClient {
         for (payment_id, booking_id, event_id) in expired {
             // Перед тем как отменить платеж, делаем последнюю попытку проверить его статус через API,
             // но только если Circuit Breaker не в состоянии Open.
-            if self.circuit_breaker.can_execute() {
+            if self.circuit_breaker.get_state().await != CircuitState::Open {
                 if let Ok(check_response) = self.check_payment_status(&payment_id).await {
                     if check_response.success {
                         if let Some(status) = &check_response.status {
@@ -517,28 +728,29 @@ impl PaymentGatewayClient {
             }
         };
 
-        // Помечаем платеж как 'expired'.
-        sqlx::query("UPDATE payment_transactions SET status = 'expired' WHERE transaction_id = $1")
-            .bind(&payment_id)
-            .execute(&mut *tx)
-            .await.ok();
-
-        // Освобождаем зарезервированные места.
-        let seats: Vec<i64> = sqlx::query_scalar("UPDATE seats SET status = 'AVAILABLE', booking_id = NULL WHERE booking_id = $1 AND status = 'RESERVED' RETURNING id")
-            .bind(booking_id)
-            .fetch_all(&mut *tx)
-            .await.unwrap_or_default();
-
-        // Удаляем бронирование.
-        sqlx::query("DELETE FROM bookings WHERE id = $1")
-            .bind(booking_id)
-            .execute(&mut *tx)
-            .await.ok();
+        // Помечаем платеж как 'expired', освобождаем места и отменяем бронирование
+        // (удалить нельзя - на него ссылается платеж).
+        let result = async {
+            state_machine::transition_payment(&mut tx, &payment_id, PaymentStatus::Expired).await?;
+            let seats = state_machine::transition_booking_seats(&mut tx, booking_id, SeatStatus::Free).await?;
+            state_machine::transition_booking(&mut tx, booking_id, BookingStatus::Cancelled).await?;
+            Ok::<_, TransitionError>(seats)
+        }.await;
+
+        let seats = match result {
+            Ok(seats) => seats,
+            Err(e) => {
+                warn!("Expired payment {} cleanup rejected: {}", payment_id, e);
+                let _ = tx.rollback().await;
+                return;
+            }
+        };
 
         // Если транзакция прошла успешно, очищаем кэши.
         if tx.commit().await.is_ok() {
             self.clear_redis_reservations(&seats).await;
             self.state.cache.invalidate_seats(event_id).await;
+            self.state.seat_events.publish(event_id, &seats, SeatChange::Expired).await;
             info!("Expired payment {} cleaned up, {} seats released", payment_id, seats.len());
         }
     }
@@ -554,24 +766,51 @@ impl PaymentGatewayClient {
         };
 
         // 1. Обновляем статус транзакции.
-        sqlx::query("UPDATE payment_transactions SET status = 'completed' WHERE transaction_id = $1")
-            .bind(payment_id)
-            .execute(&mut *tx).await.ok();
-
         // 2. Обновляем статус бронирования.
-        sqlx::query("UPDATE bookings SET status = 'paid' WHERE id = $1")
-            .bind(booking_id)
-            .execute(&mut *tx).await.ok();
-
         // 3. Помечаем места как проданные ('SOLD').
-        let seats: Vec<i64> = sqlx::query_scalar("UPDATE seats SET status = 'SOLD' WHERE booking_id = $1 AND status = 'RESERVED' RETURNING id")
-            .bind(booking_id)
-            .fetch_all(&mut *tx).await.unwrap_or_default();
+        // Повторное уведомление об уже обработанном платеже отклоняется машиной состояний.
+        let result = async {
+            state_machine::transition_payment(&mut tx, payment_id, PaymentStatus::Completed).await?;
+            state_machine::transition_booking(&mut tx, booking_id, BookingStatus::Paid).await?;
+            state_machine::transition_booking_seats(&mut tx, booking_id, SeatStatus::Sold).await
+        }.await;
+
+        let seats = match result {
+            Ok(seats) => seats,
+            Err(e) => {
+                warn!("Successful payment {} not applied: {}", payment_id, e);
+                let _ = tx.rollback().await;
+                return;
+            }
+        };
 
         if tx.commit().await.is_ok() {
             self.clear_redis_reservations(&seats).await;
             self.state.cache.invalidate_seats(event_id).await;
+            self.state.seat_events.publish(event_id, &seats, SeatChange::Sold).await;
             info!("Payment {} completed, {} seats sold", payment_id, seats.len());
+            self.invalidate_search_if_sold_out(event_id).await;
+        }
+    }
+
+    /// Сбрасывает закешированные результаты поиска с событием, если продано последнее место.
+    async fn invalidate_search_if_sold_out(&self, event_id: i64) {
+        let sold_out = sqlx::query_scalar::<_, bool>(
+            "SELECT NOT EXISTS(SELECT 1 FROM seats WHERE event_id = $1 AND status <> $2)"
+        )
+        .bind(event_id)
+        .bind(SeatStatus::Sold)
+        .fetch_one(&self.state.db.pool)
+        .await;
+
+        match sold_out {
+            Ok(true) => {
+                if let Err(e) = self.state.cache.invalidate_event_search(event_id).await {
+                    warn!("Failed to invalidate search cache for sold out event {}: {:?}", event_id, e);
+                }
+            }
+            Ok(false) => {}
+            Err(e) => warn!("Failed to check whether event {} is sold out: {}", event_id, e),
         }
     }
 
@@ -586,23 +825,28 @@ impl PaymentGatewayClient {
         };
 
         // 1. Обновляем статус транзакции.
-        sqlx::query("UPDATE payment_transactions SET status = 'failed' WHERE transaction_id = $1")
-            .bind(payment_id)
-            .execute(&mut *tx).await.ok();
-
         // 2. Освобождаем места.
-        let seats: Vec<i64> = sqlx::query_scalar("UPDATE seats SET status = 'AVAILABLE', booking_id = NULL WHERE booking_id = $1 AND status = 'RESERVED' RETURNING id")
-            .bind(booking_id)
-            .fetch_all(&mut *tx).await.unwrap_or_default();
-
-        // 3. Удаляем бронирование.
-        sqlx::query("DELETE FROM bookings WHERE id = $1")
-            .bind(booking_id)
-            .execute(&mut *tx).await.ok();
+        // 3. Отменяем бронирование (удалить нельзя - на него ссылается платеж).
+        let result = async {
+            state_machine::transition_payment(&mut tx, payment_id, PaymentStatus::Failed).await?;
+            let seats = state_machine::transition_booking_seats(&mut tx, booking_id, SeatStatus::Free).await?;
+            state_machine::transition_booking(&mut tx, booking_id, BookingStatus::Cancelled).await?;
+            Ok::<_, TransitionError>(seats)
+        }.await;
+
+        let seats = match result {
+            Ok(seats) => seats,
+            Err(e) => {
+                warn!("Failed payment {} not applied: {}", payment_id, e);
+                let _ = tx.rollback().await;
+                return;
+            }
+        };
 
         if tx.commit().await.is_ok() {
             self.clear_redis_reservations(&seats).await;
             self.state.cache.invalidate_seats(event_id).await;
+            self.state.seat_events.publish(event_id, &seats, SeatChange::Released).await;
             info!("Payment {} failed, {} seats released", payment_id, seats.len());
         }
     }
@@ -635,7 +879,7 @@ impl PaymentGatewayClient {
             },
             "AUTHORIZED" => {
                 // Пытаемся автоматически подтвердить платёж, если это возможно.
-                if self.circuit_breaker.can_execute() {
+                if self.circuit_breaker.get_state().await != CircuitState::Open {
                     if let Ok(check) = self.check_payment_status(payment_id).await {
                         if let (Some(amount), Some(currency), Some(order_id)) =
                             (check.amount, check.currency, check.order_id) {
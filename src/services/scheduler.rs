@@ -0,0 +1,233 @@
+//! scheduler.rs
+//!
+//! Периодический запуск задач `CleanupService`.
+//!
+//! Каждая задача очистки работает на своем интервале из `CleanupConfig`,
+//! включая освобождение мест с истекшим удержанием (`HoldExpiryService`),
+//! обновление `events_upcoming`, создание будущих секций `events_archive`
+//! и пачки фоновых переносов данных (`BackfillService`).
+//! Выполняет их только реплика, держащая лидерскую блокировку в Redis,
+//! остальные реплики лишь пытаются ее перехватить. Пока задача идет, блокировка
+//! продлевается; если продлить не удалось, задача прерывается. При остановке сервера
+//! планировщик дожидается текущей задачи и освобождает блокировку.
+
+use std::{future::Future, sync::Arc, time::Duration};
+use tokio::{sync::watch, task::JoinHandle, time::MissedTickBehavior};
+use tracing::{error, info, warn};
+use uuid::Uuid;
+
+use crate::{
+    AppState,
+    services::{backfill::BackfillService, cleanup::CleanupService, hold_expiry::HoldExpiryService},
+};
+
+/// Ключ лидерской блокировки очистки в Redis.
+const LEADER_LOCK_KEY: &str = "cleanup:leader";
+
+/// Хэндл запущенного планировщика для корректной остановки.
+pub struct SchedulerHandle {
+    shutdown: watch::Sender<bool>,
+    task: JoinHandle<()>,
+}
+
+impl SchedulerHandle {
+    /// Сигнализирует планировщику остановиться и ждет завершения.
+    pub async fn shutdown(self) {
+        let _ = self.shutdown.send(true);
+        if let Err(e) = self.task.await {
+            error!("Cleanup scheduler task failed: {:?}", e);
+        }
+    }
+}
+
+pub struct CleanupScheduler {
+    state: Arc<AppState>,
+    service: CleanupService,
+    holds: HoldExpiryService,
+    backfill: BackfillService,
+    /// Уникальный ID этой реплики - значение лидерской блокировки.
+    instance_id: String,
+    is_leader: bool,
+}
+
+impl CleanupScheduler {
+    /// Запускает планировщик в фоне. Если очистка выключена, задача сразу завершается.
+    pub fn start(state: Arc<AppState>) -> SchedulerHandle {
+        let (shutdown, shutdown_rx) = watch::channel(false);
+        let scheduler = Self {
+            service: CleanupService::new(state.clone()),
+            holds: HoldExpiryService::new(state.clone()),
+            backfill: BackfillService::new(state.clone()),
+            state,
+            instance_id: Uuid::new_v4().to_string(),
+            is_leader: false,
+        };
+        let task = tokio::spawn(scheduler.run(shutdown_rx));
+        SchedulerHandle { shutdown, task }
+    }
+
+    async fn run(mut self, mut shutdown: watch::Receiver<bool>) {
+        let config = self.state.config.cleanup.clone();
+        if !config.enabled {
+            info!("🧹 Cleanup scheduler disabled");
+            return;
+        }
+
+        info!(
+            "🧹 Cleanup scheduler started (instance {}): payments every {}s, bookings every {}s, redis reserves every {}s, seat holds every {}s (sweep {}s), upcoming events every {}s, partitions every {}s, backfill every {}s",
+            self.instance_id,
+            config.payments_interval_seconds,
+            config.bookings_interval_seconds,
+            config.redis_reserves_interval_seconds,
+            config.holds_interval_seconds,
+            config.holds_sweep_interval_seconds,
+            config.upcoming_refresh_interval_seconds,
+            config.partitions_interval_seconds,
+            config.backfill_interval_seconds,
+        );
+
+        let mut payments = interval(config.payments_interval_seconds);
+        let mut bookings = interval(config.bookings_interval_seconds);
+        let mut redis_reserves = interval(config.redis_reserves_interval_seconds);
+        let mut holds = interval(config.holds_interval_seconds);
+        let mut holds_sweep = interval(config.holds_sweep_interval_seconds);
+        let mut upcoming = interval(config.upcoming_refresh_interval_seconds);
+        let mut partitions = interval(config.partitions_interval_seconds);
+        let mut backfill = interval(config.backfill_interval_seconds);
+        // Лидерство продлеваем заметно чаще, чем истекает TTL.
+        let mut leadership = interval((config.leader_lock_ttl_seconds / 3).max(1));
+
+        loop {
+            tokio::select! {
+                _ = shutdown.changed() => break,
+                _ = leadership.tick() => {
+                    self.ensure_leader().await;
+                },
+                _ = payments.tick() => {
+                    if self.ensure_leader().await && !self.while_leader(self.service.cleanup_expired_payments()).await {
+                        self.step_down();
+                    }
+                },
+                _ = bookings.tick() => {
+                    if self.ensure_leader().await && !self.while_leader(self.service.cleanup_expired_bookings()).await {
+                        self.step_down();
+                    }
+                },
+                _ = redis_reserves.tick() => {
+                    if self.ensure_leader().await && !self.while_leader(self.service.cleanup_orphaned_redis_reserves()).await {
+                        self.step_down();
+                    }
+                },
+                _ = holds.tick() => {
+                    if self.ensure_leader().await && !self.while_leader(self.holds.release_expired_holds()).await {
+                        self.step_down();
+                    }
+                },
+                _ = holds_sweep.tick() => {
+                    if self.ensure_leader().await && !self.while_leader(self.holds.sweep_lapsed_holds()).await {
+                        self.step_down();
+                    }
+                },
+                _ = upcoming.tick() => {
+                    if self.ensure_leader().await && !self.while_leader(self.refresh_upcoming_events()).await {
+                        self.step_down();
+                    }
+                },
+                _ = partitions.tick() => {
+                    if self.ensure_leader().await && !self.while_leader(self.ensure_event_partitions(config.partitions_ahead_years)).await {
+                        self.step_down();
+                    }
+                },
+                _ = backfill.tick() => {
+                    if self.ensure_leader().await && !self.while_leader(self.backfill.run_pending(config.backfill_batch_size)).await {
+                        self.step_down();
+                    }
+                },
+            }
+        }
+
+        if self.is_leader {
+            if let Err(e) = self.state.cache.release_leader_lock(LEADER_LOCK_KEY, &self.instance_id).await {
+                warn!("Failed to release cleanup leader lock: {:?}", e);
+            }
+        }
+        info!("🧹 Cleanup scheduler stopped");
+    }
+
+    async fn refresh_upcoming_events(&self) {
+        if let Err(e) = self.state.db.refresh_upcoming_events().await {
+            error!("📅 Failed to refresh upcoming events: {}", e);
+        }
+    }
+
+    async fn ensure_event_partitions(&self, years_ahead: u32) {
+        match self.state.db.ensure_event_partitions(years_ahead).await {
+            Ok(0) => {}
+            Ok(created) => info!("📅 Created {} events_archive partitions", created),
+            Err(e) => error!("📅 Failed to create events_archive partitions: {}", e),
+        }
+    }
+
+    /// Выполняет задачу лидера, продлевая блокировку каждые TTL/3, пока задача
+    /// идет. Если продлить не удалось (блокировку перехватила другая реплика
+    /// или Redis недоступен), задача прерывается, чтобы две реплики не чистили
+    /// одновременно. Возвращает `false`, если задача прервана.
+    async fn while_leader(&self, task: impl Future<Output = ()>) -> bool {
+        let ttl = self.state.config.cleanup.leader_lock_ttl_seconds;
+        let renew_every = Duration::from_secs((ttl / 3).max(1));
+        let renew = async {
+            loop {
+                tokio::time::sleep(renew_every).await;
+                match self.state.cache.acquire_leader_lock(LEADER_LOCK_KEY, &self.instance_id, ttl).await {
+                    Ok(true) => {}
+                    Ok(false) => return,
+                    Err(e) => {
+                        warn!("Failed to renew cleanup leader lock: {:?}", e);
+                        return;
+                    }
+                }
+            }
+        };
+
+        tokio::select! {
+            _ = task => true,
+            _ = renew => false,
+        }
+    }
+
+    /// Отмечает потерю лидерства во время задачи.
+    fn step_down(&mut self) {
+        warn!("🧹 Instance {} lost cleanup leadership during a task, task aborted", self.instance_id);
+        self.is_leader = false;
+    }
+
+    /// Захватывает или продлевает лидерство. При ошибке Redis считаем, что мы не лидер,
+    /// чтобы две реплики не чистили одновременно.
+    async fn ensure_leader(&mut self) -> bool {
+        let ttl = self.state.config.cleanup.leader_lock_ttl_seconds;
+        let is_leader = match self.state.cache.acquire_leader_lock(LEADER_LOCK_KEY, &self.instance_id, ttl).await {
+            Ok(acquired) => acquired,
+            Err(e) => {
+                warn!("Failed to acquire cleanup leader lock: {:?}", e);
+                false
+            }
+        };
+
+        if is_leader != self.is_leader {
+            if is_leader {
+                info!("🧹 Instance {} became cleanup leader", self.instance_id);
+            } else {
+                info!("🧹 Instance {} lost cleanup leadership", self.instance_id);
+            }
+            self.is_leader = is_leader;
+        }
+        is_leader
+    }
+}
+
+/// Интервал без "догоняющих" тиков после долгой задачи.
+fn interval(seconds: u64) -> tokio::time::Interval {
+    let mut interval = tokio::time::interval(Duration::from_secs(seconds.max(1)));
+    interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    interval
+}
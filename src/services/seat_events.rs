@@ -0,0 +1,163 @@
+//! seat_events.rs
+//!
+//! Рассылка изменений статусов мест в реальном времени.
+//!
+//! Код, меняющий статус мест, публикует `SeatUpdate` в Redis-канал
+//! `seat_updates:{event_id}`. Каждая реплика держит одну подписку на
+//! `seat_updates:*` и раздает сообщения своим WebSocket-клиентам через
+//! `tokio::sync::broadcast`, поэтому клиент видит изменения, сделанные на
+//! любой реплике.
+
+use futures::StreamExt;
+use redis::AsyncCommands;
+use serde::{Deserialize, Serialize};
+use std::{sync::Arc, time::Duration};
+use tokio::sync::broadcast;
+use tracing::{error, info, warn};
+
+use crate::{models::SeatStatus, redis_client::RedisClient};
+
+/// Префикс каналов pub/sub с изменениями мест.
+const CHANNEL_PREFIX: &str = "seat_updates:";
+
+/// Сколько сообщений может отстать медленный WebSocket-клиент, прежде чем
+/// ему придется перечитать карту мест целиком.
+const BROADCAST_CAPACITY: usize = 1024;
+
+/// Пауза перед повторной подпиской после обрыва соединения с Redis.
+const RESUBSCRIBE_DELAY: Duration = Duration::from_secs(1);
+
+/// Что произошло с местами.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SeatChange {
+    /// Место удерживается в Redis, но еще не записано в бронь.
+    Selected,
+    /// Место добавлено в бронь.
+    Reserved,
+    /// Пользователь освободил место или бронь отменена.
+    Released,
+    /// Бронь оплачена.
+    Sold,
+    /// Удержание или неоплаченная бронь истекли.
+    Expired,
+}
+
+impl SeatChange {
+    /// Статус мест после изменения.
+    pub fn status(self) -> SeatStatus {
+        match self {
+            SeatChange::Selected => SeatStatus::Selected,
+            SeatChange::Reserved => SeatStatus::Reserved,
+            SeatChange::Released | SeatChange::Expired => SeatStatus::Free,
+            SeatChange::Sold => SeatStatus::Sold,
+        }
+    }
+}
+
+/// Сообщение об изменении группы мест одного события.
+/// В JSON содержит `"type": "update"`, чтобы клиент отличал его от снимка.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename = "update")]
+pub struct SeatUpdate {
+    pub event_id: i64,
+    pub seat_ids: Vec<i64>,
+    pub change: SeatChange,
+    pub status: SeatStatus,
+    /// Время изменения, миллисекунды Unix.
+    pub at: i64,
+}
+
+/// Сообщение, полученное из Redis: ID события и готовый JSON для клиентов.
+#[derive(Debug, Clone)]
+pub struct SeatUpdateMessage {
+    pub event_id: i64,
+    pub payload: Arc<str>,
+}
+
+#[derive(Clone)]
+pub struct SeatEventHub {
+    redis: RedisClient,
+    sender: broadcast::Sender<SeatUpdateMessage>,
+}
+
+impl SeatEventHub {
+    pub fn new(redis: RedisClient) -> Self {
+        let (sender, _) = broadcast::channel(BROADCAST_CAPACITY);
+        Self { redis, sender }
+    }
+
+    /// Публикует изменение мест. Ошибки только логируются: рассылка не должна
+    /// влиять на результат операции, которая уже зафиксирована в БД.
+    pub async fn publish(&self, event_id: i64, seat_ids: &[i64], change: SeatChange) {
+        if seat_ids.is_empty() {
+            return;
+        }
+
+        let update = SeatUpdate {
+            event_id,
+            seat_ids: seat_ids.to_vec(),
+            change,
+            status: change.status(),
+            at: chrono::Utc::now().timestamp_millis(),
+        };
+        let payload = match serde_json::to_string(&update) {
+            Ok(payload) => payload,
+            Err(e) => {
+                error!("Failed to serialize seat update: {:?}", e);
+                return;
+            }
+        };
+
+        let mut conn = self.redis.conn.clone();
+        let channel = format!("{}{}", CHANNEL_PREFIX, event_id);
+        if let Err(e) = conn.publish::<_, _, i64>(channel, payload).await {
+            warn!("Failed to publish seat update for event {}: {:?}", event_id, e);
+        }
+    }
+
+    /// Подписка на изменения мест этой реплики.
+    pub fn subscribe(&self) -> broadcast::Receiver<SeatUpdateMessage> {
+        self.sender.subscribe()
+    }
+
+    /// Запускает фоновую подписку на Redis. При обрыве соединения
+    /// подписка восстанавливается; изменения за время обрыва теряются,
+    /// клиенты получают их при следующем снимке карты мест.
+    pub fn start_listener(&self) {
+        let hub = self.clone();
+        tokio::spawn(async move {
+            loop {
+                if let Err(e) = hub.listen().await {
+                    warn!("Seat updates subscription failed: {:?}", e);
+                }
+                tokio::time::sleep(RESUBSCRIBE_DELAY).await;
+            }
+        });
+    }
+
+    async fn listen(&self) -> redis::RedisResult<()> {
+        let mut pubsub = self.redis.client.get_async_pubsub().await?;
+        pubsub.psubscribe(format!("{}*", CHANNEL_PREFIX)).await?;
+        info!("📡 Subscribed to seat updates");
+
+        let mut messages = pubsub.on_message();
+        while let Some(message) = messages.next().await {
+            let event_id = message
+                .get_channel_name()
+                .strip_prefix(CHANNEL_PREFIX)
+                .and_then(|id| id.parse::<i64>().ok());
+            let (Some(event_id), Ok(payload)) = (event_id, message.get_payload::<String>()) else {
+                continue;
+            };
+            // Ошибка отправки означает только отсутствие подписчиков.
+            let _ = self.sender.send(SeatUpdateMessage {
+                event_id,
+                payload: payload.into(),
+            });
+        }
+
+        warn!("Seat updates subscription closed");
+        Ok(())
+    }
+}
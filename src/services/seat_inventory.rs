@@ -0,0 +1,327 @@
+//! seat_inventory.rs
+//!
+//! Генерация мест события по шаблону зала.
+//!
+//! Шаблон задает число рядов, мест в ряду, зоны (диапазоны рядов с категорией)
+//! и цену каждой категории. Места загружаются через COPY во временную таблицу
+//! и переносятся в `seats` с `ON CONFLICT DO NOTHING`, поэтому повторный запуск
+//! того же шаблона ничего не дублирует, а лишь досоздает недостающие места.
+//!
+//! Место определяется ограничением `seats_event_section_row_number_key`
+//! (миграция 003), а не `UNIQUE(event_id, row, number)`: в разных секциях
+//! одного зала ряды и номера повторяются. Для зала без схемы (`section_id`
+//! NULL) ограничение совпадает с `(event_id, row, number)` благодаря
+//! `NULLS NOT DISTINCT`.
+
+use serde::Deserialize;
+use sqlx::PgConnection;
+use std::{collections::HashMap, fmt::Write};
+
+use crate::models::{BookingStatus, SeatStatus};
+
+/// Максимум мест, которые можно создать одним шаблоном.
+pub const MAX_TEMPLATE_SEATS: usize = 100_000;
+
+/// Максимальная цена, которая помещается в `DECIMAL(10, 2)`.
+const MAX_PRICE: f64 = 99_999_999.99;
+
+/// Длина `seats.category`.
+const MAX_CATEGORY_LEN: usize = 50;
+
+#[derive(Debug, thiserror::Error)]
+pub enum InventoryError {
+    #[error("{0}")]
+    InvalidTemplate(String),
+    #[error("seat inventory query failed: {0}")]
+    Database(#[from] sqlx::Error),
+}
+
+/// Шаблон зала.
+#[derive(Debug, Clone, Deserialize)]
+pub struct SeatTemplate {
+    /// Секция, к которой относятся места; `None` - зал без схемы.
+    pub section_id: Option<i64>,
+    /// Число рядов, ряды нумеруются с 1.
+    pub rows: i32,
+    /// Мест в ряду, места нумеруются с 1. Зона может переопределить значение.
+    pub seats_per_row: i32,
+    pub zones: Vec<SeatZone>,
+    /// Цена каждой категории из `zones`.
+    pub prices: HashMap<String, f64>,
+}
+
+/// Диапазон рядов одной категории (границы включительно).
+#[derive(Debug, Clone, Deserialize)]
+pub struct SeatZone {
+    pub category: String,
+    pub from_row: i32,
+    pub to_row: i32,
+    pub seats_per_row: Option<i32>,
+}
+
+/// Место, которое создается по шаблону.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TemplateSeat {
+    pub row: i32,
+    pub number: i32,
+    pub category: String,
+    pub price: f64,
+}
+
+/// Результат загрузки мест.
+#[derive(Debug, Clone, Copy)]
+pub struct GenerateReport {
+    /// Сколько мест описывает шаблон.
+    pub total: usize,
+    /// Сколько мест создано; остальные уже были.
+    pub created: u64,
+}
+
+fn invalid(message: impl Into<String>) -> InventoryError {
+    InventoryError::InvalidTemplate(message.into())
+}
+
+fn validate_price(category: &str, price: f64) -> Result<(), InventoryError> {
+    if !price.is_finite() || !(0.0..=MAX_PRICE).contains(&price) {
+        return Err(invalid(format!("Некорректная цена категории {}", category)));
+    }
+    Ok(())
+}
+
+impl SeatTemplate {
+    /// Проверяет шаблон и разворачивает его в список мест.
+    /// Каждый ряд должен попадать ровно в одну зону.
+    pub fn seats(&self) -> Result<Vec<TemplateSeat>, InventoryError> {
+        if self.rows <= 0 || self.seats_per_row <= 0 {
+            return Err(invalid("rows и seats_per_row должны быть > 0"));
+        }
+        if self.zones.is_empty() {
+            return Err(invalid("Шаблон должен содержать хотя бы одну зону"));
+        }
+
+        let mut zone_by_row: Vec<Option<&SeatZone>> = vec![None; self.rows as usize];
+        for zone in &self.zones {
+            let category = zone.category.trim();
+            if category.is_empty() || category.chars().count() > MAX_CATEGORY_LEN {
+                return Err(invalid(format!(
+                    "Категория зоны должна быть от 1 до {} символов",
+                    MAX_CATEGORY_LEN
+                )));
+            }
+            let price = self
+                .prices
+                .get(category)
+                .ok_or_else(|| invalid(format!("Нет цены для категории {}", category)))?;
+            validate_price(category, *price)?;
+            if zone.from_row < 1 || zone.to_row > self.rows || zone.from_row > zone.to_row {
+                return Err(invalid(format!(
+                    "Зона {}: ряды должны быть в диапазоне 1..={}",
+                    category, self.rows
+                )));
+            }
+            if zone.seats_per_row.is_some_and(|n| n <= 0) {
+                return Err(invalid(format!("Зона {}: seats_per_row должен быть > 0", category)));
+            }
+            for row in zone.from_row..=zone.to_row {
+                let slot = &mut zone_by_row[(row - 1) as usize];
+                if slot.is_some() {
+                    return Err(invalid(format!("Ряд {} входит в несколько зон", row)));
+                }
+                *slot = Some(zone);
+            }
+        }
+
+        let mut total = 0usize;
+        for (index, zone) in zone_by_row.iter().enumerate() {
+            let zone = zone.ok_or_else(|| invalid(format!("Ряд {} не входит ни в одну зону", index + 1)))?;
+            total += zone.seats_per_row.unwrap_or(self.seats_per_row) as usize;
+            if total > MAX_TEMPLATE_SEATS {
+                return Err(invalid(format!("Шаблон описывает больше {} мест", MAX_TEMPLATE_SEATS)));
+            }
+        }
+
+        let mut seats = Vec::with_capacity(total);
+        for (index, zone) in zone_by_row.into_iter().flatten().enumerate() {
+            let category = zone.category.trim();
+            let price = self.prices[category];
+            for number in 1..=zone.seats_per_row.unwrap_or(self.seats_per_row) {
+                seats.push(TemplateSeat {
+                    row: index as i32 + 1,
+                    number,
+                    category: category.to_string(),
+                    price,
+                });
+            }
+        }
+        Ok(seats)
+    }
+}
+
+/// Загружает места события. Вызывается внутри транзакции: временная таблица
+/// удаляется при ее завершении.
+pub async fn generate_seats(
+    conn: &mut PgConnection,
+    event_id: i64,
+    template: &SeatTemplate,
+) -> Result<GenerateReport, InventoryError> {
+    let seats = template.seats()?;
+
+    sqlx::query(
+        "CREATE TEMP TABLE seat_import (
+             row INTEGER NOT NULL,
+             number INTEGER NOT NULL,
+             category VARCHAR(50) NOT NULL,
+             price DECIMAL(10, 2) NOT NULL
+         ) ON COMMIT DROP"
+    )
+    .execute(&mut *conn)
+    .await?;
+
+    let mut data = String::with_capacity(seats.len() * 24);
+    for seat in &seats {
+        // Категория экранируется по правилам CSV: в кавычках, кавычки удваиваются.
+        let _ = writeln!(
+            data,
+            "{},{},\"{}\",{:.2}",
+            seat.row,
+            seat.number,
+            seat.category.replace('"', "\"\""),
+            seat.price
+        );
+    }
+
+    let mut copy = conn
+        .copy_in_raw("COPY seat_import (row, number, category, price) FROM STDIN WITH (FORMAT csv)")
+        .await?;
+    if let Err(e) = copy.send(data.into_bytes()).await {
+        let _ = copy.abort(e.to_string()).await;
+        return Err(e.into());
+    }
+    copy.finish().await?;
+
+    let created = sqlx::query(
+        "INSERT INTO seats (event_id, section_id, row, number, status, category, price)
+         SELECT $1, $2, row, number, $3, category, price
+         FROM seat_import
+         ON CONFLICT ON CONSTRAINT seats_event_section_row_number_key DO NOTHING"
+    )
+    .bind(event_id)
+    .bind(template.section_id)
+    .bind(SeatStatus::Free)
+    .execute(&mut *conn)
+    .await?
+    .rows_affected();
+
+    Ok(GenerateReport { total: seats.len(), created })
+}
+
+/// Меняет цену всех непроданных мест категории. Места броней, по которым
+/// уже идет оплата, не трогаются: сумма платежа зафиксирована. Возвращает
+/// число измененных мест.
+pub async fn reprice_category(
+    conn: &mut PgConnection,
+    event_id: i64,
+    category: &str,
+    price: f64,
+) -> Result<u64, InventoryError> {
+    validate_price(category, price)?;
+
+    let updated = sqlx::query(
+        "UPDATE seats s
+         SET price = $3
+         WHERE s.event_id = $1
+           AND s.category = $2
+           AND s.status <> $4
+           AND NOT EXISTS (
+               SELECT 1 FROM bookings b
+               WHERE b.id = s.booking_id AND b.status = ANY($5)
+           )"
+    )
+    .bind(event_id)
+    .bind(category)
+    .bind(price)
+    .bind(SeatStatus::Sold)
+    .bind([BookingStatus::PendingPayment, BookingStatus::Paid])
+    .execute(&mut *conn)
+    .await?
+    .rows_affected();
+
+    Ok(updated)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn zone(category: &str, from_row: i32, to_row: i32, seats_per_row: Option<i32>) -> SeatZone {
+        SeatZone { category: category.to_string(), from_row, to_row, seats_per_row }
+    }
+
+    fn template(rows: i32, seats_per_row: i32, zones: Vec<SeatZone>) -> SeatTemplate {
+        SeatTemplate {
+            section_id: None,
+            rows,
+            seats_per_row,
+            zones,
+            prices: HashMap::from([("VIP".to_string(), 15000.0), ("Standard".to_string(), 7000.0)]),
+        }
+    }
+
+    fn assert_invalid(template: &SeatTemplate) {
+        assert!(matches!(template.seats(), Err(InventoryError::InvalidTemplate(_))));
+    }
+
+    #[test]
+    fn expands_zones_row_by_row() {
+        let template = template(4, 3, vec![
+            zone("Standard", 3, 4, Some(2)),
+            zone(" VIP ", 1, 2, None),
+        ]);
+        let seats = template.seats().unwrap();
+
+        let layout: Vec<_> = seats.iter().map(|s| (s.row, s.number, s.category.as_str(), s.price)).collect();
+        assert_eq!(layout, [
+            (1, 1, "VIP", 15000.0), (1, 2, "VIP", 15000.0), (1, 3, "VIP", 15000.0),
+            (2, 1, "VIP", 15000.0), (2, 2, "VIP", 15000.0), (2, 3, "VIP", 15000.0),
+            (3, 1, "Standard", 7000.0), (3, 2, "Standard", 7000.0),
+            (4, 1, "Standard", 7000.0), (4, 2, "Standard", 7000.0),
+        ]);
+    }
+
+    #[test]
+    fn expansion_has_unique_row_and_number() {
+        let seats = template(20, 30, vec![zone("VIP", 1, 5, None), zone("Standard", 6, 20, Some(34))])
+            .seats()
+            .unwrap();
+        assert_eq!(seats.len(), 5 * 30 + 15 * 34);
+
+        let positions: std::collections::HashSet<_> = seats.iter().map(|s| (s.row, s.number)).collect();
+        assert_eq!(positions.len(), seats.len());
+    }
+
+    #[test]
+    fn rejects_rows_outside_exactly_one_zone() {
+        assert_invalid(&template(3, 2, vec![zone("VIP", 1, 2, None)]));
+        assert_invalid(&template(3, 2, vec![zone("VIP", 1, 2, None), zone("Standard", 2, 3, None)]));
+        assert_invalid(&template(3, 2, vec![zone("VIP", 1, 4, None)]));
+        assert_invalid(&template(3, 2, vec![zone("VIP", 0, 3, None)]));
+        assert_invalid(&template(3, 2, vec![zone("VIP", 3, 1, None)]));
+        assert_invalid(&template(3, 2, vec![]));
+    }
+
+    #[test]
+    fn rejects_invalid_sizes_and_prices() {
+        assert_invalid(&template(0, 2, vec![zone("VIP", 1, 1, None)]));
+        assert_invalid(&template(1, 0, vec![zone("VIP", 1, 1, None)]));
+        assert_invalid(&template(1, 2, vec![zone("VIP", 1, 1, Some(0))]));
+        assert_invalid(&template(1, 2, vec![zone("Balcony", 1, 1, None)]));
+        assert_invalid(&template(1, 2, vec![zone(&"x".repeat(MAX_CATEGORY_LEN + 1), 1, 1, None)]));
+        assert_invalid(&template(1000, 101, vec![zone("VIP", 1, 1000, None)]));
+
+        let mut negative = template(1, 2, vec![zone("VIP", 1, 1, None)]);
+        negative.prices.insert("VIP".to_string(), -1.0);
+        assert_invalid(&negative);
+        negative.prices.insert("VIP".to_string(), f64::NAN);
+        assert_invalid(&negative);
+    }
+}
@@ -0,0 +1,238 @@
+//! seat_picker.rs
+//!
+//! Подбор лучших свободных мест для автоматического выбора.
+//!
+//! Лучшими считаются места в ряду ближе к сцене (меньший номер ряда), а в
+//! пределах ряда - ближе к его центру. Соседние места - места одного ряда
+//! одной секции с номерами подряд.
+
+use std::collections::{BTreeMap, HashSet};
+
+use crate::models::{Seat, SeatStatus};
+
+/// Условия подбора мест.
+#[derive(Debug, Clone)]
+pub struct SeatCriteria {
+    pub quantity: usize,
+    pub category: Option<String>,
+    pub max_price: Option<f64>,
+    /// Места должны идти подряд в одном ряду.
+    pub adjacent: bool,
+}
+
+impl SeatCriteria {
+    fn matches(&self, seat: &Seat) -> bool {
+        if let Some(category) = &self.category {
+            if seat.category.as_deref() != Some(category.as_str()) {
+                return false;
+            }
+        }
+        if let Some(max_price) = self.max_price {
+            if !seat.price.is_some_and(|price| price <= max_price) {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+/// Выбирает `quantity` лучших свободных мест. `seats` - карта мест с наложенными
+/// удержаниями (`CacheService::get_seats`), `excluded` - места, которые уже
+/// оказались заняты. Возвращает `None`, если подходящих мест не хватает.
+///
+/// Сначала ищется лучший блок мест подряд; без флага `adjacent` при его
+/// отсутствии берутся лучшие места по отдельности.
+pub fn best_seats(seats: &[Seat], criteria: &SeatCriteria, excluded: &HashSet<i64>) -> Option<Vec<Seat>> {
+    if criteria.quantity == 0 {
+        return None;
+    }
+
+    // Центр ряда считается по всем местам ряда, а не только по свободным.
+    // Храним удвоенное значение, чтобы не уходить в дробные числа.
+    let mut row_bounds: BTreeMap<RowKey, (i32, i32)> = BTreeMap::new();
+    for seat in seats {
+        let bounds = row_bounds.entry(row_key(seat)).or_insert((seat.number, seat.number));
+        bounds.0 = bounds.0.min(seat.number);
+        bounds.1 = bounds.1.max(seat.number);
+    }
+    let center2 = |row: RowKey| row_bounds.get(&row).map_or(0, |(min, max)| min + max);
+
+    let mut rows: BTreeMap<RowKey, Vec<&Seat>> = BTreeMap::new();
+    for seat in seats {
+        if seat.status == SeatStatus::Free && !excluded.contains(&seat.id) && criteria.matches(seat) {
+            rows.entry(row_key(seat)).or_default().push(seat);
+        }
+    }
+    for row in rows.values_mut() {
+        row.sort_by_key(|seat| seat.number);
+    }
+
+    if let Some(block) = best_block(&rows, criteria.quantity, center2) {
+        return Some(block);
+    }
+    if criteria.adjacent {
+        return None;
+    }
+
+    let mut free: Vec<&Seat> = rows.into_values().flatten().collect();
+    if free.len() < criteria.quantity {
+        return None;
+    }
+    free.sort_by_key(|seat| (seat.row, (2 * seat.number - center2(row_key(seat))).abs(), seat.section_id, seat.number));
+    Some(free.into_iter().take(criteria.quantity).cloned().collect())
+}
+
+/// Ряд зала: номер ряда и секция. Порядок ключей - от ближнего ряда к дальнему.
+type RowKey = (i32, Option<i64>);
+
+fn row_key(seat: &Seat) -> RowKey {
+    (seat.row, seat.section_id)
+}
+
+/// Ключ сортировки блока: ряд, удвоенное расстояние до центра ряда, секция, первый номер.
+type BlockKey = (i32, i32, Option<i64>, i32);
+
+/// Лучший блок из `quantity` мест подряд: ближайший ряд, затем ближайший к центру.
+fn best_block(
+    rows: &BTreeMap<RowKey, Vec<&Seat>>,
+    quantity: usize,
+    center2: impl Fn(RowKey) -> i32,
+) -> Option<Vec<Seat>> {
+    let mut best: Option<(BlockKey, &[&Seat])> = None;
+
+    for (&(row, section_id), row_seats) in rows {
+        // Ряды идут по возрастанию: блок в ближнем ряду лучше любого в дальнем.
+        if best.is_some_and(|(best_key, _)| best_key.0 < row) {
+            break;
+        }
+        for window in row_seats.windows(quantity) {
+            let first = window[0].number;
+            let last = window[quantity - 1].number;
+            if (last - first) as usize != quantity - 1 {
+                continue;
+            }
+            let key = (row, (first + last - center2((row, section_id))).abs(), section_id, first);
+            if best.is_none_or(|(best_key, _)| key < best_key) {
+                best = Some((key, window));
+            }
+        }
+    }
+
+    best.map(|(_, window)| window.iter().map(|seat| (*seat).clone()).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn seat(id: i64, row: i32, number: i32) -> Seat {
+        Seat {
+            id,
+            event_id: 1,
+            row,
+            number,
+            section_id: None,
+            status: SeatStatus::Free,
+            booking_id: None,
+            category: None,
+            price: Some(1000.0),
+        }
+    }
+
+    /// Ряд `row` из мест с номерами `numbers`; id = row * 100 + number.
+    fn row(row: i32, numbers: impl IntoIterator<Item = i32>) -> Vec<Seat> {
+        numbers.into_iter().map(|number| seat(i64::from(row * 100 + number), row, number)).collect()
+    }
+
+    fn criteria(quantity: usize, adjacent: bool) -> SeatCriteria {
+        SeatCriteria { quantity, category: None, max_price: None, adjacent }
+    }
+
+    fn ids(seats: Option<Vec<Seat>>) -> Option<Vec<i64>> {
+        seats.map(|seats| seats.into_iter().map(|seat| seat.id).collect())
+    }
+
+    #[test]
+    fn picks_adjacent_block_in_center_of_nearest_row() {
+        let mut seats = row(1, 1..=10);
+        seats.extend(row(2, 1..=10));
+
+        let picked = ids(best_seats(&seats, &criteria(2, true), &HashSet::new()));
+        assert_eq!(picked, Some(vec![105, 106]));
+    }
+
+    #[test]
+    fn skips_row_with_gap_in_block() {
+        // В первом ряду места 3 и 6 заняты: трех мест подряд нет.
+        let mut seats = row(1, 1..=7);
+        for seat in &mut seats {
+            if seat.number == 3 || seat.number == 6 {
+                seat.status = SeatStatus::Reserved;
+            }
+        }
+        seats.extend(row(2, 1..=7));
+
+        let picked = ids(best_seats(&seats, &criteria(3, true), &HashSet::new()));
+        assert_eq!(picked, Some(vec![203, 204, 205]));
+    }
+
+    #[test]
+    fn missing_seat_numbers_break_adjacency() {
+        let seats = row(1, [1, 2, 4, 5]);
+
+        assert_eq!(ids(best_seats(&seats, &criteria(3, true), &HashSet::new())), None);
+        let picked = ids(best_seats(&seats, &criteria(3, false), &HashSet::new())).unwrap();
+        assert_eq!(picked.len(), 3);
+    }
+
+    #[test]
+    fn same_row_in_different_sections_is_not_adjacent() {
+        let mut seats = row(1, [1, 2]);
+        let mut other = row(1, [3, 4]);
+        for seat in &mut other {
+            seat.section_id = Some(2);
+        }
+        seats.extend(other);
+
+        assert_eq!(ids(best_seats(&seats, &criteria(3, true), &HashSet::new())), None);
+    }
+
+    #[test]
+    fn not_enough_free_seats() {
+        let mut seats = row(1, 1..=4);
+        seats[0].status = SeatStatus::Sold;
+        seats[1].status = SeatStatus::Selected;
+
+        assert_eq!(ids(best_seats(&seats, &criteria(3, false), &HashSet::new())), None);
+        assert_eq!(ids(best_seats(&seats, &criteria(0, false), &HashSet::new())), None);
+    }
+
+    #[test]
+    fn excluded_and_filtered_seats_are_not_picked() {
+        let mut seats = row(1, 1..=5);
+        seats[4].category = Some("VIP".to_string());
+        seats[3].price = Some(5000.0);
+        let excluded = HashSet::from([103]);
+
+        let criteria = SeatCriteria { quantity: 2, category: None, max_price: Some(2000.0), adjacent: true };
+        assert_eq!(ids(best_seats(&seats, &criteria, &excluded)), Some(vec![101, 102]));
+
+        let vip = SeatCriteria { quantity: 1, category: Some("VIP".to_string()), max_price: None, adjacent: true };
+        assert_eq!(ids(best_seats(&seats, &vip, &excluded)), Some(vec![105]));
+    }
+
+    #[test]
+    fn separate_seats_prefer_nearest_row_then_center() {
+        let mut seats = row(1, 1..=5);
+        for seat in &mut seats {
+            if seat.number != 1 {
+                seat.status = SeatStatus::Sold;
+            }
+        }
+        seats.extend(row(2, 1..=5));
+        seats[7].status = SeatStatus::Sold; // место 2.3
+
+        let picked = ids(best_seats(&seats, &criteria(3, false), &HashSet::new()));
+        assert_eq!(picked, Some(vec![101, 202, 204]));
+    }
+}